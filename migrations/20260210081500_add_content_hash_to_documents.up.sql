@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE documents ADD COLUMN content_hash CHAR(64) NULL;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE upload_sessions (
+    id VARCHAR(64) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    chunk_count INT NOT NULL DEFAULT 0,
+    received_bytes BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    INDEX idx_upload_sessions_user_id (user_id),
+    INDEX idx_upload_sessions_updated_at (updated_at)
+);
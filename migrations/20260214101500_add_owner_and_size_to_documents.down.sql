@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE documents
+    DROP INDEX idx_documents_user_id,
+    DROP COLUMN size,
+    DROP COLUMN user_id;
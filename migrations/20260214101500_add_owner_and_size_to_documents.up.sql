@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE documents
+    ADD COLUMN user_id BIGINT NULL,
+    ADD COLUMN size BIGINT NOT NULL DEFAULT 0,
+    ADD INDEX idx_documents_user_id (user_id);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE documents ADD COLUMN mime_type VARCHAR(127) NOT NULL DEFAULT 'application/octet-stream';
@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE TABLE document_versions (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    document_id BIGINT NOT NULL,
+    version INT NOT NULL,
+    file_id VARCHAR(255) NOT NULL,
+    size BIGINT NOT NULL DEFAULT 0,
+    content_hash CHAR(64) NULL,
+    mime_type VARCHAR(127) NOT NULL DEFAULT 'application/octet-stream',
+    uploaded_by BIGINT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_document_versions_document_version (document_id, version),
+    INDEX idx_document_versions_file_id (file_id)
+);
+
+ALTER TABLE documents ADD COLUMN current_version INT NOT NULL DEFAULT 1;
+
+-- Every existing document becomes version 1 of itself
+INSERT INTO document_versions (document_id, version, file_id, size, content_hash, mime_type, uploaded_by, created_at)
+SELECT id, 1, file_id, size, content_hash, mime_type, user_id, created_at
+FROM documents;
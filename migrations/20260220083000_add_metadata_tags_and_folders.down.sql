@@ -0,0 +1,12 @@
+-- Add down migration script here
+ALTER TABLE documents
+    DROP INDEX idx_documents_created_at,
+    DROP INDEX idx_documents_mime_type,
+    DROP INDEX idx_documents_folder_id,
+    DROP COLUMN folder_id,
+    DROP COLUMN metadata,
+    DROP COLUMN description;
+
+DROP TABLE document_tags;
+DROP TABLE tags;
+DROP TABLE folders;
@@ -0,0 +1,34 @@
+-- Add up migration script here
+CREATE TABLE folders (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    parent_id BIGINT NULL,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    INDEX idx_folders_user_id (user_id),
+    INDEX idx_folders_parent_id (parent_id)
+);
+
+CREATE TABLE tags (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_tags_name (name)
+);
+
+CREATE TABLE document_tags (
+    document_id BIGINT NOT NULL,
+    tag_id BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (document_id, tag_id),
+    INDEX idx_document_tags_tag_id (tag_id)
+);
+
+ALTER TABLE documents
+    ADD COLUMN description TEXT NULL,
+    ADD COLUMN metadata JSON NULL,
+    ADD COLUMN folder_id BIGINT NULL,
+    ADD INDEX idx_documents_folder_id (folder_id),
+    ADD INDEX idx_documents_mime_type (mime_type),
+    ADD INDEX idx_documents_created_at (created_at);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE document_versions
+    DROP INDEX idx_document_versions_blob_id,
+    DROP COLUMN blob_id;
+
+DROP TABLE blobs;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+CREATE TABLE blobs (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    content_hash CHAR(64) NOT NULL,
+    file_id VARCHAR(255) NOT NULL,
+    public_id VARCHAR(255) NULL,
+    resource_type VARCHAR(20) NOT NULL DEFAULT 'raw',
+    size BIGINT NOT NULL DEFAULT 0,
+    ref_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_blobs_content_hash (content_hash)
+);
+
+ALTER TABLE document_versions
+    ADD COLUMN blob_id BIGINT NULL,
+    ADD INDEX idx_document_versions_blob_id (blob_id);
+
+-- Existing uploads with a hash become blobs, one storage object is kept per hash
+INSERT INTO blobs (content_hash, file_id, size, ref_count)
+SELECT v.content_hash, MIN(v.file_id), MAX(v.size), COUNT(*)
+FROM document_versions v
+JOIN documents d ON d.id = v.document_id
+WHERE v.content_hash IS NOT NULL
+AND d.deleted_at IS NULL
+GROUP BY v.content_hash;
+
+UPDATE document_versions v
+JOIN blobs b ON b.content_hash = v.content_hash
+JOIN documents d ON d.id = v.document_id
+SET v.blob_id = b.id
+WHERE d.deleted_at IS NULL;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE blobs
+    DROP COLUMN thumbnail_public_id,
+    DROP COLUMN thumbnail_file_id;
+
+ALTER TABLE users
+    DROP COLUMN avatar_variants;
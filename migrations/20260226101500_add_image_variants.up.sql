@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE users
+    ADD COLUMN avatar_variants JSON NULL AFTER image;
+
+ALTER TABLE blobs
+    ADD COLUMN thumbnail_file_id VARCHAR(255) NULL AFTER resource_type,
+    ADD COLUMN thumbnail_public_id VARCHAR(255) NULL AFTER thumbnail_file_id;
@@ -0,0 +1,13 @@
+-- Add down migration script here
+DROP TABLE quarantined_files;
+
+ALTER TABLE documents
+    DROP INDEX idx_documents_scan_status,
+    DROP COLUMN scan_status;
+
+ALTER TABLE document_versions
+    DROP COLUMN scan_status;
+
+ALTER TABLE blobs
+    DROP COLUMN scanned_at,
+    DROP COLUMN scan_status;
@@ -0,0 +1,30 @@
+-- Add up migration script here
+ALTER TABLE blobs
+    ADD COLUMN scan_status VARCHAR(16) NOT NULL DEFAULT 'pending' AFTER size,
+    ADD COLUMN scanned_at TIMESTAMP NULL AFTER scan_status;
+
+ALTER TABLE document_versions
+    ADD COLUMN scan_status VARCHAR(16) NOT NULL DEFAULT 'pending';
+
+ALTER TABLE documents
+    ADD COLUMN scan_status VARCHAR(16) NOT NULL DEFAULT 'pending' AFTER mime_type,
+    ADD INDEX idx_documents_scan_status (scan_status);
+
+-- Files stored before scanning existed stay downloadable, an admin rescan gives them a verdict
+UPDATE blobs SET scan_status = 'unscanned';
+UPDATE document_versions SET scan_status = 'unscanned';
+UPDATE documents SET scan_status = 'unscanned';
+
+CREATE TABLE quarantined_files (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    document_id BIGINT NULL,
+    version INT NULL,
+    content_hash CHAR(64) NOT NULL,
+    size BIGINT NOT NULL DEFAULT 0,
+    path VARCHAR(512) NOT NULL,
+    signature VARCHAR(255) NOT NULL,
+    scanner VARCHAR(20) NOT NULL,
+    uploaded_by BIGINT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_quarantined_files_document_id (document_id)
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE documents
+    DROP INDEX ft_documents_name;
+
+DROP TABLE blob_contents;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE blob_contents (
+    blob_id BIGINT PRIMARY KEY,
+    content MEDIUMTEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FULLTEXT INDEX ft_blob_contents_content (content)
+);
+
+ALTER TABLE documents
+    ADD FULLTEXT INDEX ft_documents_name (name);
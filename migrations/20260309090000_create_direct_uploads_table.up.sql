@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE direct_uploads (
+    id VARCHAR(64) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    document_id BIGINT NULL,
+    name VARCHAR(255) NOT NULL,
+    extention VARCHAR(20) NOT NULL,
+    size BIGINT NOT NULL,
+    mime_type VARCHAR(127) NOT NULL,
+    checksum CHAR(64) NOT NULL,
+    backend VARCHAR(20) NOT NULL,
+    object_key VARCHAR(512) NOT NULL,
+    resource_type VARCHAR(20) NOT NULL,
+    multipart_id VARCHAR(255) NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_direct_uploads_user_id (user_id),
+    INDEX idx_direct_uploads_expires_at (expires_at)
+);
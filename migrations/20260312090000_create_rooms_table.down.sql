@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE room_members;
+DROP TABLE rooms;
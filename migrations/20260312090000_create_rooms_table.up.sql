@@ -0,0 +1,22 @@
+-- Add up migration script here
+CREATE TABLE rooms (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    is_private BOOLEAN NOT NULL DEFAULT FALSE,
+    created_by BIGINT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE KEY uq_rooms_name (name)
+);
+
+CREATE TABLE room_members (
+    room_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role VARCHAR(20) NOT NULL DEFAULT 'member',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (room_id, user_id),
+    INDEX idx_room_members_user_id (user_id)
+);
+
+-- every connection is subscribed to the general room
+INSERT INTO rooms (name, is_private) VALUES ('general', FALSE);
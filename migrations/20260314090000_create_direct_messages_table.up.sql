@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE direct_messages (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    sender_id BIGINT NOT NULL,
+    recipient_id BIGINT NOT NULL,
+    message TEXT NOT NULL,
+    delivered_at TIMESTAMP NULL,
+    read_at TIMESTAMP NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_direct_messages_recipient (recipient_id, delivered_at, id),
+    INDEX idx_direct_messages_conversation (sender_id, recipient_id, id)
+);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE pubsub_messages (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    origin VARCHAR(64) NOT NULL,
+    payload MEDIUMTEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_pubsub_messages_created_at (created_at)
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TABLE chat_sanctions;
+
+ALTER TABLE chat_messages
+    DROP COLUMN deleted_by,
+    DROP COLUMN deleted_at,
+    DROP COLUMN edited_at;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+ALTER TABLE chat_messages
+    ADD COLUMN edited_at TIMESTAMP NULL,
+    ADD COLUMN deleted_at TIMESTAMP NULL,
+    ADD COLUMN deleted_by BIGINT NULL;
+
+-- one active mute and one active ban per user, a NULL expiry lasts until lifted
+CREATE TABLE chat_sanctions (
+    user_id BIGINT NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    reason VARCHAR(255) NULL,
+    created_by BIGINT NOT NULL,
+    expires_at TIMESTAMP NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, kind)
+);
@@ -0,0 +1,33 @@
+use std::env;
+
+// origin of the frontend dev server, used when CORS_ALLOWED_ORIGINS is not set
+const DEFAULT_ALLOWED_ORIGINS: &str = "http://localhost:5173";
+
+// origins browsers may call the API from (CORS_ALLOWED_ORIGINS, comma separated, "*" allows any)
+pub fn allowed_origins() -> Vec<String> {
+    env::var("CORS_ALLOWED_ORIGINS")
+        .unwrap_or_else(|_| DEFAULT_ALLOWED_ORIGINS.to_string())
+        .split(',')
+        .map(normalize_origin)
+        .filter(|origin| !origin.is_empty())
+        .collect()
+}
+
+// function for comparing origins the way browsers send them, without a trailing slash
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_lowercase()
+}
+
+// function for checking an Origin header against a list of allowed origins
+pub fn origin_allowed(allowed: &[String], origin: &str) -> bool {
+    let origin = normalize_origin(origin);
+
+    allowed
+        .iter()
+        .any(|allowed| allowed == "*" || *allowed == origin)
+}
+
+// function for checking an Origin header against the allowlist
+pub fn is_allowed_origin(origin: &str) -> bool {
+    origin_allowed(&allowed_origins(), origin)
+}
@@ -20,3 +20,22 @@ pub async fn connect() -> MySqlPool {
         }
     }
 }
+
+// function for connecting to the database of the ignored database tests (TEST_DATABASE_URL),
+// the migrations are run first so the schema matches the queries
+#[cfg(test)]
+pub async fn connect_test() -> MySqlPool {
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must set");
+
+    let pool = MySqlPoolOptions::new()
+        .max_connections(5)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to test database");
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    pool
+}
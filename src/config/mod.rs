@@ -1 +1,8 @@
+pub mod cors;
 pub mod database;
+pub mod moderation;
+pub mod pubsub;
+pub mod scanner;
+pub mod storage;
+pub mod upload;
+pub mod websocket;
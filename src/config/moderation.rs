@@ -0,0 +1,85 @@
+use crate::utils::moderation::{
+    ChatRules, ContentFilter, LinkPolicy, RateLimit, WordAction, WordFilter,
+};
+use std::env;
+use std::time::Duration;
+
+// longest chat or direct message in characters (CHAT_MAX_LENGTH, default 2000)
+pub fn max_message_length() -> usize {
+    env::var("CHAT_MAX_LENGTH")
+        .ok()
+        .and_then(|length| length.parse::<usize>().ok())
+        .filter(|length| *length > 0)
+        .unwrap_or(2000)
+}
+
+// messages a user may send per window (CHAT_RATE_LIMIT, default 10, CHAT_RATE_WINDOW, seconds, default 10)
+pub fn message_rate_limit() -> RateLimit {
+    let messages = env::var("CHAT_RATE_LIMIT")
+        .ok()
+        .and_then(|messages| messages.parse::<u32>().ok())
+        .filter(|messages| *messages > 0)
+        .unwrap_or(10);
+    let seconds = env::var("CHAT_RATE_WINDOW")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(10);
+
+    RateLimit {
+        messages,
+        window: Duration::from_secs(seconds),
+    }
+}
+
+// function for parsing a comma separated list into lowercase entries
+pub fn parse_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|entry| entry.trim().to_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+// function for parsing a link policy (allow, deny or domains), anything unknown allows links
+pub fn parse_link_policy(policy: &str, domains: &str) -> LinkPolicy {
+    match policy.trim().to_lowercase().as_str() {
+        "deny" => LinkPolicy::Deny,
+        "domains" => LinkPolicy::Domains(parse_list(domains)),
+        _ => LinkPolicy::Allow,
+    }
+}
+
+// function for building the filters every chat message passes:
+// blocked words (CHAT_BLOCKED_WORDS, comma separated, CHAT_WORD_FILTER, mask or reject, default mask)
+// and links (CHAT_LINK_POLICY, allow, deny or domains listed in CHAT_LINK_DOMAINS, default allow)
+pub fn content_filters() -> Vec<Box<dyn ContentFilter>> {
+    let mut filters: Vec<Box<dyn ContentFilter>> = Vec::new();
+
+    let words = parse_list(&env::var("CHAT_BLOCKED_WORDS").unwrap_or_default());
+    if !words.is_empty() {
+        let action = match env::var("CHAT_WORD_FILTER").unwrap_or_default().trim() {
+            "reject" => WordAction::Reject,
+            _ => WordAction::Mask,
+        };
+        filters.push(Box::new(WordFilter { words, action }));
+    }
+
+    let links = parse_link_policy(
+        &env::var("CHAT_LINK_POLICY").unwrap_or_default(),
+        &env::var("CHAT_LINK_DOMAINS").unwrap_or_default(),
+    );
+    if links != LinkPolicy::Allow {
+        filters.push(Box::new(links));
+    }
+
+    filters
+}
+
+// function for building the chat rules from the configuration, done once when the server starts
+pub fn chat_rules() -> ChatRules {
+    ChatRules {
+        max_length: max_message_length(),
+        rate_limit: message_rate_limit(),
+        filters: content_filters(),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::utils::pubsub::{MemoryPubSub, MySqlPubSub, PubSub, RedisPubSub};
+use sqlx::MySqlPool;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+// function for building the transport selected by PUBSUB_BACKEND (memory, mysql or redis, default memory),
+// memory keeps messages in this instance, the others share them with every instance
+pub fn pubsub(db: &MySqlPool) -> Arc<dyn PubSub> {
+    match env::var("PUBSUB_BACKEND")
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "mysql" => Arc::new(MySqlPubSub {
+            db: db.clone(),
+            poll_interval: pubsub_poll_interval(),
+        }),
+        "redis" => Arc::new(RedisPubSub::new(&redis_address(), &pubsub_channel())),
+        _ => Arc::new(MemoryPubSub::new()),
+    }
+}
+
+// how often the mysql transport looks for new messages (PUBSUB_POLL_INTERVAL, milliseconds, default 500)
+pub fn pubsub_poll_interval() -> Duration {
+    let millis = env::var("PUBSUB_POLL_INTERVAL")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|millis| *millis > 0)
+        .unwrap_or(500);
+
+    Duration::from_millis(millis)
+}
+
+// address of the Redis server (REDIS_ADDRESS, host:port, default 127.0.0.1:6379)
+pub fn redis_address() -> String {
+    env::var("REDIS_ADDRESS").unwrap_or_else(|_| "127.0.0.1:6379".to_string())
+}
+
+// Redis channel the instances share (PUBSUB_CHANNEL, default chat)
+pub fn pubsub_channel() -> String {
+    env::var("PUBSUB_CHANNEL").unwrap_or_else(|_| "chat".to_string())
+}
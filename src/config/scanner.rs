@@ -0,0 +1,46 @@
+use crate::utils::malware_scanner::{
+    ClamdAddress, ClamdScanner, DisabledScanner, EicarScanner, Scanner,
+};
+use std::env;
+use std::time::Duration;
+
+// function for building the scanner selected by MALWARE_SCANNER (clamd, eicar or none, default none)
+pub fn scanner() -> Box<dyn Scanner> {
+    match env::var("MALWARE_SCANNER")
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "clamd" => Box::new(ClamdScanner {
+            address: ClamdAddress::parse(&clamd_address()),
+            timeout: clamd_timeout(),
+        }),
+        "eicar" => Box::new(EicarScanner),
+        _ => Box::new(DisabledScanner),
+    }
+}
+
+// whether files stored while no scanner was configured may be downloaded
+// (SERVE_UNSCANNED_FILES, true or false, default false), they are refused until a rescan otherwise
+pub fn serve_unscanned() -> bool {
+    env::var("SERVE_UNSCANNED_FILES")
+        .map(|value| value.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+// address of the clamd daemon (CLAMD_ADDRESS, host:port or a unix socket path, default 127.0.0.1:3310)
+pub fn clamd_address() -> String {
+    env::var("CLAMD_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3310".to_string())
+}
+
+// how long a single clamd scan may take (CLAMD_TIMEOUT, seconds, default 60)
+pub fn clamd_timeout() -> Duration {
+    let seconds = env::var("CLAMD_TIMEOUT")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(60);
+
+    Duration::from_secs(seconds)
+}
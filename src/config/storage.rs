@@ -0,0 +1,69 @@
+use std::env;
+
+pub const CLOUDINARY: &str = "cloudinary";
+pub const S3: &str = "s3";
+
+// smallest part S3 accepts in a multipart upload, except for the last one
+pub const S3_MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+// most parts S3 accepts in a multipart upload
+pub const S3_MAX_PARTS: u64 = 10_000;
+
+// bucket on an S3 compatible server such as MinIO, objects are addressed path style
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+// function for getting where documents are stored (STORAGE_BACKEND, cloudinary or s3, default cloudinary)
+pub fn storage_backend() -> &'static str {
+    match env::var("STORAGE_BACKEND")
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        S3 => S3,
+        _ => CLOUDINARY,
+    }
+}
+
+// function for reading the S3 settings (S3_ENDPOINT, S3_BUCKET, S3_REGION, S3_ACCESS_KEY, S3_SECRET_KEY),
+// None when no endpoint or bucket is configured
+pub fn s3_config() -> Option<S3Config> {
+    let endpoint = env::var("S3_ENDPOINT")
+        .ok()
+        .filter(|e| !e.trim().is_empty())?;
+    let bucket = env::var("S3_BUCKET")
+        .ok()
+        .filter(|b| !b.trim().is_empty())?;
+
+    Some(S3Config {
+        endpoint: endpoint.trim().trim_end_matches('/').to_string(),
+        bucket: bucket.trim().to_string(),
+        region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+        access_key: env::var("S3_ACCESS_KEY").unwrap_or_default(),
+        secret_key: env::var("S3_SECRET_KEY").unwrap_or_default(),
+    })
+}
+
+// size of the parts a direct S3 upload is split into (S3_PART_SIZE, default 64 MiB, at least 5 MiB)
+pub fn s3_part_size() -> u64 {
+    env::var("S3_PART_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<u64>().ok())
+        .unwrap_or(64 * 1024 * 1024)
+        .max(S3_MIN_PART_SIZE)
+}
+
+// how long presigned upload parameters stay valid (DIRECT_UPLOAD_TTL, seconds, default 3600)
+pub fn direct_upload_ttl() -> i64 {
+    env::var("DIRECT_UPLOAD_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse::<i64>().ok())
+        .filter(|ttl| *ttl > 0)
+        .unwrap_or(3600)
+}
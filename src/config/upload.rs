@@ -0,0 +1,85 @@
+use std::env;
+
+// MIME types accepted when UPLOAD_ALLOWED_TYPES is not set
+const DEFAULT_ALLOWED_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/heic",
+    "image/heif",
+    "application/pdf",
+    "application/msword",
+    "application/vnd.ms-excel",
+    "application/vnd.ms-powerpoint",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/zip",
+    "text/plain",
+];
+
+// MIME types a wildcard never matches, SVG can carry scripts so it has to be listed by name
+const WILDCARD_EXCLUDED_TYPES: &[&str] = &["image/svg+xml"];
+
+// function for parsing a byte size, anything that is not a whole number falls back to the default
+pub fn parse_bytes(value: Option<&str>, default: u64) -> u64 {
+    value
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+// function for reading a byte size from the environment with a default
+fn env_bytes(key: &str, default: u64) -> u64 {
+    parse_bytes(env::var(key).ok().as_deref(), default)
+}
+
+// maximum size of a single chunk (UPLOAD_MAX_CHUNK_SIZE, default 10 MiB)
+pub fn max_chunk_size() -> u64 {
+    env_bytes("UPLOAD_MAX_CHUNK_SIZE", 10 * 1024 * 1024)
+}
+
+// maximum size of a whole file (UPLOAD_MAX_FILE_SIZE, default 1 GiB)
+pub fn max_file_size() -> u64 {
+    env_bytes("UPLOAD_MAX_FILE_SIZE", 1024 * 1024 * 1024)
+}
+
+// storage quota of a single user (UPLOAD_USER_QUOTA, default 5 GiB)
+pub fn user_quota() -> u64 {
+    env_bytes("UPLOAD_USER_QUOTA", 5 * 1024 * 1024 * 1024)
+}
+
+// MIME types accepted for upload (UPLOAD_ALLOWED_TYPES, comma separated, "image/*" style wildcards)
+pub fn allowed_types() -> Vec<String> {
+    parse_allowed_types(
+        &env::var("UPLOAD_ALLOWED_TYPES").unwrap_or_else(|_| DEFAULT_ALLOWED_TYPES.join(",")),
+    )
+}
+
+// function for parsing a comma separated list of MIME types into lowercase entries
+pub fn parse_allowed_types(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|file_type| file_type.trim().to_lowercase())
+        .filter(|file_type| !file_type.is_empty())
+        .collect()
+}
+
+// function for checking a detected MIME type against the allowlist
+pub fn is_allowed_type(mime_type: &str) -> bool {
+    type_in_allowlist(&allowed_types(), mime_type)
+}
+
+// function for checking a MIME type against a list, "image/*" allows every image type but SVG
+pub fn type_in_allowlist(allowed: &[String], mime_type: &str) -> bool {
+    allowed
+        .iter()
+        .any(|allowed| match allowed.strip_suffix("/*") {
+            Some(prefix) => {
+                !WILDCARD_EXCLUDED_TYPES.contains(&mime_type)
+                    && mime_type
+                        .split_once('/')
+                        .is_some_and(|(kind, _)| kind == prefix)
+            }
+            None => allowed == mime_type,
+        })
+}
@@ -0,0 +1,58 @@
+use std::{env, time::Duration};
+
+// what a connection does with messages for a client that reads slower than they arrive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlowConsumerPolicy {
+    // rooms wait for the queue, a room that falls behind is replayed from its history
+    Wait,
+    // messages for a full queue are dropped, the room is replayed once the queue drains
+    Drop,
+    // the connection is closed, the client reconnects and resumes from its last id
+    Disconnect,
+}
+
+// function for parsing a slow consumer policy, anything unknown waits
+pub fn parse_slow_consumer_policy(policy: &str) -> SlowConsumerPolicy {
+    match policy.trim().to_lowercase().as_str() {
+        "drop" => SlowConsumerPolicy::Drop,
+        "disconnect" => SlowConsumerPolicy::Disconnect,
+        _ => SlowConsumerPolicy::Wait,
+    }
+}
+
+// function for getting the slow consumer policy (WS_SLOW_CONSUMER, wait, drop or disconnect, default wait)
+pub fn slow_consumer_policy() -> SlowConsumerPolicy {
+    parse_slow_consumer_policy(&env::var("WS_SLOW_CONSUMER").unwrap_or_default())
+}
+
+// messages queued for the socket of one connection (WS_OUTBOUND_BUFFER, default 64)
+pub fn outbound_buffer() -> usize {
+    env::var("WS_OUTBOUND_BUFFER")
+        .ok()
+        .and_then(|size| size.parse::<usize>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(64)
+}
+
+// how often the server pings a connection (WS_PING_INTERVAL, seconds, default 30)
+pub fn ping_interval() -> Duration {
+    let seconds = env::var("WS_PING_INTERVAL")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(30);
+
+    Duration::from_secs(seconds)
+}
+
+// how long a connection may stay silent before it is closed (WS_IDLE_TIMEOUT, seconds, default 90),
+// never shorter than the ping interval so a pong can arrive
+pub fn idle_timeout() -> Duration {
+    let timeout = env::var("WS_IDLE_TIMEOUT")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(90));
+
+    timeout.max(ping_interval())
+}
@@ -41,7 +41,7 @@ pub async fn login(
     // Get user by email
     let user = sqlx::query_as!(
         User,
-        "SELECT id, name, email, password, created_at, updated_at, image, deleted_at FROM users WHERE email = ?",
+        "SELECT id, name, email, password, created_at, updated_at, image, avatar_variants, deleted_at FROM users WHERE email = ?",
         payload.email
     )
     .fetch_one(&db)
@@ -0,0 +1,313 @@
+use crate::{
+    handlers::room_handler::MAX_HISTORY_LIMIT,
+    schemas::{
+        direct_message_schema::{DirectHistoryResponse, DirectMessage},
+        document_schema::Pagination,
+        message_schema::{ServerMessage, UnreadCount},
+        room_schema::HistoryQuery,
+    },
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+// function for finding the name of a user that may receive direct messages
+pub async fn find_user_name(db: &MySqlPool, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+    let user = sqlx::query!(
+        "SELECT name FROM users WHERE id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(user.map(|user| user.name))
+}
+
+// function for storing a direct message, returns its id
+pub async fn store_direct_message(
+    db: &MySqlPool,
+    from_user_id: i64,
+    to_user_id: i64,
+    message: &str,
+    time: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO direct_messages (sender_id, recipient_id, message, created_at) VALUES (?, ?, ?, ?)",
+        from_user_id,
+        to_user_id,
+        message,
+        time
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.last_insert_id() as i64)
+}
+
+// function for loading the direct messages no connection of a user received yet, oldest first
+pub async fn undelivered_messages(
+    db: &MySqlPool,
+    user_id: i64,
+    limit: i64,
+) -> Result<Vec<DirectMessage>, sqlx::Error> {
+    sqlx::query_as!(
+        DirectMessage,
+        "
+        SELECT m.id, m.sender_id AS from_user_id, COALESCE(u.name, '') AS `from_username!: String`,
+            m.recipient_id AS to_user_id, m.message, m.delivered_at, m.read_at, m.created_at
+        FROM direct_messages m
+        LEFT JOIN users u ON u.id = m.sender_id
+        WHERE m.recipient_id = ?
+        AND m.delivered_at IS NULL
+        ORDER BY m.id
+        LIMIT ?
+        ",
+        user_id,
+        limit
+    )
+    .fetch_all(db)
+    .await
+}
+
+// function for marking direct messages as delivered, returns how many were not marked before
+pub async fn mark_delivered(
+    db: &MySqlPool,
+    ids: &[i64],
+    time: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new("UPDATE direct_messages SET delivered_at = ");
+    builder.push_bind(time);
+    builder.push(" WHERE delivered_at IS NULL AND id IN (");
+    let mut separated = builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    let result = builder.build().execute(db).await?;
+    Ok(result.rows_affected())
+}
+
+// function for marking the direct messages from a user as read up to an id,
+// a read message counts as delivered too, returns how many were marked
+pub async fn mark_read(
+    db: &MySqlPool,
+    user_id: i64,
+    from_user_id: i64,
+    last_id: i64,
+    time: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        UPDATE direct_messages
+        SET read_at = ?, delivered_at = COALESCE(delivered_at, ?)
+        WHERE recipient_id = ?
+        AND sender_id = ?
+        AND id <= ?
+        AND read_at IS NULL
+        ",
+        time,
+        time,
+        user_id,
+        from_user_id,
+        last_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// function for counting the unread direct messages of a user per sender, latest first
+pub async fn unread_counts(db: &MySqlPool, user_id: i64) -> Result<Vec<UnreadCount>, sqlx::Error> {
+    sqlx::query_as!(
+        UnreadCount,
+        "
+        SELECT m.sender_id AS user_id, COALESCE(u.name, '') AS `username!: String`,
+            COUNT(*) AS `count!: i64`, MAX(m.id) AS `last_id!: i64`
+        FROM direct_messages m
+        LEFT JOIN users u ON u.id = m.sender_id
+        WHERE m.recipient_id = ?
+        AND m.read_at IS NULL
+        GROUP BY m.sender_id, u.name
+        ORDER BY MAX(m.id) DESC
+        ",
+        user_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+// function for turning a stored direct message into the message clients received live
+pub fn direct_server_message(message: DirectMessage) -> ServerMessage {
+    ServerMessage::DirectMessage {
+        id: message.id,
+        from_user_id: message.from_user_id,
+        from_username: message.from_username,
+        to_user_id: message.to_user_id,
+        message: message.message,
+        time: message.created_at,
+    }
+}
+
+// function for grouping delivered messages by their sender, each sender gets one receipt
+pub fn delivered_by_sender(messages: &[DirectMessage]) -> BTreeMap<i64, Vec<i64>> {
+    let mut senders: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    for message in messages {
+        senders
+            .entry(message.from_user_id)
+            .or_default()
+            .push(message.id);
+    }
+
+    senders
+}
+
+// function for listing the unread direct messages of the user per sender
+pub async fn unread(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+) -> HandlerResponse {
+    match unread_counts(&db, claims.sub).await {
+        Ok(counts) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(
+                "Unread Direct Messages",
+                json!(counts),
+            )),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to count unread messages: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+// function for paging through the direct messages with a user, newest first
+pub async fn conversation(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<i64>,
+    Query(query): Query<HistoryQuery>,
+) -> HandlerResponse {
+    match find_user_name(&db, user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::error("User not found")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to find user: {}", e))),
+            );
+        }
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_HISTORY_LIMIT);
+    let offset = (page - 1) * limit;
+    let after_id = query.after_id.unwrap_or(0);
+
+    let total = match sqlx::query!(
+        "
+        SELECT COUNT(*) AS `total!: i64`
+        FROM direct_messages
+        WHERE ((sender_id = ? AND recipient_id = ?) OR (sender_id = ? AND recipient_id = ?))
+        AND id > ?
+        ",
+        claims.sub,
+        user_id,
+        user_id,
+        claims.sub,
+        after_id
+    )
+    .fetch_one(&db)
+    .await
+    {
+        Ok(row) => row.total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count messages: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let messages = match sqlx::query_as!(
+        DirectMessage,
+        "
+        SELECT m.id, m.sender_id AS from_user_id, COALESCE(u.name, '') AS `from_username!: String`,
+            m.recipient_id AS to_user_id, m.message, m.delivered_at, m.read_at, m.created_at
+        FROM direct_messages m
+        LEFT JOIN users u ON u.id = m.sender_id
+        WHERE ((m.sender_id = ? AND m.recipient_id = ?) OR (m.sender_id = ? AND m.recipient_id = ?))
+        AND m.id > ?
+        ORDER BY m.id DESC
+        LIMIT ? OFFSET ?
+        ",
+        claims.sub,
+        user_id,
+        user_id,
+        claims.sub,
+        after_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch messages: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let total_page = (total as f64 / limit as f64).ceil() as i64;
+    let response = DirectHistoryResponse {
+        data: messages,
+        pagination: Pagination {
+            page,
+            limit,
+            total,
+            total_page,
+        },
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Direct Messages", json!(response))),
+    )
+}
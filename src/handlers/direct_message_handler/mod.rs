@@ -0,0 +1,3 @@
+pub mod direct_message_handler;
+
+pub use direct_message_handler::*;
@@ -0,0 +1,51 @@
+use super::*;
+
+// Helper function to create a stored direct message
+fn create_test_message(id: i64, from_user_id: i64) -> DirectMessage {
+    DirectMessage {
+        id,
+        from_user_id,
+        from_username: format!("user{}", from_user_id),
+        to_user_id: 9,
+        message: "halo".to_string(),
+        delivered_at: None,
+        read_at: None,
+        created_at: None,
+    }
+}
+
+// Test a stored direct message is sent like a live one
+#[tokio::test]
+async fn test_direct_server_message() {
+    match direct_server_message(create_test_message(4, 2)) {
+        ServerMessage::DirectMessage {
+            id,
+            from_user_id,
+            from_username,
+            to_user_id,
+            message,
+            ..
+        } => {
+            assert_eq!((id, from_user_id, to_user_id), (4, 2, 9));
+            assert_eq!(from_username, "user2");
+            assert_eq!(message, "halo");
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+// Test delivery receipts are grouped per sender in id order
+#[tokio::test]
+async fn test_delivered_by_sender() {
+    let messages = vec![
+        create_test_message(1, 3),
+        create_test_message(2, 2),
+        create_test_message(5, 3),
+    ];
+
+    let senders = delivered_by_sender(&messages);
+    assert_eq!(senders.len(), 2);
+    assert_eq!(senders[&2], vec![2]);
+    assert_eq!(senders[&3], vec![1, 5]);
+    assert!(delivered_by_sender(&[]).is_empty());
+}
@@ -0,0 +1,3913 @@
+use crate::{
+    config::{
+        scanner::{scanner, serve_unscanned},
+        storage::{S3, S3_MAX_PARTS, direct_upload_ttl, s3_config, s3_part_size, storage_backend},
+        upload::{is_allowed_type, max_chunk_size, max_file_size, user_quota},
+    },
+    handlers::upload_handler::{
+        CloudinaryResponse, DOCUMENT_FOLDER, THUMBNAIL_FOLDER, cloudinary_resource,
+        cloudinary_upload_params, detect_file_type, fetch_from_storage, s3_complete_multipart,
+        s3_create_multipart, s3_object_key, s3_object_size, s3_object_url, sniff_file,
+        upload_bytes_to_storage, upload_to_storage,
+    },
+    middlewares::admin_middleware::is_admin,
+    schemas::{
+        document_schema::{
+            CompletePayload, DirectUploadConfirm, DirectUploadRequest, Document, DocumentQuery,
+            DocumentResponse, DocumentSearchResponse, DocumentSearchResult,
+            DocumentVersionResponse, DownloadQuery, Pagination, RenameRequest, RescanQuery,
+            SearchQuery, SignedDownloadQuery, ZipDownloadRequest,
+        },
+        upload_schema::{
+            DedupStats, QuarantinedFileResponse, QuotaResponse, UploadSessionResponse,
+        },
+    },
+    utils::{
+        checksum::{checksum_matches, is_sha256_hex, sha256_hex},
+        events::{Event, EventBus},
+        file_type::{OCTET_STREAM, extension_for, is_container, resource_type_for},
+        image_pipeline::{THUMBNAIL_MAX_SOURCE_SIZE, document_thumbnail},
+        jwt::Claims,
+        malware_scanner::{
+            DisabledScanner, SCAN_CLEAN, SCAN_INFECTED, SCAN_PENDING, SCAN_UNSCANNED, ScanResult,
+            Scanner,
+        },
+        response::ApiResponse,
+        search::{SNIPPET_RADIUS, escape_like, highlight, search_terms, snippet},
+        signed_url::{signed_path, verify as verify_signed_path},
+        storage::{
+            destroy_storage_object, discard_direct_object, s3_abort_multipart, s3_presigned_url,
+        },
+        text_extract::{TEXT_EXTRACT_MAX_SOURCE_SIZE, extract_text, is_extractable},
+        upload_janitor::{self, upload_session_ttl},
+        upload_path::{
+            MERGED_DIR, QUARANTINE_DIR, chunk_dir, chunk_path, generate_upload_id, merged_path,
+            parse_chunk_index, sanitize_file_stem, upload_root, validate_upload_id,
+        },
+        zip_stream::{UniqueNames, ZipStream, entry_name},
+    },
+};
+use axum::{
+    Extension, Json,
+    body::{Body, Bytes},
+    extract::{Multipart, Path, Query},
+    http::{HeaderMap, HeaderName, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use futures::{SinkExt, StreamExt, channel::mpsc};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::{MySql, MySqlConnection, MySqlPool, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use stringcase::snake_case;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
+};
+use validator::Validate;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+// function for checking that an upload session belongs to the user, optionally creating it,
+// returns the number of bytes received so far
+async fn claim_upload_session(
+    db: &MySqlPool,
+    upload_id: &str,
+    user_id: i64,
+    create: bool,
+) -> Result<i64, HandlerResponse> {
+    // Parallel first chunks of one upload all try to create the session, only one insert wins
+    // and the owner is checked below against whichever did
+    if create {
+        if let Err(e) = sqlx::query!(
+            "INSERT IGNORE INTO upload_sessions (id, user_id) VALUES (?, ?)",
+            upload_id,
+            user_id
+        )
+        .execute(db)
+        .await
+        {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to create upload session: {}",
+                    e
+                ))),
+            ));
+        }
+    }
+
+    let session = match sqlx::query!(
+        "SELECT user_id, received_bytes FROM upload_sessions WHERE id = ?",
+        upload_id
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(session) => session,
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch upload session: {}",
+                    e
+                ))),
+            ));
+        }
+    };
+
+    match session {
+        Some(session) if session.user_id == user_id => Ok(session.received_bytes),
+        Some(_) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error("Upload session belongs to another user")),
+        )),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error(
+                "Upload session not found. Please upload chunks first.",
+            )),
+        )),
+    }
+}
+
+pub async fn upload_chunk(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    mut multipart: Multipart,
+) -> HandlerResponse {
+    let mut file_id = String::new();
+    let mut chunk_index = String::new();
+    let mut checksum = String::new();
+    let mut data = Vec::new();
+
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name().unwrap_or_default() {
+            "file_id" => file_id = field.text().await.unwrap_or_default(),
+            "chunk_index" => chunk_index = field.text().await.unwrap_or_default(),
+            "checksum" => checksum = field.text().await.unwrap_or_default(),
+            "data" => match field.bytes().await {
+                Ok(bytes) => data = bytes.to_vec(),
+                Err(e) => {
+                    // Body limit violations come back as 413 from the extractor
+                    return (
+                        e.status(),
+                        Json(ApiResponse::error(&format!(
+                            "Failed to read chunk {}: {}",
+                            chunk_index, e
+                        ))),
+                    );
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Validate client supplied identifiers before they are used to build any path
+    let root = upload_root();
+    let chunk_index = match parse_chunk_index(chunk_index.trim()) {
+        Ok(index) => index,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+        }
+    };
+    let (dir, path) = match (
+        chunk_dir(&root, &file_id),
+        chunk_path(&root, &file_id, chunk_index),
+    ) {
+        (Ok(dir), Ok(path)) => (dir, path),
+        (Err(e), _) | (_, Err(e)) => {
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+        }
+    };
+
+    // Enforce the chunk size limit and the allowed file types
+    if data.len() as u64 > max_chunk_size() {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(ApiResponse::error(&format!(
+                "Chunk {} exceeds the maximum chunk size of {} bytes",
+                chunk_index,
+                max_chunk_size()
+            ))),
+        );
+    }
+    if chunk_index == 0 {
+        // ZIP and OLE containers are only classified once the whole file is merged
+        let file_type = detect_file_type(&data);
+        if !is_container(&file_type) && !is_allowed_type(&file_type) {
+            return type_not_allowed(&file_type);
+        }
+    }
+
+    // Chunk checksum is required so a corrupted chunk never reaches the merge
+    if !is_sha256_hex(checksum.trim()) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::error(&format!(
+                "Checksum SHA-256 wajib diisi untuk chunk {}",
+                chunk_index
+            ))),
+        );
+    }
+
+    let actual = sha256_hex(&data);
+    if !checksum_matches(&checksum, &actual) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: format!("Checksum mismatch on chunk {}", chunk_index),
+                data: Some(json!({
+                    "chunk_index": chunk_index,
+                    "expected": checksum.trim().to_lowercase(),
+                    "actual": actual,
+                })),
+            }),
+        );
+    }
+
+    // Only the user who started the upload may add chunks to it
+    let received_bytes = match claim_upload_session(&db, &file_id, claims.sub, true).await {
+        Ok(received_bytes) => received_bytes,
+        Err(response) => return response,
+    };
+
+    // A retried chunk replaces the previous one, only count the difference
+    let previous_size = fs::metadata(&path).await.map(|m| m.len()).ok();
+    let added_bytes = data.len() as i64 - previous_size.unwrap_or(0) as i64;
+
+    // Enforce the file size limit and the user's storage quota from the session
+    if (received_bytes + added_bytes) as u64 > max_file_size() {
+        return file_too_large();
+    }
+    let (used, in_flight) = match storage_usage(&db, claims.sub).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check storage quota: {}",
+                    e
+                ))),
+            );
+        }
+    };
+    if let Err(response) = check_quota(user_quota(), used, in_flight, added_bytes) {
+        return response;
+    }
+
+    // // TODO: upload chunks to directory
+    if let Err(e) = fs::create_dir_all(&dir).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create upload directory: {}",
+                e
+            ))),
+        );
+    }
+
+    let mut file = match fs::File::create(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to store chunk: {}", e))),
+            );
+        }
+    };
+    if let Err(e) = file.write_all(&data).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!("Failed to store chunk: {}", e))),
+        );
+    }
+    drop(file); // Close the file handle
+
+    // Keep the verified checksum next to the chunk so the merge can re-check it
+    if let Err(e) = fs::write(path.with_extension("sha256"), &actual).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to store chunk checksum: {}",
+                e
+            ))),
+        );
+    }
+
+    // Track progress on the session, this also keeps it alive for the janitor
+    let new_chunks: i32 = if previous_size.is_some() { 0 } else { 1 };
+    if let Err(e) = sqlx::query!(
+        "
+        UPDATE upload_sessions
+        SET chunk_count = chunk_count + ?, received_bytes = received_bytes + ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        ",
+        new_chunks,
+        added_bytes,
+        file_id
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to update upload session: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Chunk uploaded",
+            json!({ "chunk_index": chunk_index, "checksum": actual }),
+        )),
+    )
+}
+
+// function for mapping a failed save to a response
+fn save_error_response(e: SaveError) -> HandlerResponse {
+    match e {
+        SaveError::DocumentNotFound => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Document not found")),
+        ),
+        SaveError::BlobRemoved => (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error(
+                "Stored file was removed while saving, please try again",
+            )),
+        ),
+        SaveError::Database(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to save document: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+pub async fn complete_upload(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Extension(events): Extension<EventBus>,
+    axum::Json(payload): axum::Json<CompletePayload>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        let mut field_errors: HashMap<String, Vec<String>> = HashMap::new();
+
+        // Collect All Validation Errors
+        for (field, errors) in errors.field_errors() {
+            let message = errors
+                .iter()
+                .filter_map(|e| e.message.as_ref())
+                .map(|m| m.to_string())
+                .collect::<Vec<String>>();
+
+            field_errors.insert(field.to_string(), message);
+        }
+
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: "Validation failed".to_string(),
+                data: Some(json!(field_errors)),
+            }),
+        );
+    }
+
+    // Resolve every path under the upload root from validated identifiers only
+    let root = upload_root();
+    if let Err(e) = validate_upload_id(&payload.file_id) {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+    }
+    if let Err(response) = claim_upload_session(&db, &payload.file_id, claims.sub, false).await {
+        return response;
+    }
+
+    // A new version can only be added to a document the user has access to
+    if let Some(document_id) = payload.document_id {
+        let document = match find_document(&db, document_id).await {
+            Ok(document) => document,
+            Err(response) => return response,
+        };
+        if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+            return response;
+        }
+    }
+    let (dir, output_path) = match (
+        chunk_dir(&root, &payload.file_id),
+        merged_path(
+            &root.join(MERGED_DIR),
+            &snake_case(&payload.name),
+            &payload.file_id,
+            &payload.extention,
+        ),
+    ) {
+        (Ok(dir), Ok(output_path)) => (dir, output_path),
+        (Err(e), _) | (_, Err(e)) => {
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+        }
+    };
+
+    // Merge chunks in numeric order, streaming them into the output file
+    if let Err(e) = fs::create_dir_all(root.join(MERGED_DIR)).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create merge directory: {}",
+                e
+            ))),
+        );
+    }
+    let merged = match merge_chunks(&dir, &output_path).await {
+        Ok(merged) => merged,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&output_path).await;
+            return match e {
+                MergeError::NoChunks => (
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse::error(
+                        "No upload directory found. Please upload chunks first.",
+                    )),
+                ),
+                MergeError::MissingChunk(index) => (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ApiResponse {
+                        status: false,
+                        message: format!("Missing chunk {}", index),
+                        data: Some(json!({ "chunk_index": index })),
+                    }),
+                ),
+                MergeError::ChecksumMismatch(index) => (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ApiResponse {
+                        status: false,
+                        message: format!("Checksum mismatch on chunk {}", index),
+                        data: Some(json!({ "chunk_index": index })),
+                    }),
+                ),
+                MergeError::Io(e) => (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::error(&format!(
+                        "Failed to merge chunks: {}",
+                        e
+                    ))),
+                ),
+            };
+        }
+    };
+    let response = store_upload(
+        &db,
+        &events,
+        &payload,
+        claims.sub,
+        &output_path,
+        merged,
+        None,
+    )
+    .await;
+
+    // Clean up temporary chunk directory and upload session once the document is saved
+    if response.0 == StatusCode::OK {
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let _ = sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", payload.file_id)
+            .execute(&db)
+            .await;
+    }
+
+    response
+}
+
+// function for storing a received file as a document version, shared by chunked and direct
+// uploads, the file at output_path is checked, scanned and removed, a file the client already
+// put into storage is passed as uploaded and is dropped again when identical content is stored
+async fn store_upload(
+    db: &MySqlPool,
+    events: &EventBus,
+    payload: &CompletePayload,
+    user_id: i64,
+    output_path: &std::path::Path,
+    merged: MergedFile,
+    uploaded: Option<CloudinaryResponse>,
+) -> HandlerResponse {
+    let content_hash = merged.content_hash;
+
+    // Re-check size and type on the merged file, chunks may have been replaced
+    if merged.size > max_file_size() {
+        let _ = tokio::fs::remove_file(output_path).await;
+        return file_too_large();
+    }
+    let mime_type = sniff_file(output_path)
+        .await
+        .unwrap_or_else(|_| OCTET_STREAM.to_string());
+    if !is_allowed_type(&mime_type) {
+        let _ = tokio::fs::remove_file(output_path).await;
+        return type_not_allowed(&mime_type);
+    }
+
+    // Verify the merged file against the whole-file checksum
+    if !checksum_matches(&payload.checksum, &content_hash) {
+        let _ = tokio::fs::remove_file(output_path).await;
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: "File checksum mismatch".to_string(),
+                data: Some(json!({
+                    "expected": payload.checksum.trim().to_lowercase(),
+                    "actual": content_hash,
+                })),
+            }),
+        );
+    }
+
+    // Reuse the storage object of an identical file instead of uploading it again
+    let size = merged.size as i64;
+    let existing = match find_blob(db, &content_hash, size).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(output_path).await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to look up stored files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    // Scan the merged file before it reaches storage, only content a scanner found clean is trusted,
+    // an unscanned copy stored while scanning was disabled is scanned again
+    let scanner = scanner();
+    let scan_status = if existing
+        .as_ref()
+        .is_some_and(|blob| blob.scan_status == SCAN_CLEAN)
+    {
+        SCAN_CLEAN
+    } else {
+        match scanner.scan(output_path).await {
+            Ok(ScanResult::Clean) => SCAN_CLEAN,
+            Ok(ScanResult::Skipped) => SCAN_UNSCANNED,
+            Ok(ScanResult::Infected(signature)) => {
+                return save_infected_upload(
+                    db,
+                    payload,
+                    user_id,
+                    output_path,
+                    &QuarantinedFile {
+                        content_hash: &content_hash,
+                        size,
+                        mime_type: &mime_type,
+                        signature: &signature,
+                        scanner: scanner.name(),
+                    },
+                )
+                .await;
+            }
+            Err(e) => {
+                // A scanner outage does not block uploads, the file stays pending until rescanned
+                println!("Malware scan with {} failed: {}", scanner.name(), e);
+                SCAN_PENDING
+            }
+        }
+    };
+
+    let blob = match existing {
+        Some(blob) => {
+            // The content is already stored, a copy the client uploaded directly is not needed
+            if let Some(uploaded) = &uploaded {
+                destroy_storage_object(
+                    Some(&uploaded.public_id),
+                    &uploaded.resource_type,
+                    &uploaded.secure_url,
+                )
+                .await;
+            }
+            blob
+        }
+        None => {
+            let cloudinary_response = match uploaded {
+                Some(uploaded) => uploaded,
+                None => {
+                    let file_for_upload = match fs::File::open(output_path).await {
+                        Ok(file) => file,
+                        Err(e) => {
+                            let _ = tokio::fs::remove_file(output_path).await;
+                            return (
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                Json(ApiResponse::error(&format!(
+                                    "Failed to open merged file: {}",
+                                    e
+                                ))),
+                            );
+                        }
+                    };
+                    match upload_to_storage(file_for_upload, &mime_type).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            println!("Storage upload failed: {}", e);
+                            // Keep the chunks so the client can retry, but never leak the merged file
+                            let _ = tokio::fs::remove_file(output_path).await;
+                            return (
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                Json(ApiResponse::error("Storage upload failed")),
+                            );
+                        }
+                    }
+                }
+            };
+            println!(
+                "Storage upload successful: {}",
+                cloudinary_response.secure_url
+            );
+
+            let resource_type = if cloudinary_response.resource_type.is_empty() {
+                resource_type_for(&mime_type).to_string()
+            } else {
+                cloudinary_response.resource_type.clone()
+            };
+            match register_blob(
+                db,
+                &content_hash,
+                size,
+                &cloudinary_response,
+                &resource_type,
+                scan_status,
+            )
+            .await
+            {
+                Ok(blob) => {
+                    // A concurrent upload of the same file won, drop our copy
+                    if blob.public_id.as_deref() != Some(cloudinary_response.public_id.as_str()) {
+                        destroy_storage_object(
+                            Some(&cloudinary_response.public_id),
+                            &resource_type,
+                            &cloudinary_response.secure_url,
+                        )
+                        .await;
+                    } else {
+                        store_thumbnail(db, blob.id, output_path, &mime_type, merged.size).await;
+                    }
+                    blob
+                }
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(output_path).await;
+                    destroy_storage_object(
+                        Some(&cloudinary_response.public_id),
+                        &resource_type,
+                        &cloudinary_response.secure_url,
+                    )
+                    .await;
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::error(&format!(
+                            "Failed to register stored file: {}",
+                            e
+                        ))),
+                    );
+                }
+            }
+        }
+    };
+    if let Err(e) = store_content(db, blob.id, output_path, &mime_type, merged.size).await {
+        println!("Failed to index content of blob {}: {}", blob.id, e);
+    }
+    let _ = tokio::fs::remove_file(output_path).await;
+
+    // A pending blob that has now been scanned clean is released for every document using it
+    if scan_status == SCAN_CLEAN && blob.scan_status != SCAN_CLEAN {
+        let released = mark_blob_scanned(db, blob.id, SCAN_CLEAN).await;
+        if let Err(e) = released {
+            println!("Failed to update scan status of blob {}: {}", blob.id, e);
+        }
+    }
+
+    // TODO: save to database
+    let stored = StoredVersion {
+        file_id: blob.file_id.clone(),
+        size,
+        content_hash: Some(content_hash.clone()),
+        mime_type: mime_type.clone(),
+        blob_id: Some(blob.id),
+        scan_status: scan_status.to_string(),
+    };
+    let (document_id, version) = match save_version(db, payload, &stored, user_id).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            // A blob uploaded for this request is removed again if nothing references it
+            if let Err(e) = purge_unreferenced_blobs(db, &[blob.id]).await {
+                println!("Failed to purge blob {}: {}", blob.id, e);
+            }
+            return save_error_response(e);
+        }
+    };
+
+    // The client may have given up on the request, its connections still learn the result
+    events.publish(Event::DocumentUploaded {
+        document_id,
+        version,
+        user_id,
+        name: payload.name.clone(),
+        size,
+        mime_type: mime_type.clone(),
+        scan_status: scan_status.to_string(),
+    });
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Upload successful",
+            json!({
+                "id": document_id,
+                "version": version,
+                "content_hash": content_hash,
+                "size": merged.size,
+                "mime_type": mime_type,
+                "scan_status": scan_status,
+            }),
+        )),
+    )
+}
+
+// pending direct upload, the client puts the file into storage itself and confirms it afterwards
+#[derive(sqlx::FromRow)]
+struct DirectUpload {
+    id: String,
+    user_id: i64,
+    document_id: Option<i64>,
+    name: String,
+    extention: String,
+    size: i64,
+    mime_type: String,
+    checksum: String,
+    backend: String,
+    object_key: String,
+    resource_type: String,
+    multipart_id: Option<String>,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+// function for removing a direct upload and whatever the client put into storage for it
+async fn discard_direct_upload(db: &MySqlPool, upload: &DirectUpload) {
+    if let Err(e) = discard_direct_object(
+        &upload.backend,
+        &upload.object_key,
+        &upload.resource_type,
+        upload.multipart_id.as_deref(),
+    )
+    .await
+    {
+        // The janitor only sees the row, keep it so the object is retried there
+        println!("Failed to discard direct upload {}: {}", upload.id, e);
+        return;
+    }
+
+    let _ = sqlx::query!("DELETE FROM direct_uploads WHERE id = ?", upload.id)
+        .execute(db)
+        .await;
+}
+
+// function for issuing presigned parameters so the client uploads straight to storage
+pub async fn create_direct_upload(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<DirectUploadRequest>,
+) -> HandlerResponse {
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+    if !is_sha256_hex(payload.checksum.trim()) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::error("Checksum must be a SHA-256 hex digest")),
+        );
+    }
+
+    // The declared size and type are checked again on the stored file when confirming
+    if payload.size > max_file_size() {
+        return file_too_large();
+    }
+    let mime_type = payload.mime_type.trim().to_lowercase();
+    if !is_allowed_type(&mime_type) {
+        return type_not_allowed(&mime_type);
+    }
+    let (used, in_flight) = match storage_usage(&db, claims.sub).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check storage quota: {}",
+                    e
+                ))),
+            );
+        }
+    };
+    if let Err(response) = check_quota(user_quota(), used, in_flight, payload.size as i64) {
+        return response;
+    }
+
+    // A new version can only be added to a document the user has access to
+    if let Some(document_id) = payload.document_id {
+        let document = match find_document(&db, document_id).await {
+            Ok(document) => document,
+            Err(response) => return response,
+        };
+        if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+            return response;
+        }
+    }
+
+    let id = generate_upload_id();
+    let ttl = direct_upload_ttl();
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(ttl);
+    let backend = storage_backend();
+    let resource_type = resource_type_for(&mime_type);
+
+    let (object_key, multipart_id, upload) = if backend == S3 {
+        let Some(config) = s3_config() else {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("S3 storage is not configured")),
+            );
+        };
+        let key = s3_object_key(DOCUMENT_FOLDER, &mime_type);
+
+        if payload.size <= s3_part_size() {
+            let url = s3_presigned_url(&config, "PUT", &key, &[], ttl as u64);
+            (
+                key,
+                None,
+                json!({
+                    "method": "PUT",
+                    "url": url,
+                    "headers": { "Content-Type": mime_type },
+                }),
+            )
+        } else {
+            // Parts grow past the configured size when the file would need too many of them
+            let part_size = s3_part_size().max(payload.size.div_ceil(S3_MAX_PARTS));
+            let multipart_id = match s3_create_multipart(&key, &mime_type).await {
+                Ok(multipart_id) => multipart_id,
+                Err(e) => {
+                    println!("Failed to start multipart upload: {}", e);
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::error("Failed to start multipart upload")),
+                    );
+                }
+            };
+            let parts = (1..=payload.size.div_ceil(part_size))
+                .map(|part_number| {
+                    let part = part_number.to_string();
+                    json!({
+                        "part_number": part_number,
+                        "url": s3_presigned_url(
+                            &config,
+                            "PUT",
+                            &key,
+                            &[("partNumber", &part), ("uploadId", &multipart_id)],
+                            ttl as u64,
+                        ),
+                    })
+                })
+                .collect::<Vec<Value>>();
+            (
+                key,
+                Some(multipart_id),
+                json!({
+                    "method": "PUT",
+                    "part_size": part_size,
+                    "parts": parts,
+                }),
+            )
+        }
+    } else {
+        let public_id = format!("{}/{}", DOCUMENT_FOLDER, id);
+        let (url, fields) = cloudinary_upload_params(&public_id, &mime_type);
+        (
+            public_id,
+            None,
+            json!({
+                "method": "POST",
+                "url": url,
+                "fields": fields,
+            }),
+        )
+    };
+
+    let inserted = sqlx::query!(
+        "
+        INSERT INTO direct_uploads
+            (id, user_id, document_id, name, extention, size, mime_type, checksum,
+             backend, object_key, resource_type, multipart_id, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ",
+        id,
+        claims.sub,
+        payload.document_id,
+        payload.name,
+        payload.extention,
+        payload.size as i64,
+        mime_type,
+        payload.checksum.trim().to_lowercase(),
+        backend,
+        object_key,
+        resource_type,
+        multipart_id,
+        expires_at
+    )
+    .execute(&db)
+    .await;
+    if let Err(e) = inserted {
+        if let Some(multipart_id) = &multipart_id {
+            let _ = s3_abort_multipart(&object_key, multipart_id).await;
+        }
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create direct upload: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Direct upload created",
+            json!({
+                "upload_id": id,
+                "backend": backend,
+                "expires_at": expires_at,
+                "upload": upload,
+            }),
+        )),
+    )
+}
+
+// function for confirming a direct upload, the stored object is checked, downloaded for
+// verification and scanning, and saved as a document version like a chunked upload
+pub async fn confirm_direct_upload(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Extension(events): Extension<EventBus>,
+    Path(id): Path<String>,
+    payload: Option<Json<DirectUploadConfirm>>,
+) -> HandlerResponse {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    if let Err(e) = validate_upload_id(&id) {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+    }
+
+    let upload = match sqlx::query_as!(
+        DirectUpload,
+        "
+        SELECT id, user_id, document_id, name, extention, size, mime_type, checksum,
+            backend, object_key, resource_type, multipart_id, expires_at
+        FROM direct_uploads
+        WHERE id = ?
+        ",
+        id
+    )
+    .fetch_optional(&db)
+    .await
+    {
+        Ok(Some(upload)) => upload,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::error("Upload not found")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to load direct upload: {}",
+                    e
+                ))),
+            );
+        }
+    };
+    if upload.user_id != claims.sub {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error("Upload belongs to another user")),
+        );
+    }
+    if upload.expires_at < chrono::Utc::now() {
+        discard_direct_upload(&db, &upload).await;
+        return (
+            StatusCode::GONE,
+            Json(ApiResponse::error("Upload has expired")),
+        );
+    }
+
+    // Assemble the parts of a multipart upload, a failure keeps it open for another attempt
+    if let Some(multipart_id) = &upload.multipart_id {
+        if payload.parts.is_empty() {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::error("Uploaded parts are required")),
+            );
+        }
+        let mut parts = payload
+            .parts
+            .iter()
+            .map(|part| (part.part_number, part.etag.clone()))
+            .collect::<Vec<(u32, String)>>();
+        parts.sort_by_key(|(part_number, _)| *part_number);
+
+        if let Err(e) = s3_complete_multipart(&upload.object_key, multipart_id, &parts).await {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::error(&format!(
+                    "Failed to complete multipart upload: {}",
+                    e
+                ))),
+            );
+        }
+        let _ = sqlx::query!(
+            "UPDATE direct_uploads SET multipart_id = NULL WHERE id = ?",
+            upload.id
+        )
+        .execute(&db)
+        .await;
+    }
+
+    // The object must exist with exactly the announced size
+    let object = if upload.backend == S3 {
+        let Some(config) = s3_config() else {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("S3 storage is not configured")),
+            );
+        };
+        s3_object_size(&upload.object_key).await.map(|size| {
+            size.map(|size| CloudinaryResponse {
+                public_id: upload.object_key.clone(),
+                secure_url: s3_object_url(&config, &upload.object_key),
+                width: None,
+                height: None,
+                format: upload.extention.clone(),
+                resource_type: upload.resource_type.clone(),
+                bytes: size,
+            })
+        })
+    } else {
+        cloudinary_resource(&upload.object_key, &upload.resource_type).await
+    };
+    let object = match object {
+        Ok(Some(object)) => object,
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse::error("File has not been uploaded yet")),
+            );
+        }
+        Err(e) => {
+            println!("Failed to look up direct upload {}: {}", upload.id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("Failed to look up uploaded file")),
+            );
+        }
+    };
+    if object.bytes != upload.size as u64 {
+        discard_direct_upload(&db, &upload).await;
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: "Uploaded file size mismatch".to_string(),
+                data: Some(json!({
+                    "expected": upload.size,
+                    "actual": object.bytes,
+                })),
+            }),
+        );
+    }
+
+    // Fetch the object back so it is hashed, sniffed and scanned like a merged upload
+    let root = upload_root();
+    let output_path = match merged_path(
+        &root.join(MERGED_DIR),
+        &snake_case(&upload.name),
+        &upload.id,
+        &upload.extention,
+    ) {
+        Ok(output_path) => output_path,
+        Err(e) => {
+            discard_direct_upload(&db, &upload).await;
+            return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e)));
+        }
+    };
+    if let Err(e) = fs::create_dir_all(root.join(MERGED_DIR)).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create merge directory: {}",
+                e
+            ))),
+        );
+    }
+    let merged = match download_to_file(&object.secure_url, &output_path).await {
+        Ok(merged) => merged,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&output_path).await;
+            println!("Failed to download direct upload {}: {}", upload.id, e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("Failed to download uploaded file")),
+            );
+        }
+    };
+
+    let complete = CompletePayload {
+        file_id: upload.id.clone(),
+        name: upload.name.clone(),
+        extention: upload.extention.clone(),
+        checksum: upload.checksum.clone(),
+        document_id: upload.document_id,
+    };
+    let response = store_upload(
+        &db,
+        &events,
+        &complete,
+        claims.sub,
+        &output_path,
+        merged,
+        Some(object),
+    )
+    .await;
+
+    // A rejected file is removed, server errors keep the upload so the client can retry
+    if response.0 == StatusCode::OK {
+        let _ = sqlx::query!("DELETE FROM direct_uploads WHERE id = ?", upload.id)
+            .execute(&db)
+            .await;
+    } else if response.0.is_client_error() {
+        discard_direct_upload(
+            &db,
+            &DirectUpload {
+                multipart_id: None,
+                ..upload
+            },
+        )
+        .await;
+    }
+
+    response
+}
+
+// function for saving an upload as a new document or as a new version of an existing one
+async fn save_version(
+    db: &MySqlPool,
+    payload: &CompletePayload,
+    stored: &StoredVersion,
+    user_id: i64,
+) -> Result<(i64, i32), SaveError> {
+    match payload.document_id {
+        Some(document_id) => append_version(db, document_id, stored, user_id).await,
+        None => create_document(db, &payload.name, stored, user_id).await,
+    }
+}
+
+// infected file kept in quarantine
+struct QuarantinedFile<'a> {
+    content_hash: &'a str,
+    size: i64,
+    mime_type: &'a str,
+    signature: &'a str,
+    scanner: &'a str,
+}
+
+// function for moving an infected file into quarantine and recording it
+async fn quarantine_file(
+    db: &MySqlPool,
+    path: &std::path::Path,
+    file: &QuarantinedFile<'_>,
+    document_id: i64,
+    version: i32,
+    uploaded_by: Option<i64>,
+) -> Result<(), String> {
+    let quarantine_dir = upload_root().join(QUARANTINE_DIR);
+    let quarantine_path = quarantine_dir.join(format!(
+        "{}_{}",
+        file.content_hash,
+        chrono::Utc::now().timestamp_millis()
+    ));
+
+    fs::create_dir_all(&quarantine_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    fs::rename(path, &quarantine_path)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query!(
+        "
+        INSERT INTO quarantined_files (document_id, version, content_hash, size, path, signature, scanner, uploaded_by)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        ",
+        document_id,
+        version,
+        file.content_hash,
+        file.size,
+        quarantine_path.display().to_string(),
+        file.signature,
+        file.scanner,
+        uploaded_by
+    )
+    .execute(db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// function for recording an infected upload, the document is saved so the user sees the verdict
+// but the file never reaches storage and can not be downloaded
+async fn save_infected_upload(
+    db: &MySqlPool,
+    payload: &CompletePayload,
+    user_id: i64,
+    output_path: &std::path::Path,
+    file: &QuarantinedFile<'_>,
+) -> HandlerResponse {
+    let stored = StoredVersion {
+        file_id: format!("quarantine:{}", file.content_hash),
+        size: file.size,
+        content_hash: Some(file.content_hash.to_string()),
+        mime_type: file.mime_type.to_string(),
+        blob_id: None,
+        scan_status: SCAN_INFECTED.to_string(),
+    };
+    let (document_id, version) = match save_version(db, payload, &stored, user_id).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(output_path).await;
+            return save_error_response(e);
+        }
+    };
+
+    if let Err(e) =
+        quarantine_file(db, output_path, file, document_id, version, Some(user_id)).await
+    {
+        // Never leave an infected file where it could be picked up again
+        println!("Failed to quarantine {}: {}", output_path.display(), e);
+        let _ = tokio::fs::remove_file(output_path).await;
+    }
+
+    // The upload is finished, retrying would only produce the same verdict
+    if let Ok(dir) = chunk_dir(&upload_root(), &payload.file_id) {
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+    let _ = sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", payload.file_id)
+        .execute(db)
+        .await;
+
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ApiResponse {
+            status: false,
+            message: "Malware detected, the file has been quarantined".to_string(),
+            data: Some(json!({
+                "id": document_id,
+                "version": version,
+                "scan_status": SCAN_INFECTED,
+                "signature": file.signature,
+            })),
+        }),
+    )
+}
+
+// function for storing the scan verdict of a blob, it applies to every version sharing the blob
+async fn mark_blob_scanned(
+    db: &MySqlPool,
+    blob_id: i64,
+    scan_status: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE blobs SET scan_status = ?, scanned_at = NOW() WHERE id = ?",
+        scan_status,
+        blob_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE document_versions SET scan_status = ? WHERE blob_id = ?",
+        scan_status,
+        blob_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "
+        UPDATE documents d
+        JOIN document_versions v ON v.document_id = d.id AND v.version = d.current_version
+        SET d.scan_status = v.scan_status
+        WHERE v.blob_id = ?
+        ",
+        blob_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// function for storing the scan verdict of a version without a blob
+async fn mark_version_scanned(
+    db: &MySqlPool,
+    document_id: i64,
+    version: i32,
+    scan_status: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE document_versions SET scan_status = ? WHERE document_id = ? AND version = ?",
+        scan_status,
+        document_id,
+        version
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE documents SET scan_status = ? WHERE id = ? AND current_version = ?",
+        scan_status,
+        document_id,
+        version
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// function for refusing to serve a file that has not been scanned clean,
+// files stored without a scanner are only served when that is allowed explicitly
+fn ensure_clean(scan_status: &str, serve_unscanned: bool) -> Result<(), HandlerResponse> {
+    match scan_status {
+        SCAN_CLEAN => Ok(()),
+        SCAN_UNSCANNED if serve_unscanned => Ok(()),
+        SCAN_INFECTED => Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error(
+                "Malware was detected in this file, it is quarantined",
+            )),
+        )),
+        _ => Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error(
+                "This file has not been scanned for malware yet",
+            )),
+        )),
+    }
+}
+
+// function for building the response to a file over the maximum file size
+fn file_too_large() -> HandlerResponse {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ApiResponse::error(&format!(
+            "File exceeds the maximum file size of {} bytes",
+            max_file_size()
+        ))),
+    )
+}
+
+// function for building the response to a file type outside the allowlist
+fn type_not_allowed(mime_type: &str) -> HandlerResponse {
+    (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Json(ApiResponse::error(&format!(
+            "File type {} is not allowed",
+            mime_type
+        ))),
+    )
+}
+
+// function for checking the bytes a user adds fit in their storage quota,
+// shrinking an upload that is already over it is still allowed
+fn check_quota(quota: u64, used: i64, in_flight: i64, added: i64) -> Result<(), HandlerResponse> {
+    if added <= 0 || (used + in_flight + added) as u64 <= quota {
+        return Ok(());
+    }
+
+    Err((
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ApiResponse {
+            status: false,
+            message: "Storage quota exceeded".to_string(),
+            data: Some(json!({
+                "quota": quota,
+                "used": used,
+                "in_flight": in_flight,
+            })),
+        }),
+    ))
+}
+
+// function for getting the bytes a user stores in documents and in unfinished uploads
+async fn storage_usage(db: &MySqlPool, user_id: i64) -> Result<(i64, i64), sqlx::Error> {
+    // every version keeps its own storage object, rollbacks reuse an existing one
+    let used = sqlx::query!(
+        "
+        SELECT CAST(COALESCE(SUM(size), 0) AS SIGNED) AS `total!: i64`
+        FROM (
+            SELECT DISTINCT v.file_id, v.size
+            FROM document_versions v
+            JOIN documents d ON d.id = v.document_id
+            WHERE d.user_id = ?
+            AND d.deleted_at IS NULL
+            AND v.scan_status <> 'infected'
+        ) AS stored
+        ",
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    let in_flight = sqlx::query!(
+        "
+        SELECT CAST(
+            COALESCE(SUM(received_bytes), 0)
+            + (SELECT COALESCE(SUM(size), 0) FROM direct_uploads WHERE user_id = ? AND expires_at > NOW())
+        AS SIGNED) AS `total!: i64`
+        FROM upload_sessions
+        WHERE user_id = ?
+        ",
+        user_id,
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok((used.total, in_flight.total))
+}
+
+pub async fn quota(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+) -> HandlerResponse {
+    let (used, in_flight) = match storage_usage(&db, claims.sub).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch storage usage: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let quota = user_quota();
+    let used = used.max(0) as u64;
+    let in_flight = in_flight.max(0) as u64;
+    let response = QuotaResponse {
+        quota,
+        used,
+        in_flight,
+        remaining: quota.saturating_sub(used + in_flight),
+        max_file_size: max_file_size(),
+        max_chunk_size: max_chunk_size(),
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Storage Quota", json!(response))),
+    )
+}
+
+pub async fn upload_sessions(Extension(db): Extension<MySqlPool>) -> HandlerResponse {
+    // get all in-flight upload sessions
+    let sessions = match sqlx::query!(
+        "
+        SELECT id, user_id, chunk_count, received_bytes, created_at, updated_at
+        FROM upload_sessions
+        ORDER BY updated_at DESC
+        "
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch upload sessions: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let ttl = chrono::Duration::seconds(upload_session_ttl().as_secs() as i64);
+    let response = sessions
+        .into_iter()
+        .map(|session| UploadSessionResponse {
+            id: session.id,
+            user_id: session.user_id,
+            chunk_count: session.chunk_count,
+            received_bytes: session.received_bytes,
+            created_at: session.created_at,
+            updated_at: session.updated_at,
+            expires_at: session.updated_at.map(|updated_at| updated_at + ttl),
+        })
+        .collect::<Vec<UploadSessionResponse>>();
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "List Upload Sessions",
+            json!(response),
+        )),
+    )
+}
+
+pub async fn cleanup_uploads(Extension(db): Extension<MySqlPool>) -> HandlerResponse {
+    // run the janitor now instead of waiting for the next interval
+    match upload_janitor::run_once(&db).await {
+        Ok(report) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(
+                "Upload cleanup finished",
+                json!(report),
+            )),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!("Upload cleanup failed: {}", e))),
+        ),
+    }
+}
+
+pub async fn dedup_stats(Extension(db): Extension<MySqlPool>) -> HandlerResponse {
+    // compare the bytes stored once per blob with the bytes every reference would take
+    let stats = match sqlx::query!(
+        "
+        SELECT
+            COUNT(*) AS `blobs!: i64`,
+            CAST(COALESCE(SUM(ref_count), 0) AS SIGNED) AS `references!: i64`,
+            CAST(COALESCE(SUM(size), 0) AS SIGNED) AS `stored_bytes!: i64`,
+            CAST(COALESCE(SUM(size * ref_count), 0) AS SIGNED) AS `logical_bytes!: i64`
+        FROM blobs
+        WHERE ref_count > 0
+        "
+    )
+    .fetch_one(&db)
+    .await
+    {
+        Ok(stats) => stats,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch deduplication stats: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let response = DedupStats {
+        blobs: stats.blobs,
+        references: stats.references,
+        stored_bytes: stats.stored_bytes,
+        logical_bytes: stats.logical_bytes,
+        saved_bytes: stats.logical_bytes - stats.stored_bytes,
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Deduplication Stats", json!(response))),
+    )
+}
+
+pub async fn quarantine(Extension(db): Extension<MySqlPool>) -> HandlerResponse {
+    // get all quarantined files, newest first
+    let files = match sqlx::query_as!(
+        QuarantinedFileResponse,
+        "
+        SELECT id, document_id, version, content_hash, size, signature, scanner, uploaded_by, created_at
+        FROM quarantined_files
+        ORDER BY id DESC
+        "
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(files) => files,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch quarantined files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("List Quarantined Files", json!(files))),
+    )
+}
+
+// function for downloading a stored file into a local file so it can be scanned,
+// hashing it on the way like a merge does
+async fn download_to_file(url: &str, path: &std::path::Path) -> Result<MergedFile, String> {
+    let upstream = fetch_from_storage(url, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !upstream.status().is_success() {
+        return Err(format!("storage returned {}", upstream.status()));
+    }
+
+    let mut file = BufWriter::new(fs::File::create(path).await.map_err(|e| e.to_string())?);
+    let mut body = upstream.bytes_stream();
+    let mut hasher = Sha256::new();
+    let mut size: u64 = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+
+    Ok(MergedFile {
+        size,
+        content_hash: format!("{:x}", hasher.finalize()),
+    })
+}
+
+pub async fn rescan(Extension(db): Extension<MySqlPool>, Path(id): Path<i64>) -> HandlerResponse {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    let stored = match find_version(&db, id, document.current_version).await {
+        Ok(stored) => stored,
+        Err(response) => return response,
+    };
+    // A quarantined file never reached storage, there is nothing to scan again
+    if stored.scan_status == SCAN_INFECTED {
+        return (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("Document is already quarantined")),
+        );
+    }
+
+    let root = upload_root();
+    let path = root.join(MERGED_DIR).join(format!(
+        "scan_{}_{}",
+        id,
+        chrono::Utc::now().timestamp_millis()
+    ));
+    if let Err(e) = fs::create_dir_all(root.join(MERGED_DIR)).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create scan directory: {}",
+                e
+            ))),
+        );
+    }
+    if let Err(e) = download_to_file(&stored.file_id, &path).await {
+        let _ = tokio::fs::remove_file(&path).await;
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(ApiResponse::error(&format!(
+                "Failed to fetch file from storage: {}",
+                e
+            ))),
+        );
+    }
+
+    let scanner = scanner();
+    let result = match scanner.scan(&path).await {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ApiResponse::error(&format!("Malware scan failed: {}", e))),
+            );
+        }
+    };
+
+    let marked = match stored.blob_id {
+        Some(blob_id) => mark_blob_scanned(&db, blob_id, result.status()).await,
+        None => mark_version_scanned(&db, id, document.current_version, result.status()).await,
+    };
+    if let Err(e) = marked {
+        let _ = tokio::fs::remove_file(&path).await;
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to update scan status: {}",
+                e
+            ))),
+        );
+    }
+
+    let signature = match &result {
+        ScanResult::Clean | ScanResult::Skipped => {
+            let _ = tokio::fs::remove_file(&path).await;
+            None
+        }
+        ScanResult::Infected(signature) => {
+            // The storage object is kept for the blob, but no endpoint serves it anymore
+            let file = QuarantinedFile {
+                content_hash: stored.content_hash.as_deref().unwrap_or_default(),
+                size: stored.size,
+                mime_type: &stored.mime_type,
+                signature,
+                scanner: scanner.name(),
+            };
+            if let Err(e) =
+                quarantine_file(&db, &path, &file, id, document.current_version, None).await
+            {
+                println!("Failed to quarantine {}: {}", path.display(), e);
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+            Some(signature.clone())
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document scanned",
+            json!({
+                "id": id,
+                "version": document.current_version,
+                "scan_status": result.status(),
+                "signature": signature,
+            }),
+        )),
+    )
+}
+
+// number of stored files scanned by one bulk rescan request
+const RESCAN_BATCH_SIZE: i64 = 20;
+
+// function for scanning the stored files that have no verdict yet, one version per blob
+// is scanned and the verdict applies to every version sharing it,
+// batches are paged by version id so files that keep failing do not block the ones after them
+pub async fn rescan_all(
+    Extension(db): Extension<MySqlPool>,
+    Query(query): Query<RescanQuery>,
+) -> HandlerResponse {
+    let scanner = scanner();
+    if scanner.name() == DisabledScanner.name() {
+        return (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("Malware scanning is disabled")),
+        );
+    }
+
+    let versions = match sqlx::query!(
+        "
+        SELECT v.id, v.document_id, v.version, v.file_id, v.size, v.content_hash, v.mime_type,
+            v.blob_id
+        FROM document_versions v
+        WHERE v.id IN (
+            SELECT MIN(id)
+            FROM document_versions
+            WHERE scan_status IN (?, ?)
+            GROUP BY COALESCE(blob_id, -id)
+        )
+        AND v.id > ?
+        ORDER BY v.id
+        LIMIT ?
+        ",
+        SCAN_UNSCANNED,
+        SCAN_PENDING,
+        query.after.unwrap_or(0),
+        RESCAN_BATCH_SIZE
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(versions) => versions,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch stored files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let root = upload_root();
+    if let Err(e) = fs::create_dir_all(root.join(MERGED_DIR)).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create scan directory: {}",
+                e
+            ))),
+        );
+    }
+
+    // None once the last batch is done, the next request starts over with what is left
+    let next = (versions.len() as i64 == RESCAN_BATCH_SIZE)
+        .then(|| versions.last().map(|version| version.id))
+        .flatten();
+    let mut scanned = 0;
+    let mut infected: Vec<i64> = Vec::new();
+    let mut failed: Vec<i64> = Vec::new();
+    for version in versions {
+        let path = root.join(MERGED_DIR).join(format!(
+            "scan_{}_{}_{}",
+            version.document_id,
+            version.version,
+            chrono::Utc::now().timestamp_millis()
+        ));
+
+        if let Err(e) = download_to_file(&version.file_id, &path).await {
+            println!(
+                "Failed to fetch document {} for scanning: {}",
+                version.document_id, e
+            );
+            let _ = tokio::fs::remove_file(&path).await;
+            failed.push(version.document_id);
+            continue;
+        }
+        let result = match scanner.scan(&path).await {
+            Ok(result) => result,
+            Err(e) => {
+                println!("Failed to scan document {}: {}", version.document_id, e);
+                let _ = tokio::fs::remove_file(&path).await;
+                failed.push(version.document_id);
+                continue;
+            }
+        };
+
+        let marked = match version.blob_id {
+            Some(blob_id) => mark_blob_scanned(&db, blob_id, result.status()).await,
+            None => {
+                mark_version_scanned(&db, version.document_id, version.version, result.status())
+                    .await
+            }
+        };
+        if let Err(e) = marked {
+            println!(
+                "Failed to update scan status of document {}: {}",
+                version.document_id, e
+            );
+            let _ = tokio::fs::remove_file(&path).await;
+            failed.push(version.document_id);
+            continue;
+        }
+
+        match &result {
+            ScanResult::Infected(signature) => {
+                let file = QuarantinedFile {
+                    content_hash: version.content_hash.as_deref().unwrap_or_default(),
+                    size: version.size,
+                    mime_type: &version.mime_type,
+                    signature,
+                    scanner: scanner.name(),
+                };
+                if let Err(e) = quarantine_file(
+                    &db,
+                    &path,
+                    &file,
+                    version.document_id,
+                    version.version,
+                    None,
+                )
+                .await
+                {
+                    println!("Failed to quarantine {}: {}", path.display(), e);
+                    let _ = tokio::fs::remove_file(&path).await;
+                }
+                infected.push(version.document_id);
+            }
+            ScanResult::Clean | ScanResult::Skipped => {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+        scanned += 1;
+    }
+
+    let remaining = match sqlx::query!(
+        r#"
+        SELECT COUNT(DISTINCT COALESCE(blob_id, -id)) AS `total!: i64`
+        FROM document_versions
+        WHERE scan_status IN (?, ?)
+        "#,
+        SCAN_UNSCANNED,
+        SCAN_PENDING
+    )
+    .fetch_one(&db)
+    .await
+    {
+        Ok(row) => row.total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count stored files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Documents scanned",
+            json!({
+                "scanned": scanned,
+                "infected": infected,
+                "failed": failed,
+                "remaining": remaining,
+                "next": next,
+            }),
+        )),
+    )
+}
+
+// number of stored files indexed by one reindex request
+const REINDEX_BATCH_SIZE: i64 = 20;
+
+pub async fn reindex(Extension(db): Extension<MySqlPool>) -> HandlerResponse {
+    // stored files without an index entry, uploaded before search existed
+    let blobs = match sqlx::query!(
+        r#"
+        SELECT b.id, b.file_id, b.size, MAX(v.mime_type) AS `mime_type!: String`
+        FROM blobs b
+        JOIN document_versions v ON v.blob_id = b.id
+        LEFT JOIN blob_contents c ON c.blob_id = b.id
+        WHERE c.blob_id IS NULL
+        AND b.scan_status <> ?
+        GROUP BY b.id, b.file_id, b.size
+        ORDER BY b.id
+        LIMIT ?
+        "#,
+        SCAN_INFECTED,
+        REINDEX_BATCH_SIZE
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(blobs) => blobs,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch stored files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let root = upload_root();
+    if let Err(e) = fs::create_dir_all(root.join(MERGED_DIR)).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to create index directory: {}",
+                e
+            ))),
+        );
+    }
+
+    let mut indexed = 0;
+    let mut failed: Vec<i64> = Vec::new();
+    for blob in blobs {
+        let size = blob.size.max(0) as u64;
+        let path = root.join(MERGED_DIR).join(format!(
+            "index_{}_{}",
+            blob.id,
+            chrono::Utc::now().timestamp_millis()
+        ));
+
+        // Only files text can be extracted from are downloaded, the rest get an empty entry
+        if size <= TEXT_EXTRACT_MAX_SOURCE_SIZE && is_extractable(&blob.mime_type) {
+            if let Err(e) = download_to_file(&blob.file_id, &path).await {
+                println!("Failed to fetch blob {} for indexing: {}", blob.id, e);
+                let _ = tokio::fs::remove_file(&path).await;
+                failed.push(blob.id);
+                continue;
+            }
+        }
+        let stored = store_content(&db, blob.id, &path, &blob.mime_type, size).await;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        match stored {
+            Ok(()) => indexed += 1,
+            Err(e) => {
+                println!("Failed to index content of blob {}: {}", blob.id, e);
+                failed.push(blob.id);
+            }
+        }
+    }
+
+    let remaining = match sqlx::query!(
+        r#"
+        SELECT COUNT(DISTINCT b.id) AS `total!: i64`
+        FROM blobs b
+        JOIN document_versions v ON v.blob_id = b.id
+        LEFT JOIN blob_contents c ON c.blob_id = b.id
+        WHERE c.blob_id IS NULL
+        AND b.scan_status <> ?
+        "#,
+        SCAN_INFECTED
+    )
+    .fetch_one(&db)
+    .await
+    {
+        Ok(row) => row.total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count stored files: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Documents indexed",
+            json!({
+                "indexed": indexed,
+                "failed": failed,
+                "remaining": remaining,
+            }),
+        )),
+    )
+}
+
+// headers forwarded from the storage response to the client
+const PASSTHROUGH_HEADERS: [HeaderName; 5] = [
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+pub struct StoredDocument {
+    pub name: String,
+    pub file_id: String,
+    pub user_id: Option<i64>,
+    pub mime_type: String,
+    pub current_version: i32,
+    pub scan_status: String,
+}
+
+// storage object of a single document version
+struct StoredVersion {
+    file_id: String,
+    size: i64,
+    content_hash: Option<String>,
+    mime_type: String,
+    blob_id: Option<i64>,
+    scan_status: String,
+}
+
+// storage object shared by every document version with the same content hash
+struct Blob {
+    id: i64,
+    file_id: String,
+    public_id: Option<String>,
+    resource_type: String,
+    thumbnail_file_id: Option<String>,
+    thumbnail_public_id: Option<String>,
+    scan_status: String,
+}
+
+#[derive(Debug)]
+enum SaveError {
+    DocumentNotFound,
+    // the blob was purged between lookup and use, the upload can simply be retried
+    BlobRemoved,
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for SaveError {
+    fn from(e: sqlx::Error) -> Self {
+        SaveError::Database(e)
+    }
+}
+
+// function for finding a document that has not been deleted
+pub async fn find_document(db: &MySqlPool, id: i64) -> Result<StoredDocument, HandlerResponse> {
+    match sqlx::query_as!(
+        StoredDocument,
+        "
+        SELECT name, file_id, user_id, mime_type, current_version, scan_status
+        FROM documents
+        WHERE id = ?
+        AND deleted_at IS NULL
+        ",
+        id
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(Some(document)) => Ok(document),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Document not found")),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to fetch document: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+// function for checking the user owns the document or is an admin
+pub async fn authorize_document(
+    db: &MySqlPool,
+    owner_id: Option<i64>,
+    user_id: i64,
+) -> Result<(), HandlerResponse> {
+    if owner_id == Some(user_id) {
+        return Ok(());
+    }
+
+    match is_admin(db, user_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error(
+                "You do not have access to this document",
+            )),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to check access: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+// function for finding a single version of a document
+async fn find_version(
+    db: &MySqlPool,
+    document_id: i64,
+    version: i32,
+) -> Result<StoredVersion, HandlerResponse> {
+    match sqlx::query_as!(
+        StoredVersion,
+        "
+        SELECT file_id, size, content_hash, mime_type, blob_id, scan_status
+        FROM document_versions
+        WHERE document_id = ?
+        AND version = ?
+        ",
+        document_id,
+        version
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(Some(version)) => Ok(version),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Document version not found")),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to fetch document version: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+// function for recording a version row and taking a reference on its blob
+async fn insert_version(
+    conn: &mut MySqlConnection,
+    document_id: i64,
+    version: i32,
+    stored: &StoredVersion,
+    uploaded_by: i64,
+) -> Result<(), SaveError> {
+    if let Some(blob_id) = stored.blob_id {
+        let result = sqlx::query!(
+            "UPDATE blobs SET ref_count = ref_count + 1 WHERE id = ?",
+            blob_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(SaveError::BlobRemoved);
+        }
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO document_versions (document_id, version, file_id, size, content_hash, mime_type, uploaded_by, blob_id, scan_status)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ",
+        document_id,
+        version,
+        stored.file_id,
+        stored.size,
+        stored.content_hash,
+        stored.mime_type,
+        uploaded_by,
+        stored.blob_id,
+        stored.scan_status
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// function for creating a document with its first version, returns the document id and version
+async fn create_document(
+    db: &MySqlPool,
+    name: &str,
+    stored: &StoredVersion,
+    user_id: i64,
+) -> Result<(i64, i32), SaveError> {
+    let mut tx = db.begin().await?;
+
+    let result = sqlx::query!(
+        "INSERT INTO documents (name, file_id, content_hash, user_id, size, mime_type, scan_status, current_version) VALUES (?, ?, ?, ?, ?, ?, ?, 1)",
+        name,
+        stored.file_id,
+        stored.content_hash,
+        user_id,
+        stored.size,
+        stored.mime_type,
+        stored.scan_status
+    )
+    .execute(&mut *tx)
+    .await?;
+    let document_id = result.last_insert_id() as i64;
+
+    insert_version(&mut tx, document_id, 1, stored, user_id).await?;
+    tx.commit().await?;
+
+    Ok((document_id, 1))
+}
+
+// function for appending a version to a document and making it current, returns the document id and version
+async fn append_version(
+    db: &MySqlPool,
+    document_id: i64,
+    stored: &StoredVersion,
+    user_id: i64,
+) -> Result<(i64, i32), SaveError> {
+    let mut tx = db.begin().await?;
+
+    // Lock the document so concurrent uploads get consecutive version numbers
+    let document = sqlx::query!(
+        "SELECT current_version FROM documents WHERE id = ? AND deleted_at IS NULL FOR UPDATE",
+        document_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(SaveError::DocumentNotFound)?;
+    let version = document.current_version + 1;
+
+    insert_version(&mut tx, document_id, version, stored, user_id).await?;
+    sqlx::query!(
+        "
+        UPDATE documents
+        SET file_id = ?, size = ?, content_hash = ?, mime_type = ?, scan_status = ?, current_version = ?
+        WHERE id = ?
+        ",
+        stored.file_id,
+        stored.size,
+        stored.content_hash,
+        stored.mime_type,
+        stored.scan_status,
+        version,
+        document_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((document_id, version))
+}
+
+// function for finding the blob of an identical file
+async fn find_blob(
+    db: &MySqlPool,
+    content_hash: &str,
+    size: i64,
+) -> Result<Option<Blob>, sqlx::Error> {
+    sqlx::query_as!(
+        Blob,
+        "
+        SELECT id, file_id, public_id, resource_type, thumbnail_file_id, thumbnail_public_id, scan_status
+        FROM blobs
+        WHERE content_hash = ?
+        AND size = ?
+        ",
+        content_hash,
+        size
+    )
+    .fetch_optional(db)
+    .await
+}
+
+// function for registering a freshly uploaded storage object, returns the blob for the hash,
+// which belongs to a concurrent upload if that one registered first
+async fn register_blob(
+    db: &MySqlPool,
+    content_hash: &str,
+    size: i64,
+    uploaded: &CloudinaryResponse,
+    resource_type: &str,
+    scan_status: &str,
+) -> Result<Blob, sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT IGNORE INTO blobs (content_hash, file_id, public_id, resource_type, size, scan_status, scanned_at)
+        VALUES (?, ?, ?, ?, ?, ?, IF(? IN ('pending', 'unscanned'), NULL, NOW()))
+        ",
+        content_hash,
+        uploaded.secure_url,
+        uploaded.public_id,
+        resource_type,
+        size,
+        scan_status,
+        scan_status
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query_as!(
+        Blob,
+        "
+        SELECT id, file_id, public_id, resource_type, thumbnail_file_id, thumbnail_public_id, scan_status
+        FROM blobs
+        WHERE content_hash = ?
+        ",
+        content_hash
+    )
+    .fetch_one(db)
+    .await
+}
+
+// function for generating the thumbnail of a freshly stored file and attaching it to its blob,
+// a file without a usable thumbnail is not an error so failures are only logged
+async fn store_thumbnail(
+    db: &MySqlPool,
+    blob_id: i64,
+    path: &std::path::Path,
+    mime_type: &str,
+    size: u64,
+) {
+    if size > THUMBNAIL_MAX_SOURCE_SIZE
+        || !(mime_type.starts_with("image/") || mime_type == "application/pdf")
+    {
+        return;
+    }
+
+    let bytes = match fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("Failed to read file for thumbnail: {}", e);
+            return;
+        }
+    };
+    let mime_type = mime_type.to_string();
+    let thumbnail =
+        match tokio::task::spawn_blocking(move || document_thumbnail(&bytes, &mime_type)).await {
+            Ok(Some(thumbnail)) => thumbnail,
+            Ok(None) => return,
+            Err(e) => {
+                println!("Thumbnail generation failed: {}", e);
+                return;
+            }
+        };
+
+    let uploaded =
+        match upload_bytes_to_storage(thumbnail.bytes, thumbnail.mime_type, THUMBNAIL_FOLDER).await
+        {
+            Ok(uploaded) => uploaded,
+            Err(e) => {
+                println!("Thumbnail upload failed: {}", e);
+                return;
+            }
+        };
+
+    if let Err(e) = sqlx::query!(
+        "UPDATE blobs SET thumbnail_file_id = ?, thumbnail_public_id = ? WHERE id = ?",
+        uploaded.secure_url,
+        uploaded.public_id,
+        blob_id
+    )
+    .execute(db)
+    .await
+    {
+        println!("Failed to save thumbnail of blob {}: {}", blob_id, e);
+        destroy_storage_object(Some(&uploaded.public_id), "image", &uploaded.secure_url).await;
+    }
+}
+
+// function for extracting the text of a stored file into the search index, files without text
+// get an empty entry so a reindex does not pick them up again
+async fn store_content(
+    db: &MySqlPool,
+    blob_id: i64,
+    path: &std::path::Path,
+    mime_type: &str,
+    size: u64,
+) -> Result<(), String> {
+    let indexed = sqlx::query!(
+        "SELECT blob_id FROM blob_contents WHERE blob_id = ?",
+        blob_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    if indexed.is_some() {
+        return Ok(());
+    }
+
+    let content = if size <= TEXT_EXTRACT_MAX_SOURCE_SIZE && is_extractable(mime_type) {
+        let bytes = fs::read(path).await.map_err(|e| e.to_string())?;
+        let mime_type = mime_type.to_string();
+        tokio::task::spawn_blocking(move || extract_text(&bytes, &mime_type))
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    sqlx::query!(
+        "INSERT IGNORE INTO blob_contents (blob_id, content) VALUES (?, ?)",
+        blob_id,
+        content
+    )
+    .execute(db)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// function for removing blobs that are no longer referenced and deleting their storage objects
+async fn purge_unreferenced_blobs(db: &MySqlPool, blob_ids: &[i64]) -> Result<u64, sqlx::Error> {
+    let mut purged = 0;
+
+    for blob_id in blob_ids {
+        // Lock the row so a concurrent upload can not take a reference while it is removed
+        let mut tx = db.begin().await?;
+        let blob = sqlx::query_as!(
+            Blob,
+            "
+            SELECT id, file_id, public_id, resource_type, thumbnail_file_id, thumbnail_public_id, scan_status
+            FROM blobs
+            WHERE id = ?
+            AND ref_count <= 0
+            FOR UPDATE
+            ",
+            blob_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(blob) = blob else {
+            continue;
+        };
+
+        sqlx::query!("DELETE FROM blobs WHERE id = ?", blob.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM blob_contents WHERE blob_id = ?", blob.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        destroy_storage_object(
+            blob.public_id.as_deref(),
+            &blob.resource_type,
+            &blob.file_id,
+        )
+        .await;
+        if let Some(thumbnail_file_id) = &blob.thumbnail_file_id {
+            destroy_storage_object(
+                blob.thumbnail_public_id.as_deref(),
+                "image",
+                thumbnail_file_id,
+            )
+            .await;
+        }
+        purged += 1;
+    }
+
+    Ok(purged)
+}
+
+// function for soft deleting a document and dropping the references its versions hold,
+// returns the blobs that may have become unreferenced
+async fn delete_document(db: &MySqlPool, document_id: i64) -> Result<Vec<i64>, SaveError> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "SELECT id FROM documents WHERE id = ? AND deleted_at IS NULL FOR UPDATE",
+        document_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(SaveError::DocumentNotFound)?;
+
+    let references = sqlx::query!(
+        "
+        SELECT blob_id AS `blob_id!: i64`, COUNT(*) AS `total!: i64`
+        FROM document_versions
+        WHERE document_id = ?
+        AND blob_id IS NOT NULL
+        GROUP BY blob_id
+        ",
+        document_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for reference in references.iter() {
+        sqlx::query!(
+            "UPDATE blobs SET ref_count = ref_count - ? WHERE id = ?",
+            reference.total,
+            reference.blob_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        "UPDATE document_versions SET blob_id = NULL WHERE document_id = ?",
+        document_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM document_tags WHERE document_id = ?",
+        document_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE documents SET deleted_at = NOW() WHERE id = ?",
+        document_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(references
+        .into_iter()
+        .map(|reference| reference.blob_id)
+        .collect())
+}
+
+// function for streaming a stored file to the client, passing Range requests through to storage
+async fn stream_document(document: &StoredDocument, headers: &HeaderMap) -> Response {
+    if let Err(response) = ensure_clean(&document.scan_status, serve_unscanned()) {
+        return response.into_response();
+    }
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok());
+
+    let upstream = match fetch_from_storage(&document.file_id, range).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            println!("Storage download failed: {}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ApiResponse::<Value>::error(
+                    "Failed to fetch file from storage",
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    let status = upstream.status();
+    if !status.is_success() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+        println!("Storage download failed: {}", status);
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(ApiResponse::<Value>::error(
+                "Failed to fetch file from storage",
+            )),
+        )
+            .into_response();
+    }
+
+    let file_name = format!(
+        "{}.{}",
+        sanitize_file_stem(&document.name),
+        extension_for(&document.mime_type)
+    );
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, &document.mime_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .header(header::CACHE_CONTROL, "private, no-store");
+
+    for name in PASSTHROUGH_HEADERS {
+        if let Some(value) = upstream.headers().get(&name) {
+            response = response.header(name, value);
+        }
+    }
+    if !upstream.headers().contains_key(header::ACCEPT_RANGES) {
+        response = response.header(header::ACCEPT_RANGES, "bytes");
+    }
+
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .unwrap_or_else(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to create response: {}",
+                    e
+                ))),
+            )
+                .into_response()
+        })
+}
+
+pub async fn download(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response.into_response(),
+    };
+
+    // Only the owner or an admin can download a document
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response.into_response();
+    }
+
+    // Hand out a short-lived url served by this app, never the storage url
+    if query.redirect.unwrap_or(false) {
+        if let Err(response) = ensure_clean(&document.scan_status, serve_unscanned()) {
+            return response.into_response();
+        }
+        return Redirect::temporary(&signed_path(&format!("/document/{}/signed", id)))
+            .into_response();
+    }
+
+    stream_document(&document, &headers).await
+}
+
+pub async fn signed_download(
+    Extension(db): Extension<MySqlPool>,
+    Path(id): Path<i64>,
+    Query(query): Query<SignedDownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    // The signature is the only credential on this route
+    if !verify_signed_path(
+        &format!("/document/{}/signed", id),
+        query.expires,
+        &query.signature,
+    ) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<Value>::error(
+                "Download link is invalid or has expired",
+            )),
+        )
+            .into_response();
+    }
+
+    match find_document(&db, id).await {
+        Ok(document) => stream_document(&document, &headers).await,
+        Err(response) => response.into_response(),
+    }
+}
+
+// maximum number of documents in one ZIP download
+const ZIP_MAX_DOCUMENTS: usize = 1000;
+// number of chunks buffered between the storage downloads and the client
+const ZIP_STREAM_BUFFER: usize = 8;
+
+// document packed into a ZIP download
+#[derive(sqlx::FromRow)]
+struct ZipDocument {
+    id: i64,
+    name: String,
+    file_id: String,
+    user_id: Option<i64>,
+    size: i64,
+    mime_type: String,
+    scan_status: String,
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// function for sending part of a ZIP download, fails once the client has gone away
+async fn send_zip_bytes(
+    tx: &mut mpsc::Sender<Result<Bytes, std::io::Error>>,
+    bytes: impl Into<Bytes>,
+) -> Result<(), String> {
+    tx.send(Ok(bytes.into()))
+        .await
+        .map_err(|_| "client disconnected".to_string())
+}
+
+// function for writing documents as ZIP entries, each one streamed straight from storage
+async fn write_zip(
+    tx: &mut mpsc::Sender<Result<Bytes, std::io::Error>>,
+    documents: Vec<(String, ZipDocument)>,
+) -> Result<(), String> {
+    let mut zip = ZipStream::new();
+
+    for (name, document) in documents {
+        let modified = document.updated_at.unwrap_or_default().naive_utc();
+        let header = zip.start_entry(&name, modified, document.size.max(0) as u64);
+        send_zip_bytes(tx, header).await?;
+
+        let upstream = fetch_from_storage(&document.file_id, None)
+            .await
+            .map_err(|e| format!("document {}: {}", document.id, e))?;
+        if !upstream.status().is_success() {
+            return Err(format!(
+                "document {}: storage returned {}",
+                document.id,
+                upstream.status()
+            ));
+        }
+        let mut body = upstream.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| format!("document {}: {}", document.id, e))?;
+            zip.write(&chunk);
+            send_zip_bytes(tx, chunk).await?;
+        }
+
+        let descriptor = zip.finish_entry().map_err(|e| e.to_string())?;
+        send_zip_bytes(tx, descriptor).await?;
+    }
+
+    send_zip_bytes(tx, zip.finish()).await
+}
+
+pub async fn download_zip(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    axum::Json(payload): axum::Json<ZipDownloadRequest>,
+) -> Response {
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        )
+            .into_response();
+    }
+    if payload.ids.is_empty() && payload.folder_id.is_none() && payload.tag.is_none() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: "Validation failed".to_string(),
+                data: Some(json!({
+                    "ids": ["Pilih dokumen, folder atau tag"]
+                })),
+            }),
+        )
+            .into_response();
+    }
+
+    let admin = match is_admin(&db, claims.sub).await {
+        Ok(admin) => admin,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to check access: {}",
+                    e
+                ))),
+            )
+                .into_response();
+        }
+    };
+
+    // Explicit ids are checked one by one below, filters only ever match accessible documents
+    let query = DocumentQuery {
+        page: None,
+        limit: None,
+        keyword: None,
+        tag: payload.tag.clone(),
+        folder_id: payload.folder_id,
+        mime_type: None,
+        date_from: None,
+        date_to: None,
+    };
+    let owner = (!admin && payload.ids.is_empty()).then_some(claims.sub);
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT d.id, d.name, d.file_id, d.user_id, d.size, d.mime_type, d.scan_status, d.updated_at FROM documents d",
+    );
+    push_document_filters(&mut builder, &query, owner);
+    if !payload.ids.is_empty() {
+        builder.push(" AND d.id IN (");
+        let mut separated = builder.separated(", ");
+        for id in &payload.ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+    }
+    builder
+        .push(" ORDER BY d.name, d.id LIMIT ")
+        .push_bind(ZIP_MAX_DOCUMENTS as i64 + 1);
+    let documents = match builder.build_query_as::<ZipDocument>().fetch_all(&db).await {
+        Ok(documents) => documents,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to fetch documents: {}",
+                    e
+                ))),
+            )
+                .into_response();
+        }
+    };
+
+    let found = documents.iter().map(|d| d.id).collect::<HashSet<i64>>();
+    let missing = payload
+        .ids
+        .iter()
+        .filter(|id| !found.contains(id))
+        .copied()
+        .collect::<Vec<i64>>();
+    if !missing.is_empty() || documents.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                status: false,
+                message: "Document not found".to_string(),
+                data: Some(json!({ "missing": missing })),
+            }),
+        )
+            .into_response();
+    }
+    if documents.len() > ZIP_MAX_DOCUMENTS {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::<Value>::error(&format!(
+                "A ZIP download can contain at most {} documents",
+                ZIP_MAX_DOCUMENTS
+            ))),
+        )
+            .into_response();
+    }
+
+    // Every document must be accessible and scanned clean before anything is streamed
+    let forbidden = documents
+        .iter()
+        .filter(|d| !admin && d.user_id != Some(claims.sub))
+        .map(|d| d.id)
+        .collect::<Vec<i64>>();
+    if !forbidden.is_empty() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse {
+                status: false,
+                message: "You do not have access to this document".to_string(),
+                data: Some(json!({ "forbidden": forbidden })),
+            }),
+        )
+            .into_response();
+    }
+    let allow_unscanned = serve_unscanned();
+    let unavailable = documents
+        .iter()
+        .filter(|d| ensure_clean(&d.scan_status, allow_unscanned).is_err())
+        .map(|d| json!({ "id": d.id, "scan_status": d.scan_status }))
+        .collect::<Vec<Value>>();
+    if !unavailable.is_empty() {
+        return (
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                status: false,
+                message: "Some documents have not been scanned clean".to_string(),
+                data: Some(json!({ "documents": unavailable })),
+            }),
+        )
+            .into_response();
+    }
+
+    let mut names = UniqueNames::default();
+    let entries = documents
+        .into_iter()
+        .map(|document| {
+            let name = names.claim(&entry_name(
+                &document.name,
+                extension_for(&document.mime_type),
+            ));
+            (name, document)
+        })
+        .collect::<Vec<(String, ZipDocument)>>();
+
+    // The archive is written while it is sent, a failing document aborts the download
+    let (mut tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(ZIP_STREAM_BUFFER);
+    tokio::spawn(async move {
+        if let Err(e) = write_zip(&mut tx, entries).await {
+            println!("ZIP download aborted: {}", e);
+            let _ = tx.send(Err(std::io::Error::other(e))).await;
+        }
+    });
+
+    let file_name = format!(
+        "documents_{}.zip",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    );
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .header(header::CACHE_CONTROL, "private, no-store")
+        .body(Body::from_stream(rx))
+        .unwrap_or_else(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to create response: {}",
+                    e
+                ))),
+            )
+                .into_response()
+        })
+}
+
+pub async fn thumbnail(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> Response {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response.into_response(),
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response.into_response();
+    }
+
+    if let Err(response) = ensure_clean(&document.scan_status, serve_unscanned()) {
+        return response.into_response();
+    }
+
+    // The thumbnail belongs to the blob of the current version
+    let thumbnail_file_id = match sqlx::query!(
+        "
+        SELECT b.thumbnail_file_id
+        FROM document_versions v
+        JOIN blobs b ON b.id = v.blob_id
+        WHERE v.document_id = ?
+        AND v.version = ?
+        ",
+        id,
+        document.current_version
+    )
+    .fetch_optional(&db)
+    .await
+    {
+        Ok(thumbnail) => thumbnail.and_then(|thumbnail| thumbnail.thumbnail_file_id),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to fetch thumbnail: {}",
+                    e
+                ))),
+            )
+                .into_response();
+        }
+    };
+    let Some(thumbnail_file_id) = thumbnail_file_id else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<Value>::error("Thumbnail not available")),
+        )
+            .into_response();
+    };
+
+    let upstream = match fetch_from_storage(thumbnail_file_id.as_str(), None).await {
+        Ok(upstream) if upstream.status().is_success() => upstream,
+        Ok(upstream) => {
+            println!("Thumbnail download failed: {}", upstream.status());
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ApiResponse::<Value>::error(
+                    "Failed to fetch thumbnail from storage",
+                )),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            println!("Thumbnail download failed: {}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ApiResponse::<Value>::error(
+                    "Failed to fetch thumbnail from storage",
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CACHE_CONTROL, "private, max-age=3600");
+    for name in [header::CONTENT_TYPE, header::CONTENT_LENGTH, header::ETAG] {
+        if let Some(value) = upstream.headers().get(&name) {
+            response = response.header(name, value);
+        }
+    }
+
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .unwrap_or_else(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Value>::error(&format!(
+                    "Failed to create response: {}",
+                    e
+                ))),
+            )
+                .into_response()
+        })
+}
+
+pub async fn list_versions(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> HandlerResponse {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    // get all versions, newest first
+    let versions = match sqlx::query!(
+        "
+        SELECT version, size, content_hash, mime_type, scan_status, uploaded_by, created_at
+        FROM document_versions
+        WHERE document_id = ?
+        ORDER BY version DESC
+        ",
+        id
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(versions) => versions,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch document versions: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let response = versions
+        .into_iter()
+        .map(|version| DocumentVersionResponse {
+            version: version.version,
+            size: version.size,
+            content_hash: version.content_hash,
+            mime_type: version.mime_type,
+            scan_status: version.scan_status,
+            uploaded_by: version.uploaded_by,
+            is_current: version.version == document.current_version,
+            created_at: version.created_at,
+        })
+        .collect::<Vec<DocumentVersionResponse>>();
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "List Document Versions",
+            json!(response),
+        )),
+    )
+}
+
+pub async fn download_version(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path((id, version)): Path<(i64, i32)>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response.into_response(),
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response.into_response();
+    }
+    let stored = match find_version(&db, id, version).await {
+        Ok(stored) => stored,
+        Err(response) => return response.into_response(),
+    };
+
+    if query.redirect.unwrap_or(false) {
+        if let Err(response) = ensure_clean(&stored.scan_status, serve_unscanned()) {
+            return response.into_response();
+        }
+        return Redirect::temporary(&signed_path(&format!(
+            "/document/{}/versions/{}/signed",
+            id, version
+        )))
+        .into_response();
+    }
+
+    let document = StoredDocument {
+        file_id: stored.file_id,
+        mime_type: stored.mime_type,
+        scan_status: stored.scan_status,
+        ..document
+    };
+    stream_document(&document, &headers).await
+}
+
+pub async fn signed_version_download(
+    Extension(db): Extension<MySqlPool>,
+    Path((id, version)): Path<(i64, i32)>,
+    Query(query): Query<SignedDownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    // The signature is the only credential on this route
+    if !verify_signed_path(
+        &format!("/document/{}/versions/{}/signed", id, version),
+        query.expires,
+        &query.signature,
+    ) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<Value>::error(
+                "Download link is invalid or has expired",
+            )),
+        )
+            .into_response();
+    }
+
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response.into_response(),
+    };
+    match find_version(&db, id, version).await {
+        Ok(stored) => {
+            let document = StoredDocument {
+                file_id: stored.file_id,
+                mime_type: stored.mime_type,
+                scan_status: stored.scan_status,
+                ..document
+            };
+            stream_document(&document, &headers).await
+        }
+        Err(response) => response.into_response(),
+    }
+}
+
+pub async fn rollback_version(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path((id, version)): Path<(i64, i32)>,
+) -> HandlerResponse {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+    if version == document.current_version {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error(&format!(
+                "Version {} is already the current version",
+                version
+            ))),
+        );
+    }
+    let stored = match find_version(&db, id, version).await {
+        Ok(stored) => stored,
+        Err(response) => return response,
+    };
+    if stored.scan_status == SCAN_INFECTED {
+        return (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error(&format!(
+                "Version {} is quarantined and can not be restored",
+                version
+            ))),
+        );
+    }
+
+    // Rolling back appends a new version that reuses the old storage object,
+    // so the history is never rewritten
+    match append_version(&db, id, &stored, claims.sub).await {
+        Ok((_, new_version)) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(
+                "Document rolled back",
+                json!({ "id": id, "version": new_version, "restored_from": version }),
+            )),
+        ),
+        Err(e) => save_error_response(e),
+    }
+}
+
+// columns selected when listing documents
+const DOCUMENT_COLUMNS: &str = "d.id, d.name, d.file_id, d.content_hash, d.user_id, d.size, d.mime_type, d.scan_status, d.current_version, d.description, d.metadata, d.folder_id, d.created_at, d.updated_at, d.deleted_at";
+
+// maximum page size of the document listing
+const MAX_PAGE_LIMIT: i64 = 100;
+
+// function for appending the listing filters to a documents query, owner is None for admins
+pub fn push_document_filters(
+    builder: &mut QueryBuilder<'_, MySql>,
+    query: &DocumentQuery,
+    owner: Option<i64>,
+) {
+    builder.push(" WHERE d.deleted_at IS NULL");
+
+    if let Some(owner) = owner {
+        builder.push(" AND d.user_id = ").push_bind(owner);
+    }
+    if let Some(keyword) = query.keyword.as_deref().filter(|k| !k.is_empty()) {
+        builder
+            .push(" AND d.name LIKE ")
+            .push_bind(format!("%{}%", escape_like(keyword)));
+    }
+    if let Some(tag) = query
+        .tag
+        .as_deref()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+    {
+        builder
+            .push(
+                " AND EXISTS (SELECT 1 FROM document_tags dt JOIN tags t ON t.id = dt.tag_id WHERE dt.document_id = d.id AND t.name = ",
+            )
+            .push_bind(tag)
+            .push(")");
+    }
+    if let Some(folder_id) = query.folder_id {
+        builder.push(" AND d.folder_id = ").push_bind(folder_id);
+    }
+    if let Some(mime_type) = query.mime_type.as_deref().filter(|m| !m.is_empty()) {
+        match mime_type.strip_suffix("/*") {
+            Some(prefix) => builder
+                .push(" AND d.mime_type LIKE ")
+                .push_bind(format!("{}/%", prefix)),
+            None => builder
+                .push(" AND d.mime_type = ")
+                .push_bind(mime_type.to_string()),
+        };
+    }
+    if let Some(date_from) = query.date_from {
+        builder.push(" AND d.created_at >= ").push_bind(date_from);
+    }
+    // date_to is inclusive, compare against the start of the next day
+    if let Some(date_to) = query.date_to.and_then(|date| date.succ_opt()) {
+        builder.push(" AND d.created_at < ").push_bind(date_to);
+    }
+}
+
+// function for loading the tags of several documents at once
+async fn load_tags(
+    db: &MySqlPool,
+    document_ids: &[i64],
+) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    if document_ids.is_empty() {
+        return Ok(tags);
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT dt.document_id, t.name FROM document_tags dt JOIN tags t ON t.id = dt.tag_id WHERE dt.document_id IN (",
+    );
+    let mut separated = builder.separated(", ");
+    for id in document_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(") ORDER BY t.name");
+
+    let rows = builder
+        .build_query_as::<(i64, String)>()
+        .fetch_all(db)
+        .await?;
+    for (document_id, name) in rows {
+        tags.entry(document_id).or_default().push(name);
+    }
+
+    Ok(tags)
+}
+
+// function for finding which of several documents have a thumbnail for their current version
+async fn load_thumbnails(
+    db: &MySqlPool,
+    document_ids: &[i64],
+) -> Result<HashSet<i64>, sqlx::Error> {
+    if document_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT d.id FROM documents d JOIN document_versions v ON v.document_id = d.id AND v.version = d.current_version JOIN blobs b ON b.id = v.blob_id WHERE b.thumbnail_file_id IS NOT NULL AND d.id IN (",
+    );
+    let mut separated = builder.separated(", ");
+    for id in document_ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    let ids = builder.build_query_scalar::<i64>().fetch_all(db).await?;
+
+    Ok(ids.into_iter().collect())
+}
+
+// function for the url a document thumbnail is served from
+fn thumbnail_url(document_id: i64) -> String {
+    format!("/document/{}/thumbnail", document_id)
+}
+
+pub async fn index(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<DocumentQuery>,
+) -> HandlerResponse {
+    let page: i64 = query.page.unwrap_or(1).max(1);
+    let limit: i64 = query.limit.unwrap_or(10).clamp(1, MAX_PAGE_LIMIT);
+    let offset = (page - 1) * limit;
+
+    if query
+        .date_from
+        .zip(query.date_to)
+        .is_some_and(|(date_from, date_to)| date_from > date_to)
+    {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                status: false,
+                message: "Validation failed".to_string(),
+                data: Some(json!({
+                    "date_from": ["Tanggal awal harus sebelum tanggal akhir"]
+                })),
+            }),
+        );
+    }
+
+    // Admins see every document, everyone else only their own
+    let owner = match is_admin(&db, claims.sub).await {
+        Ok(true) => None,
+        Ok(false) => Some(claims.sub),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check access: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let mut count_builder = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM documents d");
+    push_document_filters(&mut count_builder, &query, owner);
+    let total_count = match count_builder
+        .build_query_scalar::<i64>()
+        .fetch_one(&db)
+        .await
+    {
+        Ok(total) => total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count documents: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    // get documents of the requested page
+    let mut builder =
+        QueryBuilder::<MySql>::new(format!("SELECT {} FROM documents d", DOCUMENT_COLUMNS));
+    push_document_filters(&mut builder, &query, owner);
+    builder
+        .push(" ORDER BY d.id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    let mut documents = match builder.build_query_as::<Document>().fetch_all(&db).await {
+        Ok(documents) => documents,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch documents: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let ids = documents.iter().map(|d| d.id).collect::<Vec<i64>>();
+    let mut tags = match load_tags(&db, &ids).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to fetch tags: {}", e))),
+            );
+        }
+    };
+    let thumbnails = match load_thumbnails(&db, &ids).await {
+        Ok(thumbnails) => thumbnails,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch thumbnails: {}",
+                    e
+                ))),
+            );
+        }
+    };
+    for document in documents.iter_mut() {
+        document.tags = tags.remove(&document.id).unwrap_or_default();
+        document.thumbnail_url = thumbnails
+            .contains(&document.id)
+            .then(|| thumbnail_url(document.id));
+    }
+
+    // reponse documents with pagination
+    let document_response = DocumentResponse {
+        data: documents,
+        pagination: Pagination {
+            page,
+            limit,
+            total: total_count,
+            total_page: (total_count as f64 / limit as f64).ceil() as i64,
+        },
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "List Documents",
+            json!(document_response),
+        )),
+    )
+}
+
+// weight of a name match relative to a content match in the search score
+const SEARCH_NAME_WEIGHT: i64 = 2;
+
+// maximum page size of search results, every result carries a snippet of its content
+const MAX_SEARCH_LIMIT: i64 = 50;
+
+// function for appending the search conditions to a documents query, owner is None for admins,
+// words shorter than the full-text minimum still match names through LIKE
+pub fn push_search_filters(
+    builder: &mut QueryBuilder<'_, MySql>,
+    keyword: &str,
+    owner: Option<i64>,
+) {
+    builder.push(
+        " FROM documents d LEFT JOIN document_versions v ON v.document_id = d.id AND v.version = d.current_version LEFT JOIN blob_contents c ON c.blob_id = v.blob_id WHERE d.deleted_at IS NULL",
+    );
+
+    if let Some(owner) = owner {
+        builder.push(" AND d.user_id = ").push_bind(owner);
+    }
+    builder
+        .push(" AND (MATCH(d.name) AGAINST (")
+        .push_bind(keyword.to_string())
+        .push(" IN NATURAL LANGUAGE MODE) OR MATCH(c.content) AGAINST (")
+        .push_bind(keyword.to_string())
+        .push(" IN NATURAL LANGUAGE MODE) OR d.name LIKE ")
+        .push_bind(format!("%{}%", escape_like(keyword)))
+        .push(")");
+}
+
+pub async fn search(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<SearchQuery>,
+) -> HandlerResponse {
+    if let Err(errors) = query.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+    let keyword = query.q.trim();
+    let page: i64 = query.page.unwrap_or(1).max(1);
+    let limit: i64 = query.limit.unwrap_or(10).clamp(1, MAX_SEARCH_LIMIT);
+    let offset = (page - 1) * limit;
+
+    // Admins search every document, everyone else only their own
+    let owner = match is_admin(&db, claims.sub).await {
+        Ok(true) => None,
+        Ok(false) => Some(claims.sub),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check access: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let mut count_builder = QueryBuilder::<MySql>::new("SELECT COUNT(*)");
+    push_search_filters(&mut count_builder, keyword, owner);
+    let total_count = match count_builder
+        .build_query_scalar::<i64>()
+        .fetch_one(&db)
+        .await
+    {
+        Ok(total) => total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count documents: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    // get the best matches of the requested page
+    let mut builder = QueryBuilder::<MySql>::new(
+        "SELECT d.id, d.name, d.user_id, d.size, d.mime_type, d.scan_status, d.current_version, d.folder_id, d.created_at, d.updated_at, c.content, CAST(MATCH(d.name) AGAINST (",
+    );
+    builder
+        .push_bind(keyword.to_string())
+        .push(" IN NATURAL LANGUAGE MODE) * ")
+        .push_bind(SEARCH_NAME_WEIGHT)
+        .push(" + COALESCE(MATCH(c.content) AGAINST (")
+        .push_bind(keyword.to_string())
+        .push(" IN NATURAL LANGUAGE MODE), 0) AS DOUBLE) AS score");
+    push_search_filters(&mut builder, keyword, owner);
+    builder
+        .push(" ORDER BY score DESC, d.id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+    let mut results = match builder
+        .build_query_as::<DocumentSearchResult>()
+        .fetch_all(&db)
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to search documents: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let terms = search_terms(keyword);
+    for result in results.iter_mut() {
+        result.highlighted_name = highlight(&result.name, &terms);
+        result.snippet = result
+            .content
+            .take()
+            .and_then(|content| snippet(&content, &terms, SNIPPET_RADIUS));
+    }
+
+    let search_response = DocumentSearchResponse {
+        data: results,
+        pagination: Pagination {
+            page,
+            limit,
+            total: total_count,
+            total_page: (total_count as f64 / limit as f64).ceil() as i64,
+        },
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Search Documents",
+            json!(search_response),
+        )),
+    )
+}
+
+pub async fn show(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> HandlerResponse {
+    let mut document = match sqlx::query_as::<_, Document>(&format!(
+        "SELECT {} FROM documents d WHERE d.id = ? AND d.deleted_at IS NULL",
+        DOCUMENT_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&db)
+    .await
+    {
+        Ok(Some(document)) => document,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::error("Document not found")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch document: {}",
+                    e
+                ))),
+            );
+        }
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    document.tags = match load_tags(&db, &[id]).await {
+        Ok(mut tags) => tags.remove(&id).unwrap_or_default(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to fetch tags: {}", e))),
+            );
+        }
+    };
+    document.thumbnail_url = match load_thumbnails(&db, &[id]).await {
+        Ok(thumbnails) => thumbnails.contains(&id).then(|| thumbnail_url(id)),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch thumbnails: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Detail Document", json!(document))),
+    )
+}
+
+pub async fn rename(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<RenameRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    let name = payload.name.trim();
+    if let Err(e) = sqlx::query!("UPDATE documents SET name = ? WHERE id = ?", name, id)
+        .execute(&db)
+        .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to rename document: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document renamed",
+            json!({ "id": id, "name": name }),
+        )),
+    )
+}
+
+pub async fn delete(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> HandlerResponse {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    let blob_ids = match delete_document(&db, id).await {
+        Ok(blob_ids) => blob_ids,
+        Err(e) => return save_error_response(e),
+    };
+
+    // Storage objects are only deleted once the last document using them is gone
+    if let Err(e) = purge_unreferenced_blobs(&db, &blob_ids).await {
+        println!("Failed to purge blobs of document {}: {}", id, e);
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document deleted",
+            json!({ "id": id }),
+        )),
+    )
+}
+
+// buffer size used when streaming chunks into the merged file
+const MERGE_BUFFER_SIZE: usize = 64 * 1024;
+
+pub struct MergedFile {
+    pub size: u64,
+    pub content_hash: String,
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    NoChunks,
+    MissingChunk(u32),
+    ChecksumMismatch(u32),
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for MergeError {
+    fn from(e: std::io::Error) -> Self {
+        MergeError::Io(e)
+    }
+}
+
+// function for listing the chunks of an upload sorted by their numeric index
+pub async fn list_chunks(dir: &std::path::Path) -> Result<Vec<(u32, PathBuf)>, MergeError> {
+    let mut read_dir = match fs::read_dir(dir).await {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(MergeError::NoChunks),
+        Err(e) => return Err(MergeError::Io(e)),
+    };
+
+    let mut chunks = Vec::new();
+    while let Some(entry) = read_dir.next_entry().await? {
+        // Only "chunk_<n>" files count, checksum files and anything else are skipped
+        let name = entry.file_name();
+        let index = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("chunk_"))
+            .and_then(|index| parse_chunk_index(index).ok());
+        if let Some(index) = index {
+            chunks.push((index, entry.path()));
+        }
+    }
+
+    chunks.sort_by_key(|(index, _)| *index);
+    Ok(chunks)
+}
+
+// function for merging chunk files into output, verifying every chunk checksum on the way
+pub async fn merge_chunks(
+    dir: &std::path::Path,
+    output_path: &std::path::Path,
+) -> Result<MergedFile, MergeError> {
+    let chunks = list_chunks(dir).await?;
+    if chunks.is_empty() {
+        return Err(MergeError::NoChunks);
+    }
+
+    // Chunks must be contiguous from 0, a gap means the client never sent one
+    for (expected, (index, _)) in chunks.iter().enumerate() {
+        if *index != expected as u32 {
+            return Err(MergeError::MissingChunk(expected as u32));
+        }
+    }
+
+    let mut output =
+        BufWriter::with_capacity(MERGE_BUFFER_SIZE, fs::File::create(output_path).await?);
+    let mut file_hasher = Sha256::new();
+    let mut buffer = vec![0u8; MERGE_BUFFER_SIZE];
+    let mut size: u64 = 0;
+
+    for (index, path) in chunks {
+        let mut chunk = fs::File::open(&path).await?;
+        let mut chunk_hasher = Sha256::new();
+
+        loop {
+            let read = chunk.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            chunk_hasher.update(&buffer[..read]);
+            file_hasher.update(&buffer[..read]);
+            output.write_all(&buffer[..read]).await?;
+            size += read as u64;
+        }
+
+        // Re-verify the chunk against the checksum accepted at upload time
+        let expected = fs::read_to_string(path.with_extension("sha256"))
+            .await
+            .unwrap_or_default();
+        if !checksum_matches(&expected, &format!("{:x}", chunk_hasher.finalize())) {
+            return Err(MergeError::ChecksumMismatch(index));
+        }
+    }
+
+    output.flush().await?;
+
+    Ok(MergedFile {
+        size,
+        content_hash: format!("{:x}", file_hasher.finalize()),
+    })
+}
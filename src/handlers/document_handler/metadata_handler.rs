@@ -0,0 +1,64 @@
+use super::document_handler::{authorize_document, find_document};
+use crate::{
+    schemas::document_schema::MetadataRequest,
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{Extension, Json, extract::Path};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+use validator::Validate;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+pub async fn metadata(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<MetadataRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    // Both fields are replaced, sending null clears them
+    let metadata = payload.metadata.map(Value::Object);
+    if let Err(e) = sqlx::query!(
+        "UPDATE documents SET description = ?, metadata = ? WHERE id = ?",
+        payload.description,
+        metadata,
+        id
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to update metadata: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document metadata updated",
+            json!({ "id": id, "description": payload.description, "metadata": metadata }),
+        )),
+    )
+}
@@ -0,0 +1,9 @@
+pub mod document_handler;
+pub mod metadata_handler;
+pub mod move_handler;
+pub mod tag_handler;
+
+pub use document_handler::*;
+pub use metadata_handler::*;
+pub use move_handler::*;
+pub use tag_handler::*;
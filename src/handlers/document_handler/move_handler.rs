@@ -0,0 +1,85 @@
+use super::document_handler::{authorize_document, find_document};
+use crate::{
+    schemas::document_schema::MoveRequest,
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{Extension, Json, extract::Path};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+pub async fn move_document(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<MoveRequest>,
+) -> HandlerResponse {
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    // The target folder must belong to the owner of the document
+    if let Some(folder_id) = payload.folder_id {
+        match sqlx::query!("SELECT user_id FROM folders WHERE id = ?", folder_id)
+            .fetch_optional(&db)
+            .await
+        {
+            Ok(Some(folder)) if Some(folder.user_id) == document.user_id => {}
+            Ok(Some(_)) => {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ApiResponse::error(
+                        "Folder does not belong to the document owner",
+                    )),
+                );
+            }
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ApiResponse::error("Folder not found")),
+                );
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::error(&format!(
+                        "Failed to fetch folder: {}",
+                        e
+                    ))),
+                );
+            }
+        }
+    }
+
+    if let Err(e) = sqlx::query!(
+        "UPDATE documents SET folder_id = ? WHERE id = ?",
+        payload.folder_id,
+        id
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to move document: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document moved",
+            json!({ "id": id, "folder_id": payload.folder_id }),
+        )),
+    )
+}
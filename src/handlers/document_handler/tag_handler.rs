@@ -0,0 +1,100 @@
+use super::document_handler::{authorize_document, find_document};
+use crate::{
+    schemas::document_schema::TagsRequest,
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{Extension, Json, extract::Path};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+use validator::Validate;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+// function for normalizing tag names, trimmed, lowercased and without duplicates
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    normalized
+}
+
+// function for replacing the tags of a document
+async fn replace_tags(
+    db: &MySqlPool,
+    document_id: i64,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM document_tags WHERE document_id = ?",
+        document_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for tag in tags {
+        sqlx::query!("INSERT IGNORE INTO tags (name) VALUES (?)", tag)
+            .execute(&mut *tx)
+            .await?;
+        let tag = sqlx::query!("SELECT id FROM tags WHERE name = ?", tag)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO document_tags (document_id, tag_id) VALUES (?, ?)",
+            document_id,
+            tag.id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+pub async fn tags(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<TagsRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+
+    let document = match find_document(&db, id).await {
+        Ok(document) => document,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_document(&db, document.user_id, claims.sub).await {
+        return response;
+    }
+
+    let tags = normalize_tags(&payload.tags);
+    if let Err(e) = replace_tags(&db, id, &tags).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!("Failed to update tags: {}", e))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Document tags updated",
+            json!({ "id": id, "tags": tags }),
+        )),
+    )
+}
@@ -0,0 +1,1387 @@
+use crate::utils::{
+    checksum::{checksum_matches, is_sha256_hex, sha256_hex},
+    malware_scanner::{
+        ClamdAddress, ClamdScanner, DisabledScanner, EICAR_SIGNATURE, EicarScanner, SCAN_CLEAN,
+        SCAN_INFECTED, SCAN_PENDING, SCAN_UNSCANNED, ScanResult, Scanner, parse_clamd_reply,
+    },
+    search::{escape_like, highlight, search_terms, snippet},
+    signed_url::{sign_with, verify_with},
+    text_extract::{DOCX, collapse_whitespace, docx_xml_text, extract_text},
+    upload_path::{
+        chunk_dir, chunk_path, merged_path, parse_chunk_index, resolve_under, sanitize_file_stem,
+        validate_extension, validate_upload_id,
+    },
+    zip_stream::{UniqueNames, ZipStream, dos_date_time, entry_name},
+};
+
+use super::{
+    MergeError, MergedFile, StoredVersion, append_version, check_quota, claim_upload_session,
+    create_direct_upload, create_document, delete, download_version, ensure_clean, file_too_large,
+    find_document, find_version, list_versions, merge_chunks, push_document_filters,
+    push_search_filters, rollback_version, store_upload, type_not_allowed,
+};
+use crate::config::database::connect_test;
+use crate::config::upload::{
+    max_file_size, parse_allowed_types, parse_bytes, type_in_allowlist, user_quota,
+};
+use crate::handlers::document_handler::tag_handler::normalize_tags;
+use crate::handlers::upload_handler::tests::create_test_pdf;
+use crate::schemas::document_schema::{
+    CompletePayload, DirectUploadRequest, DocumentQuery, DownloadQuery,
+};
+use crate::utils::events::EventBus;
+use crate::utils::hub::Hub;
+use crate::utils::jwt::Claims;
+use crate::utils::upload_janitor::{parse_seconds, sweep};
+use crate::utils::upload_path::generate_upload_id;
+use axum::{
+    Extension, Json,
+    extract::{Path as UrlPath, Query},
+    http::{HeaderMap, StatusCode, header},
+};
+use chrono::NaiveDate;
+use sha2::{Digest, Sha256};
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// Helper function to create the upload root used by the tests
+pub fn create_test_root() -> PathBuf {
+    PathBuf::from("/srv/app/uploads")
+}
+
+// Helper function to create a document query without filters
+pub fn create_test_query() -> DocumentQuery {
+    DocumentQuery {
+        page: None,
+        limit: None,
+        keyword: None,
+        tag: None,
+        folder_id: None,
+        mime_type: None,
+        date_from: None,
+        date_to: None,
+    }
+}
+
+// Helper function to write a chunk and its checksum file like upload_chunk does
+pub async fn write_test_chunk(dir: &Path, index: u32, data: &[u8]) {
+    let path = dir.join(format!("chunk_{}", index));
+    tokio::fs::write(&path, data).await.unwrap();
+    tokio::fs::write(path.with_extension("sha256"), sha256_hex(data))
+        .await
+        .unwrap();
+}
+
+// Helper function to create a user id no other test run uses, so storage usage starts empty
+pub fn create_test_user_id() -> i64 {
+    i64::from_str_radix(&generate_upload_id()[..12], 16).unwrap()
+}
+
+// Helper function to create an upload session that has been idle for some seconds
+pub async fn create_test_upload_session(db: &MySqlPool, user_id: i64, idle_seconds: i64) -> String {
+    let upload_id = generate_upload_id();
+    sqlx::query!(
+        "
+        INSERT INTO upload_sessions (id, user_id, updated_at)
+        VALUES (?, ?, NOW() - INTERVAL ? SECOND)
+        ",
+        upload_id,
+        user_id,
+        idle_seconds
+    )
+    .execute(db)
+    .await
+    .unwrap();
+
+    upload_id
+}
+
+// Helper function to create a direct upload request for a plain text file of some size
+pub fn create_test_direct_upload(size: u64, mime_type: &str) -> DirectUploadRequest {
+    DirectUploadRequest {
+        name: "laporan".to_string(),
+        extention: "txt".to_string(),
+        size,
+        mime_type: mime_type.to_string(),
+        checksum: sha256_hex(b"laporan"),
+        document_id: None,
+    }
+}
+
+// Helper function to create a document whose versions are stored under these file ids
+// with these scan statuses, the last one is current
+pub async fn create_test_document(db: &MySqlPool, user_id: i64, versions: &[(&str, &str)]) -> i64 {
+    let mut document_id = 0;
+    for (index, (file_id, scan_status)) in versions.iter().enumerate() {
+        let stored = StoredVersion {
+            file_id: file_id.to_string(),
+            size: 7,
+            content_hash: Some(sha256_hex(file_id.as_bytes())),
+            mime_type: "text/plain".to_string(),
+            blob_id: None,
+            scan_status: scan_status.to_string(),
+        };
+        document_id = match index {
+            0 => create_document(db, "laporan", &stored, user_id).await,
+            _ => append_version(db, document_id, &stored, user_id).await,
+        }
+        .unwrap()
+        .0;
+    }
+
+    document_id
+}
+
+// Helper function to delete a test document with its versions
+pub async fn delete_test_document(db: &MySqlPool, document_id: i64) {
+    sqlx::query!(
+        "DELETE FROM document_versions WHERE document_id = ?",
+        document_id
+    )
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query!("DELETE FROM documents WHERE id = ?", document_id)
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+// Helper function to create a blob of content that was already scanned clean,
+// it has no public id so purging it never reaches storage
+pub async fn create_test_blob(db: &MySqlPool, content: &[u8]) -> i64 {
+    sqlx::query!(
+        "
+        INSERT INTO blobs (content_hash, file_id, resource_type, size, scan_status, scanned_at)
+        VALUES (?, ?, 'raw', ?, 'clean', NOW())
+        ",
+        sha256_hex(content),
+        format!("https://storage.test/{}", sha256_hex(content)),
+        content.len() as i64
+    )
+    .execute(db)
+    .await
+    .unwrap()
+    .last_insert_id() as i64
+}
+
+// Helper function to read the reference count of a blob, None once it was purged
+pub async fn blob_ref_count(db: &MySqlPool, blob_id: i64) -> Option<i32> {
+    sqlx::query_scalar!("SELECT ref_count FROM blobs WHERE id = ?", blob_id)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+// Helper function to store content as a new document the way complete_upload does,
+// returns the id of the document
+pub async fn store_test_upload(db: &MySqlPool, user_id: i64, content: &[u8]) -> i64 {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("merged");
+    tokio::fs::write(&output_path, content).await.unwrap();
+
+    let payload = CompletePayload {
+        file_id: generate_upload_id(),
+        name: "laporan".to_string(),
+        extention: "txt".to_string(),
+        checksum: sha256_hex(content),
+        document_id: None,
+    };
+    let merged = MergedFile {
+        size: content.len() as u64,
+        content_hash: sha256_hex(content),
+    };
+    let events = EventBus::new(Arc::new(Hub::new()));
+
+    let (status, Json(body)) =
+        store_upload(db, &events, &payload, user_id, &output_path, merged, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body.message);
+    assert!(!output_path.exists());
+
+    body.data.unwrap()["id"].as_i64().unwrap()
+}
+
+// Test sha256 digests of known inputs are lowercase hex
+#[tokio::test]
+async fn test_sha256_hex() {
+    assert_eq!(
+        sha256_hex(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert!(is_sha256_hex(&sha256_hex(b"chunk")));
+}
+
+// Test client checksums match regardless of case and surrounding whitespace
+#[tokio::test]
+async fn test_checksum_matches() {
+    let actual = sha256_hex(b"abc");
+
+    assert!(checksum_matches(&actual, &actual));
+    assert!(checksum_matches(&actual.to_uppercase(), &actual));
+    assert!(checksum_matches(&format!(" {}\n", actual), &actual));
+    assert!(!checksum_matches(&sha256_hex(b"abd"), &actual));
+    assert!(!checksum_matches(&actual[..63], &actual));
+    assert!(!checksum_matches("", &actual));
+}
+
+// Test only 64 hex digits are accepted as a sha256 checksum
+#[tokio::test]
+async fn test_is_sha256_hex() {
+    let digest = sha256_hex(b"abc");
+
+    assert!(is_sha256_hex(&digest));
+    assert!(is_sha256_hex(&digest.to_uppercase()));
+    assert!(!is_sha256_hex(""));
+    assert!(!is_sha256_hex(&digest[..63]));
+    assert!(!is_sha256_hex(&format!("{}0", digest)));
+    assert!(!is_sha256_hex(&"g".repeat(64)));
+    assert!(!is_sha256_hex(&format!("{} ", &digest[..63])));
+    // 32 two-byte characters are 64 bytes but not hex
+    assert!(!is_sha256_hex(&"é".repeat(32)));
+}
+
+#[tokio::test]
+async fn test_upload_id_validation() {
+    // Test valid upload ids
+    assert!(validate_upload_id("abc123").is_ok());
+    assert!(validate_upload_id("file-id_01").is_ok());
+    assert!(validate_upload_id(&"a".repeat(64)).is_ok());
+
+    // Test invalid upload ids
+    assert!(validate_upload_id("").is_err());
+    assert!(validate_upload_id(&"a".repeat(65)).is_err());
+    assert!(validate_upload_id("file id").is_err());
+    assert!(validate_upload_id("file.id").is_err());
+}
+
+#[tokio::test]
+async fn test_upload_id_traversal() {
+    // Test relative traversal attempts
+    assert!(validate_upload_id("..").is_err());
+    assert!(validate_upload_id("../../etc").is_err());
+    assert!(validate_upload_id("..\\..\\windows").is_err());
+    assert!(validate_upload_id("abc/../../etc").is_err());
+    assert!(validate_upload_id("%2e%2e%2fetc").is_err());
+
+    // Test that no chunk directory can be built from them
+    let root = create_test_root();
+    assert!(chunk_dir(&root, "../../etc").is_err());
+    assert!(chunk_path(&root, "..", 0).is_err());
+}
+
+#[tokio::test]
+async fn test_upload_id_absolute_path() {
+    let root = create_test_root();
+
+    // Test absolute paths on unix and windows
+    assert!(validate_upload_id("/etc/passwd").is_err());
+    assert!(validate_upload_id("C:\\Windows").is_err());
+    assert!(chunk_dir(&root, "/tmp").is_err());
+
+    // Test that resolve_under never accepts an absolute path
+    assert!(resolve_under(&root, Path::new("/etc/passwd")).is_err());
+    assert!(resolve_under(&root, Path::new("a/../../b")).is_err());
+    assert!(resolve_under(&root, Path::new("./a")).is_err());
+    assert!(resolve_under(&root, Path::new("")).is_err());
+}
+
+#[tokio::test]
+async fn test_upload_id_unicode_tricks() {
+    // Test fullwidth dots and slashes
+    assert!(validate_upload_id("\u{FF0E}\u{FF0E}\u{FF0F}etc").is_err());
+    // Test unicode division slash and fraction slash
+    assert!(validate_upload_id("..\u{2215}etc").is_err());
+    assert!(validate_upload_id("..\u{2044}etc").is_err());
+    // Test right-to-left override and zero width characters
+    assert!(validate_upload_id("abc\u{202E}gpj").is_err());
+    assert!(validate_upload_id("abc\u{200B}").is_err());
+    // Test null byte and non-ascii letters
+    assert!(validate_upload_id("abc\0").is_err());
+    assert!(validate_upload_id("fіle").is_err());
+}
+
+#[tokio::test]
+async fn test_chunk_index_parsing() {
+    // Test valid chunk indexes
+    assert_eq!(parse_chunk_index("0"), Ok(0));
+    assert_eq!(parse_chunk_index("42"), Ok(42));
+
+    // Test invalid chunk indexes
+    assert!(parse_chunk_index("").is_err());
+    assert!(parse_chunk_index("-1").is_err());
+    assert!(parse_chunk_index("+1").is_err());
+    assert!(parse_chunk_index("1/../../x").is_err());
+    assert!(parse_chunk_index("\u{0661}").is_err());
+    assert!(parse_chunk_index("99999999999").is_err());
+}
+
+#[tokio::test]
+async fn test_extension_validation() {
+    // Test valid extensions
+    assert_eq!(validate_extension("pdf"), Ok("pdf".to_string()));
+    assert_eq!(validate_extension(".DOCX"), Ok("docx".to_string()));
+
+    // Test invalid extensions
+    assert!(validate_extension("").is_err());
+    assert!(validate_extension(".").is_err());
+    assert!(validate_extension("pdf/../../x").is_err());
+    assert!(validate_extension("/etc/passwd").is_err());
+    assert!(validate_extension("tar.gz").is_err());
+    assert!(validate_extension("p\u{0064}f\u{202E}").is_err());
+    assert!(validate_extension("averyverylongextension").is_err());
+}
+
+#[tokio::test]
+async fn test_paths_stay_under_root() {
+    let root = create_test_root();
+
+    // Test chunk paths
+    let path = chunk_path(&root, "upload-1", 7).unwrap();
+    assert_eq!(path, root.join("upload-1").join("chunk_7"));
+    assert!(path.starts_with(&root));
+
+    // Test merged file path built from a hostile name
+    let merged = merged_path(&root, "../../etc/passwd", "abc-123", "txt").unwrap();
+    assert!(merged.starts_with(&root));
+    assert_eq!(merged.parent(), Some(root.as_path()));
+    assert_eq!(merged, root.join("etc_passwd-abc-123.txt"));
+
+    // Test uploads of the same name get their own merged file
+    assert_ne!(
+        merged_path(&root, "report", "upload-a", "pdf"),
+        merged_path(&root, "report", "upload-b", "pdf")
+    );
+
+    // Test merged file path with a hostile extension or upload id
+    assert!(merged_path(&root, "report", "abc", "/../../x").is_err());
+    assert!(merged_path(&root, "report", "../abc", "pdf").is_err());
+}
+
+#[tokio::test]
+async fn test_file_stem_sanitization() {
+    assert_eq!(sanitize_file_stem("laporan_bulanan"), "laporan_bulanan");
+    assert_eq!(sanitize_file_stem("../.."), "file");
+    assert_eq!(sanitize_file_stem("ｒｅｐｏｒｔ"), "file");
+    assert_eq!(sanitize_file_stem(&"a".repeat(300)).len(), 100);
+}
+
+#[tokio::test]
+async fn test_merge_orders_chunks_numerically() {
+    let dir = tempfile::tempdir().unwrap();
+    let chunks_dir = dir.path().join("upload-1");
+    tokio::fs::create_dir_all(&chunks_dir).await.unwrap();
+
+    // Write hundreds of chunks in reverse order so name and creation order are both wrong
+    let total = 350;
+    for index in (0..total).rev() {
+        write_test_chunk(&chunks_dir, index, format!("[{}]", index).as_bytes()).await;
+    }
+
+    let output_path = dir.path().join("merged.bin");
+    let merged = merge_chunks(&chunks_dir, &output_path).await.unwrap();
+
+    let expected = (0..total)
+        .map(|index| format!("[{}]", index))
+        .collect::<String>();
+    let actual = tokio::fs::read_to_string(&output_path).await.unwrap();
+    assert_eq!(actual, expected);
+    assert_eq!(merged.size, expected.len() as u64);
+    assert_eq!(merged.content_hash, sha256_hex(expected.as_bytes()));
+}
+
+#[tokio::test]
+async fn test_merge_reports_missing_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    for index in [0, 1, 2, 4] {
+        write_test_chunk(dir.path(), index, b"data").await;
+    }
+
+    let result = merge_chunks(dir.path(), &dir.path().join("merged.bin")).await;
+    assert!(matches!(result, Err(MergeError::MissingChunk(3))));
+}
+
+#[tokio::test]
+async fn test_merge_reports_corrupted_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    for index in 0..12 {
+        write_test_chunk(dir.path(), index, b"data").await;
+    }
+
+    // Corrupt chunk 10 after its checksum was accepted
+    tokio::fs::write(dir.path().join("chunk_10"), b"dat")
+        .await
+        .unwrap();
+
+    let result = merge_chunks(dir.path(), &dir.path().join("merged.bin")).await;
+    assert!(matches!(result, Err(MergeError::ChecksumMismatch(10))));
+}
+
+#[tokio::test]
+async fn test_merge_without_chunks() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let missing = merge_chunks(&dir.path().join("missing"), &dir.path().join("a.bin")).await;
+    assert!(matches!(missing, Err(MergeError::NoChunks)));
+
+    let empty = merge_chunks(dir.path(), &dir.path().join("b.bin")).await;
+    assert!(matches!(empty, Err(MergeError::NoChunks)));
+}
+
+#[tokio::test]
+#[ignore = "writes about 3 GB to the temp dir, run with `cargo test --release -- --ignored`"]
+async fn test_merge_multi_gb_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let chunks_dir = dir.path().join("upload-big");
+    tokio::fs::create_dir_all(&chunks_dir).await.unwrap();
+
+    // 48 chunks of 64 MiB each, only one chunk buffer is held at a time
+    let chunk_size = 64 * 1024 * 1024;
+    let total = 48u32;
+    let mut expected_hasher = Sha256::new();
+    let mut data = vec![0u8; chunk_size];
+    for index in 0..total {
+        data.iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b = (i as u32).wrapping_add(index) as u8);
+        expected_hasher.update(&data);
+        write_test_chunk(&chunks_dir, index, &data).await;
+    }
+    drop(data);
+
+    let output_path = dir.path().join("merged.bin");
+    let merged = merge_chunks(&chunks_dir, &output_path).await.unwrap();
+
+    let expected_size = chunk_size as u64 * total as u64;
+    assert_eq!(merged.size, expected_size);
+    assert_eq!(
+        tokio::fs::metadata(&output_path).await.unwrap().len(),
+        expected_size
+    );
+    assert_eq!(
+        merged.content_hash,
+        format!("{:x}", expected_hasher.finalize())
+    );
+}
+
+// Test a signed link verifies until it expires
+#[tokio::test]
+async fn test_signed_url_roundtrip() {
+    let secret = "test-secret";
+    let path = "/document/42/signed";
+    let expires = 1_700_000_300;
+    let signature = sign_with(secret, path, expires);
+    let now = 1_700_000_000;
+
+    // Test a valid signature before and at the expiry time
+    assert_eq!(signature.len(), 64);
+    assert!(verify_with(secret, path, expires, &signature, now));
+    assert!(verify_with(secret, path, expires, &signature, expires));
+
+    // Test hex case does not matter
+    let uppercase = signature.to_uppercase();
+    assert!(verify_with(secret, path, expires, &uppercase, now));
+}
+
+// Test expired, tampered or malformed signed links are refused
+#[tokio::test]
+async fn test_signed_url_rejects_expired_or_tampered() {
+    let secret = "test-secret";
+    let path = "/document/42/signed";
+    let expires = 1_700_000_300;
+    let signature = sign_with(secret, path, expires);
+    let now = 1_700_000_000;
+
+    // Test expired links
+    assert!(!verify_with(secret, path, expires, &signature, expires + 1));
+
+    // Test another document, a longer expiry and another secret
+    let other_path = "/document/43/signed";
+    assert!(!verify_with(secret, other_path, expires, &signature, now));
+    assert!(!verify_with(secret, path, expires + 3600, &signature, now));
+    assert!(!verify_with("other-secret", path, expires, &signature, now));
+
+    // Test malformed signatures
+    assert!(!verify_with(secret, path, expires, "", now));
+    assert!(!verify_with(secret, path, expires, &signature[..62], now));
+    assert!(!verify_with(secret, path, expires, &"zz".repeat(32), now));
+    assert!(!verify_with(secret, path, expires, "é", now));
+}
+
+#[tokio::test]
+async fn test_tag_normalization() {
+    let tags = vec![
+        " Kontrak ".to_string(),
+        "kontrak".to_string(),
+        "HR".to_string(),
+        "   ".to_string(),
+        "2026".to_string(),
+    ];
+
+    assert_eq!(normalize_tags(&tags), vec!["kontrak", "hr", "2026"]);
+    assert!(normalize_tags(&[]).is_empty());
+}
+
+#[tokio::test]
+async fn test_document_filters() {
+    // Test no filters for an admin
+    let mut builder = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM documents d");
+    push_document_filters(&mut builder, &create_test_query(), None);
+    assert_eq!(
+        builder.sql(),
+        "SELECT COUNT(*) FROM documents d WHERE d.deleted_at IS NULL"
+    );
+
+    // Test every filter for a regular user
+    let query = DocumentQuery {
+        keyword: Some("kontrak".to_string()),
+        tag: Some(" HR ".to_string()),
+        folder_id: Some(7),
+        mime_type: Some("image/*".to_string()),
+        date_from: NaiveDate::from_ymd_opt(2026, 1, 1),
+        date_to: NaiveDate::from_ymd_opt(2026, 1, 31),
+        ..create_test_query()
+    };
+    let mut builder = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM documents d");
+    push_document_filters(&mut builder, &query, Some(3));
+    let sql = builder.sql();
+    assert!(sql.contains("AND d.user_id = ?"));
+    assert!(sql.contains("AND d.name LIKE ?"));
+    assert!(sql.contains("AND t.name = ?)"));
+    assert!(sql.contains("AND d.folder_id = ?"));
+    assert!(sql.contains("AND d.mime_type LIKE ?"));
+    assert!(sql.contains("AND d.created_at >= ?"));
+    assert!(sql.contains("AND d.created_at < ?"));
+    assert_eq!(sql.matches('?').count(), 7);
+
+    // Test an exact mime type and blank filters
+    let query = DocumentQuery {
+        keyword: Some("".to_string()),
+        tag: Some("  ".to_string()),
+        mime_type: Some("application/pdf".to_string()),
+        ..create_test_query()
+    };
+    let mut builder = QueryBuilder::<MySql>::new("SELECT COUNT(*) FROM documents d");
+    push_document_filters(&mut builder, &query, None);
+    assert_eq!(
+        builder.sql(),
+        "SELECT COUNT(*) FROM documents d WHERE d.deleted_at IS NULL AND d.mime_type = ?"
+    );
+}
+
+// Helper function to start a fake clamd that reads one INSTREAM request and sends a reply
+pub async fn create_test_clamd(reply: &'static [u8]) -> (String, tokio::task::JoinHandle<Vec<u8>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut command = [0u8; 10];
+        socket.read_exact(&mut command).await.unwrap();
+        assert_eq!(&command, b"zINSTREAM\0");
+
+        // collect chunks until the zero length terminator
+        let mut received = Vec::new();
+        loop {
+            let length = socket.read_u32().await.unwrap() as usize;
+            if length == 0 {
+                break;
+            }
+            let mut chunk = vec![0u8; length];
+            socket.read_exact(&mut chunk).await.unwrap();
+            received.extend_from_slice(&chunk);
+        }
+
+        socket.write_all(reply).await.unwrap();
+        received
+    });
+
+    (address, handle)
+}
+
+// Test clamd replies are parsed into scan results
+#[tokio::test]
+async fn test_clamd_reply_parsing() {
+    assert_eq!(
+        parse_clamd_reply("stream: OK\0").unwrap(),
+        ScanResult::Clean
+    );
+    assert_eq!(
+        parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+        ScanResult::Infected("Win.Test.EICAR_HDB-1".to_string())
+    );
+    assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    assert!(parse_clamd_reply("").is_err());
+
+    assert_eq!(
+        ClamdAddress::parse("127.0.0.1:3310"),
+        ClamdAddress::Tcp("127.0.0.1:3310".to_string())
+    );
+    assert_eq!(
+        ClamdAddress::parse("/var/run/clamav/clamd.ctl"),
+        ClamdAddress::Unix(PathBuf::from("/var/run/clamav/clamd.ctl"))
+    );
+    assert_eq!(
+        ClamdAddress::parse("unix:///tmp/clamd.sock"),
+        ClamdAddress::Unix(PathBuf::from("/tmp/clamd.sock"))
+    );
+}
+
+// Test the EICAR stub flags the test string, also across read boundaries
+#[tokio::test]
+async fn test_eicar_scanner() {
+    let dir = tempfile::tempdir().unwrap();
+    let clean = dir.path().join("clean.txt");
+    let infected = dir.path().join("infected.txt");
+    let split = dir.path().join("split.bin");
+
+    std::fs::write(&clean, b"just a document").unwrap();
+    std::fs::write(&infected, EICAR_SIGNATURE).unwrap();
+    // the signature starts a few bytes before the first 64 KiB read ends
+    let mut data = vec![b'a'; 64 * 1024 - 10];
+    data.extend_from_slice(EICAR_SIGNATURE);
+    data.extend_from_slice(&[b'b'; 100]);
+    std::fs::write(&split, &data).unwrap();
+
+    let scanner = EicarScanner;
+    assert_eq!(scanner.scan(&clean).await.unwrap(), ScanResult::Clean);
+    assert_eq!(
+        scanner.scan(&infected).await.unwrap(),
+        ScanResult::Infected("Eicar-Test-Signature".to_string())
+    );
+    assert!(matches!(
+        scanner.scan(&split).await.unwrap(),
+        ScanResult::Infected(_)
+    ));
+    assert_eq!(
+        DisabledScanner.scan(&infected).await.unwrap(),
+        ScanResult::Skipped
+    );
+    assert_eq!(ScanResult::Skipped.status(), SCAN_UNSCANNED);
+}
+
+// Test the clamd client streams the whole file and reads the verdict
+#[tokio::test]
+async fn test_clamd_scanner() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("upload.bin");
+    let data = (0..200_000u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<u8>>();
+    std::fs::write(&path, &data).unwrap();
+
+    let (address, handle) = create_test_clamd(b"stream: Eicar-Test-Signature FOUND\0").await;
+    let scanner = ClamdScanner {
+        address: ClamdAddress::Tcp(address),
+        timeout: Duration::from_secs(5),
+    };
+    assert_eq!(
+        scanner.scan(&path).await.unwrap(),
+        ScanResult::Infected("Eicar-Test-Signature".to_string())
+    );
+    assert_eq!(handle.await.unwrap(), data);
+
+    // an unreachable daemon is an error, the upload stays pending
+    let unreachable = ClamdScanner {
+        address: ClamdAddress::Tcp("127.0.0.1:1".to_string()),
+        timeout: Duration::from_secs(5),
+    };
+    assert!(unreachable.scan(&path).await.is_err());
+}
+
+// Helper function to create a DOCX file with one paragraph per line
+pub fn create_test_docx(paragraphs: &[&str]) -> Vec<u8> {
+    let body = paragraphs
+        .iter()
+        .map(|p| {
+            format!(
+                "<w:p><w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+                p
+            )
+        })
+        .collect::<String>();
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\"><w:body>{}</w:body></w:document>",
+        body
+    );
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("[Content_Types].xml", options).unwrap();
+    zip.write_all(b"<Types/>").unwrap();
+    zip.start_file("word/document.xml", options).unwrap();
+    zip.write_all(xml.as_bytes()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+// Test text is extracted from plain text, PDF and DOCX files
+#[tokio::test]
+async fn test_text_extraction() {
+    // Test plain text is collapsed into single spaces
+    let text = extract_text(b"Laporan\r\n\tkeuangan   2026\n", "text/plain");
+    assert_eq!(text.as_deref(), Some("Laporan keuangan 2026"));
+
+    // Test PDF pages
+    let pdf = create_test_pdf(None, Some("Laporan keuangan tahunan"));
+    let text = extract_text(&pdf, "application/pdf").unwrap();
+    assert_eq!(text, "Laporan keuangan tahunan");
+
+    // Test DOCX paragraphs with escaped characters
+    let docx = create_test_docx(&["Kontrak kerja", "PT Maju &amp; Jaya &#x2014; 2026"]);
+    let text = extract_text(&docx, DOCX).unwrap();
+    assert_eq!(text, "Kontrak kerja PT Maju & Jaya \u{2014} 2026");
+
+    // Test files without text
+    assert_eq!(extract_text(b"   \n", "text/plain"), None);
+    assert_eq!(extract_text(b"not a pdf", "application/pdf"), None);
+    assert_eq!(extract_text(b"not a zip", DOCX), None);
+    assert_eq!(extract_text(b"\x89PNG", "image/png"), None);
+}
+
+// Test WordprocessingML markup becomes plain text
+#[tokio::test]
+async fn test_docx_xml_text() {
+    let xml = "<w:p><w:r><w:t>Satu</w:t><w:tab/><w:t>dua</w:t></w:r></w:p><w:p><w:r><w:t/><w:br/><w:t xml:space=\"preserve\"> tiga &lt;4&gt; &unknown;</w:t></w:r></w:p>";
+    assert_eq!(docx_xml_text(xml), "Satu\tdua\n\n tiga <4> &unknown;\n");
+
+    // Test text outside of runs is ignored
+    assert_eq!(docx_xml_text("<w:instrText>PAGE</w:instrText>"), "");
+}
+
+// Test whitespace collapsing stops at whole words within the limit
+#[tokio::test]
+async fn test_collapse_whitespace() {
+    assert_eq!(collapse_whitespace("  a  b\n c ", 100), "a b c");
+    assert_eq!(collapse_whitespace("satu dua tiga", 8), "satu dua");
+    assert_eq!(collapse_whitespace("satu dua tiga", 7), "satu");
+    assert_eq!(collapse_whitespace("panjang", 3), "");
+}
+
+// Test search queries are split into distinct lowercase words
+#[tokio::test]
+async fn test_search_terms() {
+    assert_eq!(
+        search_terms("Laporan, KEUANGAN laporan-2026"),
+        vec!["laporan", "keuangan", "2026"]
+    );
+    assert!(search_terms(" -- ").is_empty());
+}
+
+// Test whole-word matches are highlighted and everything else is escaped
+#[tokio::test]
+async fn test_highlight() {
+    let terms = search_terms("kontrak kerja");
+    assert_eq!(
+        highlight("Kontrak <Kerja> & kontraktor", &terms),
+        "<mark>Kontrak</mark> &lt;<mark>Kerja</mark>&gt; &amp; kontraktor"
+    );
+    assert_eq!(highlight("Tanpa hasil", &terms), "Tanpa hasil");
+
+    // Test the longest term wins when terms share a prefix
+    let terms = search_terms("data database");
+    assert_eq!(
+        highlight("database dan data", &terms),
+        "<mark>database</mark> dan <mark>data</mark>"
+    );
+}
+
+// Test snippets are cut around the first match on word boundaries
+#[tokio::test]
+async fn test_snippet() {
+    let terms = search_terms("anggaran");
+    let text = "Rapat membahas rencana kerja dan anggaran tahun depan bersama seluruh divisi";
+
+    assert_eq!(
+        snippet(text, &terms, 20).unwrap(),
+        "…rencana kerja dan <mark>anggaran</mark> tahun depan bersama…"
+    );
+    assert_eq!(
+        snippet(text, &terms, 200).unwrap(),
+        "Rapat membahas rencana kerja dan <mark>anggaran</mark> tahun depan bersama seluruh divisi"
+    );
+    assert_eq!(snippet(text, &search_terms("laporan"), 20), None);
+
+    // Test multi-byte text is cut on character boundaries
+    let terms = search_terms("ünïcode");
+    assert_eq!(
+        snippet("ééé ÜNÏCODE ààà", &terms, 3).unwrap(),
+        "…<mark>ÜNÏCODE</mark>…"
+    );
+}
+
+// Test LIKE wildcards in a keyword are matched literally
+#[tokio::test]
+async fn test_escape_like() {
+    assert_eq!(escape_like("laporan"), "laporan");
+    assert_eq!(escape_like("100%_final"), "100\\%\\_final");
+    assert_eq!(escape_like("C:\\docs"), "C:\\\\docs");
+}
+
+// Test search filters respect ownership and fall back to LIKE on names
+#[tokio::test]
+async fn test_search_filters() {
+    let mut builder = QueryBuilder::<MySql>::new("SELECT COUNT(*)");
+    push_search_filters(&mut builder, "kontrak", None);
+    let sql = builder.sql();
+    assert!(sql.contains("WHERE d.deleted_at IS NULL AND (MATCH(d.name)"));
+    assert!(!sql.contains("d.user_id"));
+    assert_eq!(sql.matches('?').count(), 3);
+
+    let mut builder = QueryBuilder::<MySql>::new("SELECT COUNT(*)");
+    push_search_filters(&mut builder, "kontrak", Some(3));
+    let sql = builder.sql();
+    assert!(sql.contains("AND d.user_id = ?"));
+    assert!(sql.contains("MATCH(c.content) AGAINST (? IN NATURAL LANGUAGE MODE)"));
+    assert!(sql.contains("OR d.name LIKE ?)"));
+    assert_eq!(sql.matches('?').count(), 4);
+}
+
+// Helper function to create a streamed ZIP archive from name and content pairs
+pub fn create_test_zip_stream(files: &[(&str, &[u8])], size_hint: u64) -> Vec<u8> {
+    let modified = NaiveDate::from_ymd_opt(2026, 3, 14)
+        .unwrap()
+        .and_hms_opt(9, 26, 54)
+        .unwrap();
+    let mut zip = ZipStream::new();
+    let mut archive = Vec::new();
+
+    for (name, data) in files {
+        archive.extend(zip.start_entry(name, modified, size_hint));
+        // write in small pieces like a storage download
+        for chunk in data.chunks(3) {
+            zip.write(chunk);
+            archive.extend_from_slice(chunk);
+        }
+        archive.extend(zip.finish_entry().unwrap());
+    }
+    archive.extend(zip.finish());
+    archive
+}
+
+// Test a streamed ZIP archive can be read back by a regular ZIP reader
+#[tokio::test]
+async fn test_zip_stream_roundtrip() {
+    let files: [(&str, &[u8]); 3] = [
+        ("laporan.pdf", b"%PDF-1.7 isi laporan"),
+        ("kosong.txt", b""),
+        ("Catatan rapat \u{2014} final.txt", b"baris satu\nbaris dua"),
+    ];
+
+    // Test regular and ZIP64 entries
+    for size_hint in [0, u64::from(u32::MAX)] {
+        let archive = create_test_zip_stream(&files, size_hint);
+        let mut reader = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        assert_eq!(reader.len(), files.len());
+
+        for (name, data) in files {
+            let mut entry = reader.by_name(name).unwrap();
+            assert_eq!(entry.compression(), zip::CompressionMethod::Stored);
+            assert_eq!(entry.size(), data.len() as u64);
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut content).unwrap();
+            assert_eq!(content, data);
+        }
+    }
+}
+
+// Test finishing an entry that was never started fails
+#[tokio::test]
+async fn test_zip_stream_requires_started_entry() {
+    let mut zip = ZipStream::new();
+    assert!(zip.finish_entry().is_err());
+
+    // Test an empty archive is only the end record
+    assert_eq!(ZipStream::new().finish().len(), 22);
+}
+
+// Test timestamps are converted to MS-DOS time and date
+#[tokio::test]
+async fn test_dos_date_time() {
+    let modified = NaiveDate::from_ymd_opt(2026, 3, 14)
+        .unwrap()
+        .and_hms_opt(9, 26, 54)
+        .unwrap();
+    assert_eq!(
+        dos_date_time(modified),
+        ((9 << 11) | (26 << 5) | 27, (46 << 9) | (3 << 5) | 14)
+    );
+
+    // Test dates before 1980 are clamped to the first MS-DOS date
+    let modified = NaiveDate::from_ymd_opt(1970, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    assert_eq!(dos_date_time(modified), (0, (1 << 5) | 1));
+}
+
+// Test document names become safe entry names with their extension
+#[tokio::test]
+async fn test_zip_entry_names() {
+    assert_eq!(entry_name("Laporan Q1", "pdf"), "Laporan Q1.pdf");
+    assert_eq!(entry_name("Laporan Q1.PDF", "pdf"), "Laporan Q1.PDF");
+    assert_eq!(entry_name("../../etc/passwd", "txt"), "_.._etc_passwd.txt");
+    assert_eq!(
+        entry_name("a\\b:c*d?\"e<f>g|h\n", "txt"),
+        "a_b_c_d__e_f_g_h_.txt"
+    );
+    assert_eq!(entry_name(" .. ", "txt"), "file.txt");
+    assert_eq!(entry_name(&"x".repeat(300), "pdf").chars().count(), 204);
+}
+
+// Test duplicate entry names get a counter before the extension
+#[tokio::test]
+async fn test_zip_unique_names() {
+    let mut names = UniqueNames::default();
+    assert_eq!(names.claim("laporan.pdf"), "laporan.pdf");
+    assert_eq!(names.claim("Laporan.PDF"), "Laporan (1).PDF");
+    assert_eq!(names.claim("laporan.pdf"), "laporan (2).pdf");
+    assert_eq!(names.claim("laporan (1).pdf"), "laporan (1) (1).pdf");
+    assert_eq!(names.claim("README"), "README");
+    assert_eq!(names.claim("README"), "README (1)");
+    assert_eq!(names.claim(".env"), ".env");
+    assert_eq!(names.claim(".env"), ".env (1)");
+}
+
+// Test janitor durations fall back to their default unless they are whole seconds
+#[tokio::test]
+async fn test_janitor_seconds_parsing() {
+    assert_eq!(parse_seconds(Some("90"), 86400), Duration::from_secs(90));
+    assert_eq!(parse_seconds(Some("0"), 86400), Duration::ZERO);
+    assert_eq!(parse_seconds(None, 86400), Duration::from_secs(86400));
+    assert_eq!(parse_seconds(Some(""), 3600), Duration::from_secs(3600));
+    assert_eq!(parse_seconds(Some("-5"), 3600), Duration::from_secs(3600));
+    assert_eq!(parse_seconds(Some("1.5"), 3600), Duration::from_secs(3600));
+    assert_eq!(parse_seconds(Some("1h"), 3600), Duration::from_secs(3600));
+}
+
+// Test parallel first chunks of one upload all claim the same session
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_claim_upload_session_race() {
+    let db = connect_test().await;
+    let upload_id = generate_upload_id();
+
+    let claims = (0..8).map(|_| {
+        let db = db.clone();
+        let upload_id = upload_id.clone();
+        tokio::spawn(async move { claim_upload_session(&db, &upload_id, 7, true).await })
+    });
+    for claim in claims {
+        assert_eq!(claim.await.unwrap().unwrap(), 0);
+    }
+
+    let (status, _) = claim_upload_session(&db, &upload_id, 8, true)
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = claim_upload_session(&db, &generate_upload_id(), 7, false)
+        .await
+        .unwrap_err();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", upload_id)
+        .execute(&db)
+        .await
+        .unwrap();
+}
+
+// Test the janitor expires idle sessions with their chunks and keeps active ones
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_janitor_expires_idle_sessions() {
+    let db = connect_test().await;
+    let root = tempfile::tempdir().unwrap();
+    let ttl = Duration::from_secs(3600);
+
+    let idle = create_test_upload_session(&db, 7, 7200).await;
+    let active = create_test_upload_session(&db, 7, 60).await;
+    for upload_id in [&idle, &active] {
+        let dir = chunk_dir(root.path(), upload_id).unwrap();
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        write_test_chunk(&dir, 0, b"chunk").await;
+    }
+
+    // A chunk directory without a session is removed once it is older than the TTL
+    let orphan = chunk_dir(root.path(), &generate_upload_id()).unwrap();
+    tokio::fs::create_dir_all(&orphan).await.unwrap();
+    std::fs::File::open(&orphan)
+        .unwrap()
+        .set_modified(SystemTime::now() - ttl * 2)
+        .unwrap();
+
+    let report = sweep(&db, root.path(), ttl).await.unwrap();
+    assert!(report.expired_sessions >= 1);
+    assert!(report.orphaned_chunk_dirs >= 1);
+    assert!(report.reclaimed_bytes >= 5 + 64);
+
+    let remaining = sqlx::query_scalar!(
+        "SELECT id FROM upload_sessions WHERE id IN (?, ?)",
+        idle,
+        active
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(remaining, vec![active.clone()]);
+    assert!(!chunk_dir(root.path(), &idle).unwrap().exists());
+    assert!(chunk_dir(root.path(), &active).unwrap().exists());
+    assert!(!orphan.exists());
+
+    sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", active)
+        .execute(&db)
+        .await
+        .unwrap();
+}
+
+// Test upload limits fall back to their default unless they are a whole number of bytes
+#[tokio::test]
+async fn test_upload_limit_parsing() {
+    assert_eq!(parse_bytes(Some("1048576"), 10), 1048576);
+    assert_eq!(parse_bytes(Some("0"), 10), 0);
+    assert_eq!(parse_bytes(None, 10), 10);
+    assert_eq!(parse_bytes(Some(""), 10), 10);
+    assert_eq!(parse_bytes(Some("-1"), 10), 10);
+    assert_eq!(parse_bytes(Some("10MB"), 10), 10);
+    assert_eq!(parse_bytes(Some("18446744073709551616"), 10), 10);
+}
+
+// Test the allowlist matches exact types and "image/*" style wildcards
+#[tokio::test]
+async fn test_allowed_type_wildcards() {
+    let allowed = parse_allowed_types(" image/*, Application/PDF ,,text/plain");
+    assert_eq!(allowed, vec!["image/*", "application/pdf", "text/plain"]);
+
+    assert!(type_in_allowlist(&allowed, "image/png"));
+    assert!(type_in_allowlist(&allowed, "image/heic"));
+    assert!(type_in_allowlist(&allowed, "application/pdf"));
+    assert!(type_in_allowlist(&allowed, "text/plain"));
+    assert!(!type_in_allowlist(&allowed, "text/html"));
+    assert!(!type_in_allowlist(&allowed, "application/pdfx"));
+    assert!(!type_in_allowlist(&allowed, "images/png"));
+    assert!(!type_in_allowlist(&allowed, "image"));
+    assert!(!type_in_allowlist(&[], "image/png"));
+
+    // SVG is only allowed when it is listed by name
+    assert!(!type_in_allowlist(&allowed, "image/svg+xml"));
+    let allowed = parse_allowed_types("image/*,image/svg+xml");
+    assert!(type_in_allowlist(&allowed, "image/svg+xml"));
+}
+
+// Test clean files are served, unscanned ones only when allowed, pending and infected ones are refused
+#[tokio::test]
+async fn test_ensure_clean() {
+    assert!(ensure_clean(SCAN_CLEAN, false).is_ok());
+    assert!(ensure_clean(SCAN_UNSCANNED, true).is_ok());
+
+    let (status, _) = ensure_clean(SCAN_UNSCANNED, false).unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = ensure_clean(SCAN_PENDING, true).unwrap_err();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = ensure_clean(SCAN_INFECTED, true).unwrap_err();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+// Test the limit responses carry the right status and what the client needs to know
+#[tokio::test]
+async fn test_upload_limit_responses() {
+    let (status, Json(body)) = file_too_large();
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(body.message.contains(&max_file_size().to_string()));
+
+    let (status, Json(body)) = type_not_allowed("application/x-msdownload");
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        body.message,
+        "File type application/x-msdownload is not allowed"
+    );
+}
+
+// Test the quota counts stored and in flight bytes, shrinking an upload is always allowed
+#[tokio::test]
+async fn test_quota_check() {
+    assert!(check_quota(100, 60, 30, 10).is_ok());
+    assert!(check_quota(100, 150, 0, -20).is_ok());
+    assert!(check_quota(100, 150, 0, 0).is_ok());
+
+    let (status, Json(body)) = check_quota(100, 60, 30, 11).unwrap_err();
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body.message, "Storage quota exceeded");
+    assert_eq!(
+        body.data,
+        Some(serde_json::json!({ "quota": 100, "used": 60, "in_flight": 30 }))
+    );
+}
+
+// Test oversized and disallowed direct uploads are refused before the database is used
+#[tokio::test]
+async fn test_direct_upload_limits() {
+    // a lazy pool never connects, any query would fail the request with 500
+    let db = MySqlPool::connect_lazy("mysql://localhost/unused").unwrap();
+    let claims = Claims { sub: 7, exp: 0 };
+
+    let (status, _) = create_direct_upload(
+        Extension(db.clone()),
+        Extension(claims.clone()),
+        Json(create_test_direct_upload(max_file_size() + 1, "text/plain")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let (status, _) = create_direct_upload(
+        Extension(db),
+        Extension(claims),
+        Json(create_test_direct_upload(10, "application/x-msdownload")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+// Test a direct upload that does not fit in the quota left is refused
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_direct_upload_quota() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let upload_id = create_test_upload_session(&db, user_id, 0).await;
+    sqlx::query!(
+        "UPDATE upload_sessions SET received_bytes = ? WHERE id = ?",
+        user_quota(),
+        upload_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let (status, Json(body)) = create_direct_upload(
+        Extension(db.clone()),
+        Extension(Claims {
+            sub: user_id,
+            exp: 0,
+        }),
+        Json(create_test_direct_upload(10, "text/plain")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body.message, "Storage quota exceeded");
+
+    sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", upload_id)
+        .execute(&db)
+        .await
+        .unwrap();
+}
+
+// Test versions are listed newest first and only the owner may list them
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_list_versions() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let id = create_test_document(&db, user_id, &[("file-1", "clean"), ("file-2", "clean")]).await;
+
+    let (status, Json(body)) = list_versions(
+        Extension(db.clone()),
+        Extension(Claims {
+            sub: user_id,
+            exp: 0,
+        }),
+        UrlPath(id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let versions = body.data.unwrap();
+    let versions = versions.as_array().unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0]["version"], 2);
+    assert_eq!(versions[0]["is_current"], true);
+    assert_eq!(versions[1]["version"], 1);
+    assert_eq!(versions[1]["is_current"], false);
+    assert_eq!(versions[1]["content_hash"], sha256_hex(b"file-1"));
+
+    let (status, _) = list_versions(
+        Extension(db.clone()),
+        Extension(Claims {
+            sub: create_test_user_id(),
+            exp: 0,
+        }),
+        UrlPath(id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    delete_test_document(&db, id).await;
+    let (status, _) = list_versions(
+        Extension(db),
+        Extension(Claims {
+            sub: user_id,
+            exp: 0,
+        }),
+        UrlPath(id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// Test a version download redirects to a signed link of that version once it is scanned clean
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_download_version() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let id =
+        create_test_document(&db, user_id, &[("file-1", "clean"), ("file-2", "pending")]).await;
+
+    let download = |user_id: i64, version: i32| {
+        download_version(
+            Extension(db.clone()),
+            Extension(Claims {
+                sub: user_id,
+                exp: 0,
+            }),
+            UrlPath((id, version)),
+            Query(DownloadQuery {
+                redirect: Some(true),
+            }),
+            HeaderMap::new(),
+        )
+    };
+
+    let response = download(user_id, 1).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.starts_with(&format!("/document/{}/versions/1/signed?expires=", id)));
+
+    assert_eq!(download(user_id, 2).await.status(), StatusCode::CONFLICT);
+    assert_eq!(download(user_id, 9).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        download(create_test_user_id(), 1).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    delete_test_document(&db, id).await;
+}
+
+// Test a rollback appends a new current version that reuses the old storage object
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_rollback_version() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let id = create_test_document(
+        &db,
+        user_id,
+        &[
+            ("file-1", "clean"),
+            ("file-2", "infected"),
+            ("file-3", "clean"),
+        ],
+    )
+    .await;
+
+    let rollback = |version: i32| {
+        rollback_version(
+            Extension(db.clone()),
+            Extension(Claims {
+                sub: user_id,
+                exp: 0,
+            }),
+            UrlPath((id, version)),
+        )
+    };
+
+    assert_eq!(rollback(3).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(rollback(2).await.0, StatusCode::CONFLICT);
+    assert_eq!(rollback(9).await.0, StatusCode::NOT_FOUND);
+
+    let (status, Json(body)) = rollback(1).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body.data,
+        Some(serde_json::json!({ "id": id, "version": 4, "restored_from": 1 }))
+    );
+
+    let restored = find_version(&db, id, 4).await.unwrap();
+    assert_eq!(restored.file_id, "file-1");
+    assert_eq!(restored.content_hash, Some(sha256_hex(b"file-1")));
+    let document = find_document(&db, id).await.unwrap();
+    assert_eq!(document.current_version, 4);
+    assert_eq!(document.file_id, "file-1");
+
+    // the versions that were rolled back over are kept
+    assert_eq!(find_version(&db, id, 3).await.unwrap().file_id, "file-3");
+
+    delete_test_document(&db, id).await;
+}
+
+// Test identical uploads share one blob and each takes a reference on it
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_upload_reuses_identical_blob() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let content = format!("laporan {}", generate_upload_id()).into_bytes();
+    let blob_id = create_test_blob(&db, &content).await;
+
+    let first = store_test_upload(&db, user_id, &content).await;
+    assert_eq!(blob_ref_count(&db, blob_id).await, Some(1));
+    let second = store_test_upload(&db, user_id, &content).await;
+    assert_eq!(blob_ref_count(&db, blob_id).await, Some(2));
+
+    // both documents point at the storage object of the blob
+    let file_id = format!("https://storage.test/{}", sha256_hex(&content));
+    for id in [first, second] {
+        let document = find_document(&db, id).await.unwrap();
+        assert_eq!(document.file_id, file_id);
+        assert_eq!(document.scan_status, "clean");
+    }
+
+    delete_test_document(&db, first).await;
+    delete_test_document(&db, second).await;
+    sqlx::query!("DELETE FROM blobs WHERE id = ?", blob_id)
+        .execute(&db)
+        .await
+        .unwrap();
+}
+
+// Test deleting documents drops their blob references and purges the blob with the last one
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_delete_releases_blob_references() {
+    let db = connect_test().await;
+    let user_id = create_test_user_id();
+    let content = format!("laporan {}", generate_upload_id()).into_bytes();
+    let blob_id = create_test_blob(&db, &content).await;
+
+    let first = store_test_upload(&db, user_id, &content).await;
+    let second = store_test_upload(&db, user_id, &content).await;
+    assert_eq!(blob_ref_count(&db, blob_id).await, Some(2));
+
+    let remove = |id: i64| {
+        delete(
+            Extension(db.clone()),
+            Extension(Claims {
+                sub: user_id,
+                exp: 0,
+            }),
+            UrlPath(id),
+        )
+    };
+
+    assert_eq!(remove(first).await.0, StatusCode::OK);
+    assert_eq!(blob_ref_count(&db, blob_id).await, Some(1));
+    assert_eq!(remove(first).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(blob_ref_count(&db, blob_id).await, Some(1));
+
+    assert_eq!(remove(second).await.0, StatusCode::OK);
+    assert_eq!(blob_ref_count(&db, blob_id).await, None);
+
+    delete_test_document(&db, first).await;
+    delete_test_document(&db, second).await;
+}
@@ -0,0 +1,326 @@
+use crate::{
+    schemas::folder_schema::{FolderNode, FolderRequest},
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{Extension, Json, extract::Path};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+use std::collections::{HashMap, HashSet};
+use validator::Validate;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+// function for building a nested tree from a flat folder list,
+// folders whose parent is missing are placed at the root
+pub fn build_folder_tree(
+    folders: Vec<(i64, String, Option<i64>)>,
+    document_counts: &HashMap<i64, i64>,
+) -> Vec<FolderNode> {
+    let ids = folders
+        .iter()
+        .map(|(id, _, _)| *id)
+        .collect::<HashSet<i64>>();
+    let mut children: HashMap<Option<i64>, Vec<FolderNode>> = HashMap::new();
+
+    for (id, name, parent_id) in folders {
+        let parent = parent_id.filter(|parent_id| ids.contains(parent_id) && *parent_id != id);
+        children.entry(parent).or_default().push(FolderNode {
+            id,
+            name,
+            parent_id,
+            document_count: document_counts.get(&id).copied().unwrap_or(0),
+            children: Vec::new(),
+        });
+    }
+
+    let mut visited = HashSet::new();
+    attach_children(None, &mut children, &mut visited)
+}
+
+// function for taking the children of a folder and attaching their own children recursively
+fn attach_children(
+    parent: Option<i64>,
+    children: &mut HashMap<Option<i64>, Vec<FolderNode>>,
+    visited: &mut HashSet<i64>,
+) -> Vec<FolderNode> {
+    let mut nodes = children.remove(&parent).unwrap_or_default();
+
+    for node in nodes.iter_mut() {
+        // a corrupted parent chain must never loop forever
+        if visited.insert(node.id) {
+            node.children = attach_children(Some(node.id), children, visited);
+        }
+    }
+
+    nodes
+}
+
+// function for checking that moving folder_id under new_parent would create a cycle
+pub fn would_create_cycle(
+    parents: &HashMap<i64, Option<i64>>,
+    folder_id: i64,
+    new_parent: Option<i64>,
+) -> bool {
+    let mut current = new_parent;
+    let mut seen = HashSet::new();
+
+    while let Some(id) = current {
+        if id == folder_id || !seen.insert(id) {
+            return true;
+        }
+        current = parents.get(&id).copied().flatten();
+    }
+
+    false
+}
+
+// function for loading the parent of every folder of a user
+async fn folder_parents(
+    db: &MySqlPool,
+    user_id: i64,
+) -> Result<HashMap<i64, Option<i64>>, sqlx::Error> {
+    let folders = sqlx::query!(
+        "SELECT id, parent_id FROM folders WHERE user_id = ?",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(folders
+        .into_iter()
+        .map(|folder| (folder.id, folder.parent_id))
+        .collect())
+}
+
+pub async fn store(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<FolderRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+
+    let parents = match folder_parents(&db, claims.sub).await {
+        Ok(parents) => parents,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch folders: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    // Folders can only be nested inside the user's own folders
+    if payload
+        .parent_id
+        .is_some_and(|parent_id| !parents.contains_key(&parent_id))
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Parent folder not found")),
+        );
+    }
+
+    let name = payload.name.trim();
+    let result = match sqlx::query!(
+        "INSERT INTO folders (name, parent_id, user_id) VALUES (?, ?, ?)",
+        name,
+        payload.parent_id,
+        claims.sub
+    )
+    .execute(&db)
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to create folder: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::CREATED,
+        Json(ApiResponse::success(
+            "Folder created",
+            json!({
+                "id": result.last_insert_id(),
+                "name": name,
+                "parent_id": payload.parent_id,
+            }),
+        )),
+    )
+}
+
+pub async fn update(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<FolderRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+
+    let parents = match folder_parents(&db, claims.sub).await {
+        Ok(parents) => parents,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch folders: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    if !parents.contains_key(&id) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Folder not found")),
+        );
+    }
+    if payload
+        .parent_id
+        .is_some_and(|parent_id| !parents.contains_key(&parent_id))
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Parent folder not found")),
+        );
+    }
+
+    // A folder can never be moved into itself or one of its descendants
+    if would_create_cycle(&parents, id, payload.parent_id) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::error(
+                "Folder cannot be moved into itself or one of its subfolders",
+            )),
+        );
+    }
+
+    let name = payload.name.trim();
+    if let Err(e) = sqlx::query!(
+        "UPDATE folders SET name = ?, parent_id = ? WHERE id = ?",
+        name,
+        payload.parent_id,
+        id
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to update folder: {}",
+                e
+            ))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Folder updated",
+            json!({ "id": id, "name": name, "parent_id": payload.parent_id }),
+        )),
+    )
+}
+
+pub async fn tree(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+) -> HandlerResponse {
+    // get all folders of the user
+    let folders = match sqlx::query!(
+        "
+        SELECT id, name, parent_id
+        FROM folders
+        WHERE user_id = ?
+        ORDER BY name
+        ",
+        claims.sub
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(folders) => folders,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch folders: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    // count the documents directly inside each folder
+    let counts = match sqlx::query!(
+        "
+        SELECT folder_id AS `folder_id!: i64`, COUNT(*) AS `total!: i64`
+        FROM documents
+        WHERE user_id = ?
+        AND folder_id IS NOT NULL
+        AND deleted_at IS NULL
+        GROUP BY folder_id
+        ",
+        claims.sub
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(counts) => counts,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count documents: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let document_counts = counts
+        .into_iter()
+        .map(|count| (count.folder_id, count.total))
+        .collect::<HashMap<i64, i64>>();
+    let tree = build_folder_tree(
+        folders
+            .into_iter()
+            .map(|folder| (folder.id, folder.name, folder.parent_id))
+            .collect(),
+        &document_counts,
+    );
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Folder Tree", json!(tree))),
+    )
+}
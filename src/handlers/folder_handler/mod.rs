@@ -0,0 +1,3 @@
+pub mod folder_handler;
+
+pub use folder_handler::*;
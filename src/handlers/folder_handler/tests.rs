@@ -0,0 +1,70 @@
+use super::{build_folder_tree, would_create_cycle};
+use std::collections::HashMap;
+
+// Helper function to create a flat folder list
+pub fn create_test_folders() -> Vec<(i64, String, Option<i64>)> {
+    vec![
+        (1, "Kontrak".to_string(), None),
+        (2, "2025".to_string(), Some(1)),
+        (3, "2026".to_string(), Some(1)),
+        (4, "Q1".to_string(), Some(3)),
+        (5, "Invoice".to_string(), None),
+    ]
+}
+
+#[tokio::test]
+async fn test_build_folder_tree() {
+    let counts = HashMap::from([(1, 3), (4, 7)]);
+    let tree = build_folder_tree(create_test_folders(), &counts);
+
+    // Test root folders keep their order
+    assert_eq!(tree.iter().map(|f| f.id).collect::<Vec<i64>>(), vec![1, 5]);
+    assert_eq!(tree[0].document_count, 3);
+    assert_eq!(tree[1].document_count, 0);
+
+    // Test nested children
+    assert_eq!(
+        tree[0].children.iter().map(|f| f.id).collect::<Vec<i64>>(),
+        vec![2, 3]
+    );
+    assert_eq!(tree[0].children[1].children[0].id, 4);
+    assert_eq!(tree[0].children[1].children[0].document_count, 7);
+}
+
+#[tokio::test]
+async fn test_build_folder_tree_with_broken_parents() {
+    // Test a missing parent, a self reference and a loop between two folders
+    let folders = vec![
+        (1, "Orphan".to_string(), Some(99)),
+        (2, "Self".to_string(), Some(2)),
+        (3, "A".to_string(), Some(4)),
+        (4, "B".to_string(), Some(3)),
+    ];
+    let tree = build_folder_tree(folders, &HashMap::new());
+
+    // Orphans and self references end up at the root, the loop is never reachable
+    assert_eq!(tree.iter().map(|f| f.id).collect::<Vec<i64>>(), vec![1, 2]);
+    assert!(tree.iter().all(|f| f.children.is_empty()));
+}
+
+#[tokio::test]
+async fn test_folder_move_cycle_detection() {
+    let parents = create_test_folders()
+        .into_iter()
+        .map(|(id, _, parent_id)| (id, parent_id))
+        .collect::<HashMap<i64, Option<i64>>>();
+
+    // Test valid moves
+    assert!(!would_create_cycle(&parents, 4, None));
+    assert!(!would_create_cycle(&parents, 4, Some(5)));
+    assert!(!would_create_cycle(&parents, 3, Some(2)));
+
+    // Test moving a folder into itself or one of its descendants
+    assert!(would_create_cycle(&parents, 1, Some(1)));
+    assert!(would_create_cycle(&parents, 1, Some(4)));
+    assert!(would_create_cycle(&parents, 3, Some(4)));
+
+    // Test an existing loop in the data
+    let broken = HashMap::from([(1, Some(2)), (2, Some(1)), (3, None)]);
+    assert!(would_create_cycle(&broken, 3, Some(1)));
+}
@@ -1,6 +1,10 @@
 pub mod auth_handler;
 pub mod data_handler;
+pub mod direct_message_handler;
 pub mod document_handler;
+pub mod folder_handler;
+pub mod moderation_handler;
+pub mod room_handler;
 pub mod upload_handler;
 pub mod user_handler;
 pub mod websocket_handler;
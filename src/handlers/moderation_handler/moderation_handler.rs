@@ -0,0 +1,279 @@
+use crate::{
+    schemas::{moderation_schema::ChatSanction, room_schema::ChatMessage},
+    utils::{jwt::Claims, response::ApiResponse},
+};
+use axum::{Extension, Json};
+use chrono::{DateTime, Duration, Utc};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+// users with this role or admins may moderate the chat
+pub const ROLE_MODERATOR: &str = "moderator";
+
+// kinds of sanctions
+pub const SANCTION_MUTE: &str = "mute";
+pub const SANCTION_BAN: &str = "ban";
+// longest reason of a sanction in characters, the column is VARCHAR(255)
+pub const MAX_SANCTION_REASON_LENGTH: usize = 255;
+
+// function for checking a user role may moderate the chat
+pub fn is_moderator_role(role: &str) -> bool {
+    role == "admin" || role == ROLE_MODERATOR
+}
+
+// function for checking a user may change a room message, only its author or a moderator may
+pub fn may_change_message(user_id: i64, moderator: bool, author_id: i64) -> bool {
+    moderator || user_id == author_id
+}
+
+// function for getting when a sanction of some seconds ends,
+// None lasts until it is lifted, as does a duration too long to count
+pub fn sanction_expiry(now: DateTime<Utc>, seconds: Option<u64>) -> Option<DateTime<Utc>> {
+    seconds
+        .and_then(|seconds| i64::try_from(seconds).ok())
+        .and_then(Duration::try_seconds)
+        .and_then(|duration| now.checked_add_signed(duration))
+}
+
+// function for getting when the running sanction of a kind ends,
+// one lasting until it is lifted never ends on its own
+pub fn sanction_until(sanctions: &[ChatSanction], kind: &str) -> Option<DateTime<Utc>> {
+    sanctions
+        .iter()
+        .find(|sanction| sanction.kind == kind)
+        .map(|sanction| sanction.expires_at.unwrap_or(DateTime::<Utc>::MAX_UTC))
+}
+
+// function for checking the reason given for a sanction fits its column
+pub fn check_sanction_reason(reason: Option<&str>) -> Result<(), String> {
+    match reason {
+        Some(reason) if reason.chars().count() > MAX_SANCTION_REASON_LENGTH => Err(format!(
+            "Reason is longer than {} characters",
+            MAX_SANCTION_REASON_LENGTH
+        )),
+        _ => Ok(()),
+    }
+}
+
+// function for checking the running sanctions of a user allow it to chat,
+// returns what the user is told otherwise
+pub fn sanction_refusal(sanctions: &[ChatSanction]) -> Result<(), String> {
+    if sanction_until(sanctions, SANCTION_BAN).is_some() {
+        return Err("You are banned from the chat".to_string());
+    }
+    match sanction_until(sanctions, SANCTION_MUTE) {
+        Some(until) if until != DateTime::<Utc>::MAX_UTC => {
+            Err(format!("You are muted until {}", until.to_rfc3339()))
+        }
+        Some(_) => Err("You are muted".to_string()),
+        None => Ok(()),
+    }
+}
+
+// function for finding the role of a user that may be sanctioned
+pub async fn find_user_role(db: &MySqlPool, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+    let user = sqlx::query!(
+        "SELECT role FROM users WHERE id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(user.map(|user| user.role))
+}
+
+// function for loading the sanctions of a user that are still running
+pub async fn active_sanctions(
+    db: &MySqlPool,
+    user_id: i64,
+) -> Result<Vec<ChatSanction>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatSanction,
+        "
+        SELECT s.user_id, COALESCE(u.name, '') AS `username!: String`, s.kind, s.reason,
+            s.created_by, s.expires_at, s.created_at
+        FROM chat_sanctions s
+        LEFT JOIN users u ON u.id = s.user_id
+        WHERE s.user_id = ?
+        AND (s.expires_at IS NULL OR s.expires_at > NOW())
+        ",
+        user_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+// function for storing a sanction, it replaces a running sanction of the same kind
+pub async fn store_sanction(
+    db: &MySqlPool,
+    user_id: i64,
+    kind: &str,
+    reason: Option<&str>,
+    created_by: i64,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        INSERT INTO chat_sanctions (user_id, kind, reason, created_by, expires_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON DUPLICATE KEY UPDATE
+            reason = VALUES(reason),
+            created_by = VALUES(created_by),
+            expires_at = VALUES(expires_at),
+            created_at = CURRENT_TIMESTAMP
+        ",
+        user_id,
+        kind,
+        reason,
+        created_by,
+        expires_at
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// function for lifting a running sanction, false when the user had none of that kind
+pub async fn lift_sanction(db: &MySqlPool, user_id: i64, kind: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM chat_sanctions
+        WHERE user_id = ?
+        AND kind = ?
+        AND (expires_at IS NULL OR expires_at > NOW())
+        ",
+        user_id,
+        kind
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// function for finding a room message that was not deleted
+pub async fn find_room_message(
+    db: &MySqlPool,
+    id: i64,
+) -> Result<Option<ChatMessage>, sqlx::Error> {
+    sqlx::query_as!(
+        ChatMessage,
+        "
+        SELECT m.id, r.name AS room, m.user_id, COALESCE(u.name, '') AS `username!: String`,
+            m.kind, m.message, m.created_at, m.edited_at
+        FROM chat_messages m
+        JOIN rooms r ON r.id = m.room_id
+        LEFT JOIN users u ON u.id = m.user_id
+        WHERE m.id = ?
+        AND m.deleted_at IS NULL
+        ",
+        id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+// function for changing the text of a room message
+pub async fn edit_room_message(
+    db: &MySqlPool,
+    id: i64,
+    message: &str,
+    time: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE chat_messages SET message = ?, edited_at = ? WHERE id = ? AND deleted_at IS NULL",
+        message,
+        time,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// function for deleting a room message, the entry is kept without its text so ids stay in order
+pub async fn delete_room_message(
+    db: &MySqlPool,
+    id: i64,
+    deleted_by: i64,
+    time: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE chat_messages
+        SET message = NULL, deleted_at = ?, deleted_by = ?
+        WHERE id = ?
+        AND deleted_at IS NULL
+        ",
+        time,
+        deleted_by,
+        id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// function for listing the running mutes and bans, only moderators may see them
+pub async fn sanctions(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+) -> HandlerResponse {
+    match find_user_role(&db, claims.sub).await {
+        Ok(Some(role)) if is_moderator_role(&role) => {}
+        Ok(_) => {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(ApiResponse::error("Moderator access required")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to check role: {}", e))),
+            );
+        }
+    }
+
+    let sanctions = match sqlx::query_as!(
+        ChatSanction,
+        "
+        SELECT s.user_id, COALESCE(u.name, '') AS `username!: String`, s.kind, s.reason,
+            s.created_by, s.expires_at, s.created_at
+        FROM chat_sanctions s
+        LEFT JOIN users u ON u.id = s.user_id
+        WHERE s.expires_at IS NULL OR s.expires_at > NOW()
+        ORDER BY s.created_at DESC
+        "
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(sanctions) => sanctions,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch sanctions: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("List Sanctions", json!(sanctions))),
+    )
+}
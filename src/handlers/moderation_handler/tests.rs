@@ -0,0 +1,220 @@
+use super::*;
+use crate::config::moderation::{parse_link_policy, parse_list};
+use crate::utils::hub::Hub;
+use crate::utils::moderation::{
+    ContentFilter, LinkPolicy, RateBucket, RateLimit, Verdict, WordAction, WordFilter,
+    filter_message, link_hosts,
+};
+use std::time::{Duration as StdDuration, Instant};
+
+// Helper function to create a word filter
+fn create_test_word_filter(action: WordAction) -> WordFilter {
+    WordFilter {
+        words: parse_list("spam, Scam"),
+        action,
+    }
+}
+
+// Helper function to create a running sanction
+fn create_test_sanction(kind: &str, expires_at: Option<DateTime<Utc>>) -> ChatSanction {
+    ChatSanction {
+        user_id: 3,
+        username: "Siti".to_string(),
+        kind: kind.to_string(),
+        reason: None,
+        created_by: 1,
+        expires_at,
+        created_at: None,
+    }
+}
+
+// Test blocked words are masked as whole words regardless of case
+#[test]
+fn test_word_filter_mask() {
+    let filter = create_test_word_filter(WordAction::Mask);
+
+    assert_eq!(
+        filter.check("No SPAM, spammer or scam!"),
+        Verdict::Allow("No ****, spammer or ****!".to_string())
+    );
+    assert_eq!(filter.check("halo"), Verdict::Allow("halo".to_string()));
+}
+
+// Test the word filter can refuse the whole message
+#[test]
+fn test_word_filter_reject() {
+    let filter = create_test_word_filter(WordAction::Reject);
+
+    assert!(matches!(filter.check("this is spam"), Verdict::Reject(_)));
+    assert_eq!(
+        filter.check("spammer"),
+        Verdict::Allow("spammer".to_string())
+    );
+}
+
+// Test the hosts of links are found with or without a scheme
+#[test]
+fn test_link_hosts() {
+    assert_eq!(
+        link_hosts("see https://Docs.Example.com/page?x=1, www.test.org. and http://a.io:8080"),
+        vec!["docs.example.com", "www.test.org", "a.io"]
+    );
+    assert!(link_hosts("no links here, example.com is plain text").is_empty());
+}
+
+// Test the link policies
+#[test]
+fn test_link_policy() {
+    let message = "read https://docs.example.com/guide";
+
+    assert_eq!(parse_link_policy("", ""), LinkPolicy::Allow);
+    assert_eq!(
+        LinkPolicy::Allow.check(message),
+        Verdict::Allow(message.to_string())
+    );
+    assert_eq!(
+        parse_link_policy("deny", "").check(message),
+        Verdict::Reject("Links are not allowed".to_string())
+    );
+
+    let domains = parse_link_policy("Domains", "example.com, rust-lang.org");
+    assert_eq!(domains.check(message), Verdict::Allow(message.to_string()));
+    assert_eq!(
+        domains.check("https://badexample.com"),
+        Verdict::Reject("Links to badexample.com are not allowed".to_string())
+    );
+}
+
+// Test filters run in order and a rewrite reaches the next filter
+#[test]
+fn test_filter_message() {
+    let filters: Vec<Box<dyn ContentFilter>> = vec![
+        Box::new(create_test_word_filter(WordAction::Mask)),
+        Box::new(LinkPolicy::Deny),
+    ];
+
+    assert_eq!(
+        filter_message(&filters, "spam again"),
+        Ok("**** again".to_string())
+    );
+    assert_eq!(
+        filter_message(&filters, "spam www.scam.com"),
+        Err("Links are not allowed".to_string())
+    );
+    assert_eq!(filter_message(&[], "spam"), Ok("spam".to_string()));
+}
+
+// Test a rate bucket allows a burst and refills evenly over the window
+#[test]
+fn test_rate_bucket() {
+    let limit = RateLimit {
+        messages: 2,
+        window: StdDuration::from_secs(10),
+    };
+    let start = Instant::now();
+    let mut bucket = RateBucket::new(limit, start);
+
+    assert_eq!(bucket.take(limit, start), Ok(()));
+    assert_eq!(bucket.take(limit, start), Ok(()));
+    let wait = bucket.take(limit, start).unwrap_err();
+    assert!(wait > StdDuration::from_secs(4) && wait <= StdDuration::from_secs(5));
+
+    assert_eq!(
+        bucket.take(limit, start + StdDuration::from_secs(5)),
+        Ok(())
+    );
+    assert!(
+        bucket
+            .take(limit, start + StdDuration::from_secs(5))
+            .is_err()
+    );
+
+    assert!(!bucket.is_full(limit, start + StdDuration::from_secs(10)));
+    assert!(bucket.is_full(limit, start + StdDuration::from_secs(15)));
+}
+
+// Test the connections of a user share one rate limit
+#[test]
+fn test_hub_rate_limit() {
+    let hub = Hub::new();
+    let limit = RateLimit {
+        messages: 1,
+        window: StdDuration::from_secs(60),
+    };
+    let now = Instant::now();
+
+    assert!(hub.take_message(3, limit, now).is_ok());
+    assert!(hub.take_message(3, limit, now).is_err());
+    assert!(hub.take_message(4, limit, now).is_ok());
+}
+
+// Test who may moderate and change messages
+#[test]
+fn test_moderator_rules() {
+    assert!(is_moderator_role("admin"));
+    assert!(is_moderator_role(ROLE_MODERATOR));
+    assert!(!is_moderator_role("user"));
+
+    assert!(may_change_message(3, false, 3));
+    assert!(!may_change_message(3, false, 4));
+    assert!(may_change_message(3, true, 4));
+}
+
+// Test sanction durations, a missing one lasts until it is lifted
+#[test]
+fn test_sanction_expiry() {
+    let now = Utc::now();
+
+    assert_eq!(
+        sanction_expiry(now, Some(60)),
+        Some(now + Duration::seconds(60))
+    );
+    assert_eq!(sanction_expiry(now, None), None);
+    assert_eq!(sanction_expiry(now, Some(u64::MAX)), None);
+
+    let until = now + Duration::minutes(5);
+    let sanctions = vec![
+        create_test_sanction(SANCTION_MUTE, Some(until)),
+        create_test_sanction(SANCTION_BAN, None),
+    ];
+    assert_eq!(sanction_until(&sanctions, SANCTION_MUTE), Some(until));
+    assert_eq!(
+        sanction_until(&sanctions, SANCTION_BAN),
+        Some(DateTime::<Utc>::MAX_UTC)
+    );
+    assert_eq!(sanction_until(&[], SANCTION_MUTE), None);
+}
+
+// Test the reason of a sanction must fit its column
+#[test]
+fn test_check_sanction_reason() {
+    assert_eq!(check_sanction_reason(None), Ok(()));
+    assert_eq!(check_sanction_reason(Some(&"é".repeat(255))), Ok(()));
+    assert_eq!(
+        check_sanction_reason(Some(&"a".repeat(256))),
+        Err("Reason is longer than 255 characters".to_string())
+    );
+}
+
+// Test the stored sanctions decide whether a user may chat, a ban wins over a mute
+#[test]
+fn test_sanction_refusal() {
+    let until = Utc::now() + Duration::minutes(5);
+
+    assert_eq!(sanction_refusal(&[]), Ok(()));
+    assert_eq!(
+        sanction_refusal(&[create_test_sanction(SANCTION_MUTE, Some(until))]),
+        Err(format!("You are muted until {}", until.to_rfc3339()))
+    );
+    assert_eq!(
+        sanction_refusal(&[create_test_sanction(SANCTION_MUTE, None)]),
+        Err("You are muted".to_string())
+    );
+    assert_eq!(
+        sanction_refusal(&[
+            create_test_sanction(SANCTION_MUTE, None),
+            create_test_sanction(SANCTION_BAN, Some(until)),
+        ]),
+        Err("You are banned from the chat".to_string())
+    );
+}
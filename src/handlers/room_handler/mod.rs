@@ -0,0 +1,3 @@
+pub mod room_handler;
+
+pub use room_handler::*;
@@ -0,0 +1,686 @@
+use crate::{
+    middlewares::admin_middleware::is_admin,
+    schemas::{
+        document_schema::Pagination,
+        message_schema::ServerMessage,
+        room_schema::{
+            ChatHistoryResponse, ChatMessage, HistoryQuery, Room, RoomMember, RoomMemberRequest,
+            RoomRequest,
+        },
+    },
+    utils::{hub::Hub, hub::normalize_room_name, jwt::Claims, response::ApiResponse},
+};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::sync::Arc;
+use validator::Validate;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+pub const ROLE_OWNER: &str = "owner";
+pub const ROLE_MEMBER: &str = "member";
+
+// kinds of history entries
+pub const MESSAGE_CHAT: &str = "chat";
+pub const MESSAGE_JOINED: &str = "joined";
+pub const MESSAGE_LEFT: &str = "left";
+
+// most history entries returned by one page
+pub const MAX_HISTORY_LIMIT: i64 = 100;
+
+#[derive(Debug, PartialEq)]
+pub enum RoomAccess {
+    // carries the room id
+    Allowed(i64),
+    NotFound,
+    Forbidden,
+}
+
+struct StoredRoom {
+    id: i64,
+    name: String,
+    is_private: bool,
+}
+
+// function for checking a user may join a room by name, private rooms only admit their members
+pub async fn room_access(
+    db: &MySqlPool,
+    name: &str,
+    user_id: i64,
+) -> Result<RoomAccess, sqlx::Error> {
+    let room = sqlx::query!(
+        "
+        SELECT r.id, r.is_private AS `is_private: bool`,
+            EXISTS (
+                SELECT 1 FROM room_members m WHERE m.room_id = r.id AND m.user_id = ?
+            ) AS `is_member!: bool`
+        FROM rooms r
+        WHERE r.name = ?
+        ",
+        user_id,
+        name
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match room {
+        None => RoomAccess::NotFound,
+        Some(room) if room.is_private && !room.is_member => RoomAccess::Forbidden,
+        Some(room) => RoomAccess::Allowed(room.id),
+    })
+}
+
+// function for storing an entry in the history of a room, returns its id
+pub async fn store_room_message(
+    db: &MySqlPool,
+    room_id: i64,
+    user_id: i64,
+    kind: &str,
+    message: Option<&str>,
+    time: chrono::DateTime<chrono::Utc>,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO chat_messages (room_id, user_id, kind, message, created_at) VALUES (?, ?, ?, ?, ?)",
+        room_id,
+        user_id,
+        kind,
+        message,
+        time
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.last_insert_id() as i64)
+}
+
+// function for loading the history of rooms after an id, oldest first
+pub async fn room_messages_after(
+    db: &MySqlPool,
+    room_ids: &[i64],
+    last_id: i64,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, sqlx::Error> {
+    if room_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut builder = QueryBuilder::<MySql>::new(
+        "
+        SELECT m.id, r.name AS room, m.user_id, COALESCE(u.name, '') AS username,
+            m.kind, m.message, m.created_at, m.edited_at
+        FROM chat_messages m
+        JOIN rooms r ON r.id = m.room_id
+        LEFT JOIN users u ON u.id = m.user_id
+        WHERE m.deleted_at IS NULL AND m.id > ",
+    );
+    builder.push_bind(last_id);
+    builder.push(" AND m.room_id IN (");
+    let mut separated = builder.separated(", ");
+    for room_id in room_ids {
+        separated.push_bind(*room_id);
+    }
+    separated.push_unseparated(")");
+    builder.push(" ORDER BY m.id LIMIT ");
+    builder.push_bind(limit);
+
+    builder.build_query_as::<ChatMessage>().fetch_all(db).await
+}
+
+// function for turning a history entry into the message clients received live
+pub fn server_message(message: ChatMessage) -> Option<ServerMessage> {
+    match message.kind.as_str() {
+        MESSAGE_CHAT => Some(ServerMessage::Chat {
+            id: message.id,
+            room: message.room,
+            user_id: message.user_id,
+            username: message.username,
+            message: message.message.unwrap_or_default(),
+            time: message.created_at,
+        }),
+        MESSAGE_JOINED => Some(ServerMessage::UserJoined {
+            id: message.id,
+            room: message.room,
+            user_id: message.user_id,
+            username: message.username,
+            time: message.created_at,
+        }),
+        MESSAGE_LEFT => Some(ServerMessage::UserLeft {
+            id: message.id,
+            room: message.room,
+            user_id: message.user_id,
+            username: message.username,
+            time: message.created_at,
+        }),
+        _ => None,
+    }
+}
+
+// function for finding a room by id
+async fn find_room(db: &MySqlPool, id: i64) -> Result<StoredRoom, HandlerResponse> {
+    match sqlx::query_as!(
+        StoredRoom,
+        "SELECT id, name, is_private AS `is_private: bool` FROM rooms WHERE id = ?",
+        id
+    )
+    .fetch_optional(db)
+    .await
+    {
+        Ok(Some(room)) => Ok(room),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Room not found")),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!("Failed to fetch room: {}", e))),
+        )),
+    }
+}
+
+// function for getting the role of a user in a room, None when the user is not a member
+async fn member_role(
+    db: &MySqlPool,
+    room_id: i64,
+    user_id: i64,
+) -> Result<Option<String>, sqlx::Error> {
+    let member = sqlx::query!(
+        "SELECT role FROM room_members WHERE room_id = ? AND user_id = ?",
+        room_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(member.map(|member| member.role))
+}
+
+// function for checking the user owns the room or is an admin
+async fn authorize_room_owner(
+    db: &MySqlPool,
+    room_id: i64,
+    user_id: i64,
+) -> Result<(), HandlerResponse> {
+    let owner = match member_role(db, room_id, user_id).await {
+        Ok(role) => role.as_deref() == Some(ROLE_OWNER),
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check access: {}",
+                    e
+                ))),
+            ));
+        }
+    };
+    if owner {
+        return Ok(());
+    }
+
+    match is_admin(db, user_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error("Only the room owner can manage members")),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to check access: {}",
+                e
+            ))),
+        )),
+    }
+}
+
+// function for listing the public rooms and the private rooms the user belongs to
+pub async fn index(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+) -> HandlerResponse {
+    let rooms = match sqlx::query_as!(
+        Room,
+        "
+        SELECT r.id, r.name, r.is_private AS `is_private: bool`, r.created_by,
+            (SELECT COUNT(*) FROM room_members m WHERE m.room_id = r.id) AS `member_count!: i64`,
+            r.created_at, r.updated_at
+        FROM rooms r
+        WHERE r.is_private = FALSE
+        OR EXISTS (SELECT 1 FROM room_members m WHERE m.room_id = r.id AND m.user_id = ?)
+        ORDER BY r.name
+        ",
+        claims.sub
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(rooms) => rooms,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to fetch rooms: {}", e))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("List Rooms", json!(rooms))),
+    )
+}
+
+// function for creating a room, the creator becomes its owner
+pub async fn store(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<RoomRequest>,
+) -> HandlerResponse {
+    // Request Validation
+    if let Err(errors) = payload.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::validation(&errors)),
+        );
+    }
+    let name = normalize_room_name(&payload.name).unwrap_or_default();
+
+    let existing = sqlx::query!("SELECT id FROM rooms WHERE name = ?", name)
+        .fetch_optional(&db)
+        .await;
+    match existing {
+        Ok(Some(_)) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse::error("Room name is already taken")),
+            );
+        }
+        Ok(None) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to fetch room: {}", e))),
+            );
+        }
+    }
+
+    let created: Result<u64, sqlx::Error> = async {
+        let mut tx = db.begin().await?;
+
+        let room_id = sqlx::query!(
+            "INSERT INTO rooms (name, is_private, created_by) VALUES (?, ?, ?)",
+            name,
+            payload.is_private,
+            claims.sub
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_id();
+        sqlx::query!(
+            "INSERT INTO room_members (room_id, user_id, role) VALUES (?, ?, ?)",
+            room_id,
+            claims.sub,
+            ROLE_OWNER
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(room_id)
+    }
+    .await;
+    let room_id = match created {
+        Ok(room_id) => room_id,
+        // Another request took the name between the check above and the insert
+        Err(e) if e.to_string().contains("Duplicate entry") => {
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse::error("Room name is already taken")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to create room: {}", e))),
+            );
+        }
+    };
+
+    (
+        StatusCode::CREATED,
+        Json(ApiResponse::success(
+            "Room created",
+            json!({
+                "id": room_id,
+                "name": name,
+                "is_private": payload.is_private,
+                "created_by": claims.sub,
+            }),
+        )),
+    )
+}
+
+// function for listing the members of a room the user can see
+pub async fn members(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> HandlerResponse {
+    let room = match find_room(&db, id).await {
+        Ok(room) => room,
+        Err(response) => return response,
+    };
+    if room.is_private {
+        match room_access(&db, &room.name, claims.sub).await {
+            Ok(RoomAccess::Allowed(_)) => {}
+            Ok(_) => {
+                if let Err(response) = authorize_room_owner(&db, room.id, claims.sub).await {
+                    return response;
+                }
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::error(&format!(
+                        "Failed to check access: {}",
+                        e
+                    ))),
+                );
+            }
+        }
+    }
+
+    let members = match sqlx::query_as!(
+        RoomMember,
+        "
+        SELECT m.user_id, u.name, m.role, m.created_at
+        FROM room_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.room_id = ?
+        ORDER BY m.created_at, m.user_id
+        ",
+        room.id
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(members) => members,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch members: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("List Room Members", json!(members))),
+    )
+}
+
+// function for adding a user to a room, only the owner or an admin may do this
+pub async fn add_member(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Json(payload): Json<RoomMemberRequest>,
+) -> HandlerResponse {
+    let room = match find_room(&db, id).await {
+        Ok(room) => room,
+        Err(response) => return response,
+    };
+    if let Err(response) = authorize_room_owner(&db, room.id, claims.sub).await {
+        return response;
+    }
+
+    let user = sqlx::query!(
+        "SELECT id FROM users WHERE id = ? AND deleted_at IS NULL",
+        payload.user_id
+    )
+    .fetch_optional(&db)
+    .await;
+    match user {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::error("User not found")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!("Failed to fetch user: {}", e))),
+            );
+        }
+    }
+
+    // Adding an existing member keeps its role
+    if let Err(e) = sqlx::query!(
+        "INSERT IGNORE INTO room_members (room_id, user_id, role) VALUES (?, ?, ?)",
+        room.id,
+        payload.user_id,
+        ROLE_MEMBER
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!("Failed to add member: {}", e))),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Member added",
+            json!({
+                "room_id": room.id,
+                "user_id": payload.user_id,
+            }),
+        )),
+    )
+}
+
+// function for removing a user from a room, members may also remove themselves,
+// connections of a user removed from a private room are unsubscribed immediately
+pub async fn remove_member(
+    Extension(db): Extension<MySqlPool>,
+    Extension(hub): Extension<Arc<Hub>>,
+    Extension(claims): Extension<Claims>,
+    Path((id, user_id)): Path<(i64, i64)>,
+) -> HandlerResponse {
+    let room = match find_room(&db, id).await {
+        Ok(room) => room,
+        Err(response) => return response,
+    };
+    if user_id != claims.sub {
+        if let Err(response) = authorize_room_owner(&db, room.id, claims.sub).await {
+            return response;
+        }
+    }
+
+    // A room always keeps its owner
+    match member_role(&db, room.id, user_id).await {
+        Ok(Some(role)) if role == ROLE_OWNER => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::error("The room owner can not be removed")),
+            );
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ApiResponse::error("User is not a member of this room")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch member: {}",
+                    e
+                ))),
+            );
+        }
+    }
+
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM room_members WHERE room_id = ? AND user_id = ?",
+        room.id,
+        user_id
+    )
+    .execute(&db)
+    .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::error(&format!(
+                "Failed to remove member: {}",
+                e
+            ))),
+        );
+    }
+
+    // Anyone may join a public room again, only private rooms lose the live connection
+    if room.is_private {
+        hub.publish(
+            &room.name,
+            ServerMessage::MemberRemoved {
+                room: room.name.clone(),
+                user_id,
+            },
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "Member removed",
+            json!({
+                "room_id": room.id,
+                "user_id": user_id,
+            }),
+        )),
+    )
+}
+
+// function for paging through the history of a room, newest first
+pub async fn messages(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+    Query(query): Query<HistoryQuery>,
+) -> HandlerResponse {
+    let room = match find_room(&db, id).await {
+        Ok(room) => room,
+        Err(response) => return response,
+    };
+    match room_access(&db, &room.name, claims.sub).await {
+        Ok(RoomAccess::Allowed(_)) => {}
+        Ok(_) => {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(ApiResponse::error("You are not a member of this room")),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to check access: {}",
+                    e
+                ))),
+            );
+        }
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_HISTORY_LIMIT);
+    let offset = (page - 1) * limit;
+    let after_id = query.after_id.unwrap_or(0);
+
+    let total = match sqlx::query!(
+        "SELECT COUNT(*) AS `total!: i64` FROM chat_messages WHERE room_id = ? AND id > ? AND deleted_at IS NULL",
+        room.id,
+        after_id
+    )
+    .fetch_one(&db)
+    .await
+    {
+        Ok(row) => row.total,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to count messages: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let messages = match sqlx::query_as!(
+        ChatMessage,
+        "
+        SELECT m.id, r.name AS room, m.user_id, COALESCE(u.name, '') AS `username!: String`,
+            m.kind, m.message, m.created_at, m.edited_at
+        FROM chat_messages m
+        JOIN rooms r ON r.id = m.room_id
+        LEFT JOIN users u ON u.id = m.user_id
+        WHERE m.room_id = ?
+        AND m.id > ?
+        AND m.deleted_at IS NULL
+        ORDER BY m.id DESC
+        LIMIT ? OFFSET ?
+        ",
+        room.id,
+        after_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error(&format!(
+                    "Failed to fetch messages: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    let total_page = (total as f64 / limit as f64).ceil() as i64;
+    let response = ChatHistoryResponse {
+        data: messages,
+        pagination: Pagination {
+            page,
+            limit,
+            total,
+            total_page,
+        },
+    };
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success("Room History", json!(response))),
+    )
+}
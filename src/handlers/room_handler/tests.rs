@@ -0,0 +1,180 @@
+use super::*;
+use crate::schemas::room_schema::ChatMessage;
+use crate::utils::hub::{DEFAULT_ROOM, MAX_ROOM_NAME_LEN};
+
+// Helper function to create a chat message for a room
+fn create_test_message(room: &str, message: &str) -> ServerMessage {
+    ServerMessage::Chat {
+        id: 1,
+        room: room.to_string(),
+        user_id: 1,
+        username: "Budi".to_string(),
+        message: message.to_string(),
+        time: None,
+    }
+}
+
+// Test room names are normalized and restricted to a safe alphabet
+#[tokio::test]
+async fn test_normalize_room_name() {
+    assert_eq!(
+        normalize_room_name(" General ").as_deref(),
+        Some(DEFAULT_ROOM)
+    );
+    assert_eq!(
+        normalize_room_name("team_rust-2026").as_deref(),
+        Some("team_rust-2026")
+    );
+    assert!(normalize_room_name("").is_none());
+    assert!(normalize_room_name("-leading").is_none());
+    assert!(normalize_room_name("with space").is_none());
+    assert!(normalize_room_name("ruang-é").is_none());
+    assert!(normalize_room_name(&"a".repeat(MAX_ROOM_NAME_LEN)).is_some());
+    assert!(normalize_room_name(&"a".repeat(MAX_ROOM_NAME_LEN + 1)).is_none());
+
+    let request = RoomRequest {
+        name: "Bad Name".to_string(),
+        is_private: false,
+    };
+    assert!(request.validate().is_err());
+}
+
+// Test messages only reach subscribers of their room
+#[tokio::test]
+async fn test_hub_rooms_are_isolated() {
+    let hub = Arc::new(Hub::new());
+    let mut general = hub.subscribe("general");
+    let mut project = hub.subscribe("project");
+
+    assert_eq!(
+        hub.publish("project", create_test_message("project", "rapat")),
+        1
+    );
+    assert_eq!(
+        hub.publish("nobody", create_test_message("nobody", "halo")),
+        0
+    );
+    assert_eq!(
+        hub.publish("general", create_test_message("general", "halo")),
+        1
+    );
+
+    match project.recv().await.unwrap() {
+        ServerMessage::Chat { room, message, .. } => {
+            assert_eq!((room.as_str(), message.as_str()), ("project", "rapat"));
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    match general.recv().await.unwrap() {
+        ServerMessage::Chat { room, .. } => assert_eq!(room, "general"),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+// Test room channels are created lazily and released with their last subscriber
+#[tokio::test]
+async fn test_hub_releases_empty_rooms() {
+    let hub = Arc::new(Hub::new());
+    assert!(hub.active_rooms().is_empty());
+
+    let first = hub.subscribe("project");
+    let second = hub.subscribe("project");
+    let other = hub.subscribe("general");
+    assert_eq!(hub.active_rooms(), vec!["general", "project"]);
+
+    drop(first);
+    assert_eq!(hub.active_rooms(), vec!["general", "project"]);
+    drop(second);
+    assert_eq!(hub.active_rooms(), vec!["general"]);
+    assert_eq!(other.room(), "general");
+    drop(other);
+    assert!(hub.active_rooms().is_empty());
+}
+
+// Helper function to create a stored history entry
+fn create_test_history(id: i64, kind: &str, message: Option<&str>) -> ChatMessage {
+    ChatMessage {
+        id,
+        room: "project".to_string(),
+        user_id: 3,
+        username: "Siti".to_string(),
+        kind: kind.to_string(),
+        message: message.map(str::to_string),
+        created_at: None,
+        edited_at: None,
+    }
+}
+
+// Test history entries replay as the messages clients received live
+#[tokio::test]
+async fn test_history_server_message() {
+    match server_message(create_test_history(10, MESSAGE_CHAT, Some("halo"))) {
+        Some(ServerMessage::Chat {
+            id, room, message, ..
+        }) => {
+            assert_eq!(
+                (id, room.as_str(), message.as_str()),
+                (10, "project", "halo")
+            );
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    assert!(matches!(
+        server_message(create_test_history(11, MESSAGE_JOINED, None)),
+        Some(ServerMessage::UserJoined {
+            id: 11,
+            user_id: 3,
+            ..
+        })
+    ));
+    assert!(matches!(
+        server_message(create_test_history(12, MESSAGE_LEFT, None)),
+        Some(ServerMessage::UserLeft { id: 12, .. })
+    ));
+    assert!(server_message(create_test_history(13, "unknown", None)).is_none());
+
+    // the id is part of every room message sent to clients
+    let json = serde_json::to_value(
+        server_message(create_test_history(14, MESSAGE_CHAT, Some("x"))).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(json["id"], 14);
+}
+
+// Test two requests creating the same room get one room and a conflict
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_store_room_name_race() {
+    let db = crate::config::database::connect_test().await;
+    let name = format!("race-{}", crate::utils::upload_path::generate_upload_id());
+    let create = |user_id: i64| {
+        store(
+            Extension(db.clone()),
+            Extension(Claims {
+                sub: user_id,
+                exp: 0,
+            }),
+            Json(RoomRequest {
+                name: name.clone(),
+                is_private: false,
+            }),
+        )
+    };
+
+    let ((first, _), (second, _)) = tokio::join!(create(1), create(2));
+    let mut statuses = vec![first, second];
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::CREATED, StatusCode::CONFLICT]);
+
+    sqlx::query!(
+        "DELETE m FROM room_members m JOIN rooms r ON r.id = m.room_id WHERE r.name = ?",
+        name
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query!("DELETE FROM rooms WHERE name = ?", name)
+        .execute(&db)
+        .await
+        .unwrap();
+}
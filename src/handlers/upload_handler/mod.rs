@@ -0,0 +1,3 @@
+pub mod upload_handler;
+
+pub use upload_handler::*;
@@ -0,0 +1,521 @@
+use crate::{
+    config::storage::{S3, S3Config, s3_config, storage_backend},
+    utils::{
+        file_type::{extension_for, resource_type_for, sniff, sniff_bytes},
+        image_pipeline::{self, ImageVariant},
+        s3_presign::uri_encode,
+        storage::{
+            S3_REQUEST_TTL, generate_signature, require_s3, s3_key_for_url, s3_presigned_url,
+        },
+        upload_path::generate_upload_id,
+    },
+};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use regex::Regex;
+use reqwest::multipart;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::env;
+use std::path::Path;
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+pub mod tests;
+
+// buffer size used when streaming a file to cloudinary
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+// cloudinary folder and S3 prefix of uploaded documents
+pub const DOCUMENT_FOLDER: &str = "uploads/rust";
+// cloudinary folders and S3 prefixes of generated image variants
+const AVATAR_FOLDER: &str = "uploads/rust/avatars";
+pub const THUMBNAIL_FOLDER: &str = "uploads/rust/thumbnails";
+
+// stored object returned by cloudinary, S3 uploads are described with the same fields
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CloudinaryResponse {
+    pub public_id: String,
+    pub secure_url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // raw uploads have no format
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub resource_type: String,
+    #[serde(default)]
+    pub bytes: u64,
+}
+
+// function for detecting the MIME type of bytes held in memory
+pub fn detect_file_type(bytes: &[u8]) -> String {
+    sniff_bytes(bytes).to_string()
+}
+
+// function for detecting the MIME type of a file on disk, ZIP and OLE containers are inspected
+pub async fn sniff_file(path: &Path) -> std::io::Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        sniff(&mut file).map(|mime| mime.to_string())
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+pub async fn upload_cloudinary(
+    file: File,
+    mime_type: &str,
+) -> Result<CloudinaryResponse, Box<dyn std::error::Error>> {
+    let cloud_name = env::var("CLOUDINARY_CLOUD_NAME").unwrap();
+    let api_key = env::var("CLOUDINARY_API_KEY").unwrap();
+    let api_secret = env::var("CLOUDINARY_API_SECRET").unwrap();
+
+    let timestamp = Utc::now().timestamp();
+    let folder = DOCUMENT_FOLDER.to_string();
+
+    // signature = sha1("timestamp=xxx<api_secret>")
+    let signature = generate_signature(
+        &[
+            ("folder", folder.clone()),
+            ("timestamp", timestamp.clone().to_string()),
+        ],
+        &api_secret,
+    );
+
+    // The resource type decides how cloudinary stores and delivers the file
+    let size = file.metadata().await?.len();
+    let resource_type = resource_type_for(mime_type);
+
+    // Stream the file body with a bounded buffer instead of loading it into memory
+    let body = reqwest::Body::wrap_stream(ReaderStream::with_capacity(file, STREAM_BUFFER_SIZE));
+
+    let form = multipart::Form::new()
+        .part(
+            "file",
+            multipart::Part::stream_with_length(body, size)
+                .file_name(format!("upload.{}", extension_for(mime_type)))
+                .mime_str(mime_type)?,
+        )
+        .text("timestamp", timestamp.to_string())
+        .text("api_key", api_key)
+        .text("signature", signature)
+        .text("folder", folder);
+
+    let url = format!(
+        "https://api.cloudinary.com/v1_1/{}/{}/upload",
+        cloud_name, resource_type
+    );
+
+    let client = reqwest::Client::new();
+
+    let res = client.post(url).multipart(form).send().await?;
+
+    // handle error
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("Cloudinary Error: {}", res.status()).into());
+    }
+
+    let response: CloudinaryResponse = res.json().await?;
+
+    Ok(response)
+}
+
+// function for uploading bytes held in memory (generated image variants) to a cloudinary folder
+pub async fn upload_bytes_cloudinary(
+    bytes: Vec<u8>,
+    mime_type: &str,
+    folder: &str,
+) -> Result<CloudinaryResponse, Box<dyn std::error::Error>> {
+    let cloud_name = env::var("CLOUDINARY_CLOUD_NAME").unwrap();
+    let api_key = env::var("CLOUDINARY_API_KEY").unwrap();
+    let api_secret = env::var("CLOUDINARY_API_SECRET").unwrap();
+
+    let timestamp = Utc::now().timestamp();
+    let signature = generate_signature(
+        &[
+            ("folder", folder.to_string()),
+            ("timestamp", timestamp.to_string()),
+        ],
+        &api_secret,
+    );
+
+    let form = multipart::Form::new()
+        .part(
+            "file",
+            multipart::Part::bytes(bytes)
+                .file_name(format!("upload.{}", extension_for(mime_type)))
+                .mime_str(mime_type)?,
+        )
+        .text("timestamp", timestamp.to_string())
+        .text("api_key", api_key)
+        .text("signature", signature)
+        .text("folder", folder.to_string());
+
+    let url = format!(
+        "https://api.cloudinary.com/v1_1/{}/{}/upload",
+        cloud_name,
+        resource_type_for(mime_type)
+    );
+
+    let client = reqwest::Client::new();
+
+    let res = client.post(url).multipart(form).send().await?;
+
+    // handle error
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("Cloudinary Error: {}", res.status()).into());
+    }
+
+    let response: CloudinaryResponse = res.json().await?;
+
+    Ok(response)
+}
+
+// function for the signed parameters a client posts along with a file to upload it to cloudinary
+// directly, returns the upload url and the form fields
+pub fn cloudinary_upload_params(public_id: &str, mime_type: &str) -> (String, Map<String, Value>) {
+    let cloud_name = env::var("CLOUDINARY_CLOUD_NAME").unwrap();
+    let api_key = env::var("CLOUDINARY_API_KEY").unwrap();
+    let api_secret = env::var("CLOUDINARY_API_SECRET").unwrap();
+
+    // cloudinary accepts a signed upload for an hour after its timestamp
+    let timestamp = Utc::now().timestamp();
+    let signature = generate_signature(
+        &[
+            ("public_id", public_id.to_string()),
+            ("timestamp", timestamp.to_string()),
+        ],
+        &api_secret,
+    );
+
+    let url = format!(
+        "https://api.cloudinary.com/v1_1/{}/{}/upload",
+        cloud_name,
+        resource_type_for(mime_type)
+    );
+    let mut fields = Map::new();
+    fields.insert("api_key".to_string(), Value::String(api_key));
+    fields.insert(
+        "timestamp".to_string(),
+        Value::String(timestamp.to_string()),
+    );
+    fields.insert(
+        "public_id".to_string(),
+        Value::String(public_id.to_string()),
+    );
+    fields.insert("signature".to_string(), Value::String(signature));
+
+    (url, fields)
+}
+
+// function for looking up an uploaded cloudinary resource, None when it does not exist
+pub async fn cloudinary_resource(
+    public_id: &str,
+    resource_type: &str,
+) -> Result<Option<CloudinaryResponse>, Box<dyn std::error::Error>> {
+    let cloud_name = env::var("CLOUDINARY_CLOUD_NAME").unwrap();
+    let api_key = env::var("CLOUDINARY_API_KEY").unwrap();
+    let api_secret = env::var("CLOUDINARY_API_SECRET").unwrap();
+
+    let url = format!(
+        "https://api.cloudinary.com/v1_1/{}/resources/{}/upload/{}",
+        cloud_name, resource_type, public_id
+    );
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(url)
+        .basic_auth(api_key, Some(api_secret))
+        .send()
+        .await?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("Cloudinary Error: {}", res.status()).into());
+    }
+
+    Ok(Some(res.json().await?))
+}
+
+// function for the url an S3 object is addressed by, path style so it works with MinIO
+pub fn s3_object_url(config: &S3Config, key: &str) -> String {
+    format!(
+        "{}/{}/{}",
+        config.endpoint,
+        config.bucket,
+        uri_encode(key, true)
+    )
+}
+
+// function for generating a new object key below a folder
+pub fn s3_object_key(folder: &str, mime_type: &str) -> String {
+    format!(
+        "{}/{}/{}.{}",
+        folder,
+        Utc::now().format("%Y/%m"),
+        generate_upload_id(),
+        extension_for(mime_type)
+    )
+}
+
+// function for uploading a body to a new S3 object below a folder
+async fn upload_s3(
+    body: reqwest::Body,
+    size: u64,
+    mime_type: &str,
+    folder: &str,
+) -> Result<CloudinaryResponse, Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+    let key = s3_object_key(folder, mime_type);
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .put(s3_presigned_url(&config, "PUT", &key, &[], S3_REQUEST_TTL))
+        .header(reqwest::header::CONTENT_TYPE, mime_type)
+        .header(reqwest::header::CONTENT_LENGTH, size)
+        .body(body)
+        .send()
+        .await?;
+
+    // handle error
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("S3 Error: {}", res.status()).into());
+    }
+
+    Ok(CloudinaryResponse {
+        secure_url: s3_object_url(&config, &key),
+        public_id: key,
+        width: None,
+        height: None,
+        format: extension_for(mime_type).to_string(),
+        resource_type: resource_type_for(mime_type).to_string(),
+        bytes: size,
+    })
+}
+
+// function for uploading a document file to the storage backend selected by STORAGE_BACKEND
+pub async fn upload_to_storage(
+    file: File,
+    mime_type: &str,
+) -> Result<CloudinaryResponse, Box<dyn std::error::Error>> {
+    if storage_backend() != S3 {
+        return upload_cloudinary(file, mime_type).await;
+    }
+
+    let size = file.metadata().await?.len();
+    let body = reqwest::Body::wrap_stream(ReaderStream::with_capacity(file, STREAM_BUFFER_SIZE));
+    upload_s3(body, size, mime_type, DOCUMENT_FOLDER).await
+}
+
+// function for uploading generated bytes (document thumbnails) next to the documents
+pub async fn upload_bytes_to_storage(
+    bytes: Vec<u8>,
+    mime_type: &str,
+    folder: &str,
+) -> Result<CloudinaryResponse, Box<dyn std::error::Error>> {
+    if storage_backend() != S3 {
+        return upload_bytes_cloudinary(bytes, mime_type, folder).await;
+    }
+
+    let size = bytes.len() as u64;
+    upload_s3(bytes.into(), size, mime_type, folder).await
+}
+
+// function for reading the size of an S3 object, None when it does not exist
+pub async fn s3_object_size(key: &str) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .head(s3_presigned_url(&config, "HEAD", key, &[], S3_REQUEST_TTL))
+        .send()
+        .await?;
+
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("S3 Error: {}", res.status()).into());
+    }
+
+    // the body of a HEAD response is empty, the size is only in the header
+    let size = res
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok())
+        .ok_or("S3 Error: missing Content-Length")?;
+
+    Ok(Some(size))
+}
+
+// function for reading the text of the first element with a name from an S3 XML response
+pub fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", name))? + start;
+
+    Some(&xml[start..end])
+}
+
+// function for starting an S3 multipart upload, returns its upload id
+pub async fn s3_create_multipart(
+    key: &str,
+    mime_type: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .post(s3_presigned_url(
+            &config,
+            "POST",
+            key,
+            &[("uploads", "")],
+            S3_REQUEST_TTL,
+        ))
+        .header(reqwest::header::CONTENT_TYPE, mime_type)
+        .send()
+        .await?;
+
+    // handle error
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("S3 Error: {}", res.status()).into());
+    }
+
+    let body = res.text().await?;
+    let upload_id = xml_element(&body, "UploadId").ok_or("S3 Error: missing UploadId")?;
+
+    Ok(upload_id.to_string())
+}
+
+// function for building the body that completes a multipart upload from part numbers and ETags
+pub fn complete_multipart_body(parts: &[(u32, String)]) -> String {
+    let parts = parts
+        .iter()
+        .map(|(part_number, etag)| {
+            format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part_number,
+                etag.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+        parts
+    )
+}
+
+// function for assembling the uploaded parts of a multipart upload into the object
+pub async fn s3_complete_multipart(
+    key: &str,
+    upload_id: &str,
+    parts: &[(u32, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .post(s3_presigned_url(
+            &config,
+            "POST",
+            key,
+            &[("uploadId", upload_id)],
+            S3_REQUEST_TTL,
+        ))
+        .header(reqwest::header::CONTENT_TYPE, "application/xml")
+        .body(complete_multipart_body(parts))
+        .send()
+        .await?;
+
+    // S3 can report a failed completion in the body of a 200 response
+    let status = res.status();
+    let body = res.text().await?;
+    if status.is_client_error() || status.is_server_error() || body.contains("<Error>") {
+        let message = xml_element(&body, "Message").unwrap_or_default();
+        return Err(format!("S3 Error: {} {}", status, message).into());
+    }
+
+    Ok(())
+}
+
+// function for fetching a stored file, forwarding the client's Range header if any,
+// objects in a private S3 bucket are fetched through a presigned url
+pub async fn fetch_from_storage(
+    url: &str,
+    range: Option<&str>,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = s3_config()
+        .and_then(|config| {
+            s3_key_for_url(&config, url)
+                .map(|key| s3_presigned_url(&config, "GET", &key, &[], S3_REQUEST_TTL))
+        })
+        .unwrap_or_else(|| url.to_string());
+
+    let client = reqwest::Client::new();
+    let mut request = client.get(url);
+
+    if let Some(range) = range {
+        request = request.header(reqwest::header::RANGE, range);
+    }
+
+    request.send().await
+}
+
+// function for parsing an image data url, returns None when it is not a valid base64 image
+pub fn parse_image_data_url(data_url: &str) -> Option<(String, Vec<u8>)> {
+    // regex to get mime type and base64 data
+    let re = Regex::new(r"^data:(image/[\w.+-]+);base64,(.+)$").unwrap();
+    let caps = re.captures(data_url.trim())?;
+    // get mime type
+    let mime = caps.get(1)?.as_str();
+    // decode base64 data
+    let bytes = general_purpose::STANDARD
+        .decode(caps.get(2)?.as_str())
+        .ok()?;
+
+    Some((mime.to_string(), bytes))
+}
+
+// function for decoding an avatar data url and generating every avatar variant
+pub fn avatar_variants_from_data_url(data_url: &str) -> Result<Vec<ImageVariant>, String> {
+    let (_, bytes) = parse_image_data_url(data_url)
+        .ok_or_else(|| "Gambar harus berupa data URL base64".to_string())?;
+
+    image_pipeline::avatar_variants(&bytes).map_err(|e| format!("Gambar tidak valid: {}", e))
+}
+
+// function for uploading avatar variants, returns the url of the full-size avatar
+// and a map of variant name to url
+pub async fn upload_avatar_variants(
+    variants: Vec<ImageVariant>,
+) -> Result<(String, Value), Box<dyn std::error::Error>> {
+    let mut urls = Map::new();
+
+    for variant in variants {
+        let uploaded =
+            upload_bytes_to_storage(variant.bytes, variant.mime_type, AVATAR_FOLDER).await?;
+        urls.insert(variant.name, Value::String(uploaded.secure_url));
+    }
+
+    let original = urls
+        .get("original")
+        .and_then(|url| url.as_str())
+        .ok_or("Avatar upload returned no original")?
+        .to_string();
+
+    Ok((original, Value::Object(urls)))
+}
@@ -165,6 +165,7 @@ async fn test_user_response_serialization() {
             name: "Test User".to_string(),
             email: "test@example.com".to_string(),
             image: Some("image.jpg".to_string()),
+            avatar_variants: None,
             created_at: Some(Utc::now()),
             updated_at: Some(Utc::now()),
         }],
@@ -269,6 +270,7 @@ async fn test_datetime_handling() {
         name: "Test User".to_string(),
         email: "test@example.com".to_string(),
         image: None,
+        avatar_variants: None,
         created_at: Some(now),
         updated_at: Some(now),
     };
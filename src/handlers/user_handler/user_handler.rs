@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fs::File;
 
-use crate::handlers::upload_handler::upload_base64_cloudinary;
+use crate::handlers::upload_handler::{avatar_variants_from_data_url, upload_avatar_variants};
 use crate::models::user::User;
 use crate::schemas::user_schema::{
     Pagination, UserQuery, UserResponse, UserStoreRequest, UserStoreResponse, UserUpdateRequest,
 };
+use crate::utils::events::{Event, EventBus};
 use crate::utils::password::hash_password;
 use crate::utils::response::ApiResponse;
 use axum::extract::{Path, Query};
@@ -26,6 +27,50 @@ use validator::Validate;
 #[path = "./tests.rs"]
 mod tests;
 
+// function for validating an avatar data url, resizing it and uploading every variant,
+// returns the full-size url and the url of each variant
+async fn upload_avatar(
+    image: &str,
+) -> Result<(String, Value), (StatusCode, Json<ApiResponse<Value>>)> {
+    let image = image.to_string();
+    let variants =
+        match tokio::task::spawn_blocking(move || avatar_variants_from_data_url(&image)).await {
+            Ok(Ok(variants)) => variants,
+            Ok(Err(message)) => {
+                let mut errors = HashMap::new();
+                errors.insert("image".to_string(), vec![message]);
+                return Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ApiResponse {
+                        status: false,
+                        message: "Validation failed".to_string(),
+                        data: Some(json!(errors)),
+                    }),
+                ));
+            }
+            Err(e) => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::error(&format!(
+                        "Failed to process image: {}",
+                        e
+                    ))),
+                ));
+            }
+        };
+
+    match upload_avatar_variants(variants).await {
+        Ok(uploaded) => Ok(uploaded),
+        Err(e) => {
+            println!("Avatar upload failed: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("Cloudinary upload failed")),
+            ))
+        }
+    }
+}
+
 pub async fn index(
     Extension(db): Extension<MySqlPool>,
     Query(query): Query<UserQuery>,
@@ -53,7 +98,7 @@ pub async fn index(
     let users = match sqlx::query_as!(
         User,
         "
-        SELECT id, name, email, password, image, created_at, updated_at, deleted_at
+        SELECT id, name, email, password, image, avatar_variants, created_at, updated_at, deleted_at
         FROM users
         WHERE name LIKE ?
         AND deleted_at IS NULL
@@ -85,6 +130,7 @@ pub async fn index(
                 name: user.name,
                 email: user.email,
                 image: user.image,
+                avatar_variants: user.avatar_variants,
                 created_at: user.created_at,
                 updated_at: user.updated_at,
             })
@@ -163,27 +209,24 @@ pub async fn store(
         }
     }
 
-    // upload image base64 to cloudinary
-    let image_cloudinary: Option<String> = if let Some(image) = &payload.image {
-        if !image.is_empty() {
-            let image_path = upload_base64_cloudinary(image.clone()).await.unwrap();
-            println!("Image path: {:#?}", image_path);
-            Some(image_path.secure_url.clone())
-        } else {
-            None
-        }
-    } else {
-        None
+    // resize the avatar and upload every variant to storage
+    let (image_cloudinary, avatar_variants) = match &payload.image {
+        Some(image) if !image.is_empty() => match upload_avatar(image).await {
+            Ok((image, variants)) => (Some(image), Some(variants)),
+            Err(response) => return response,
+        },
+        _ => (None, None),
     };
 
     let hashed = hash_password(&payload.password);
 
     // insert data user to database
     let result = sqlx::query!(
-        "INSERT INTO users (name, email, image, password) VALUES (?, ?, ?, ?)",
+        "INSERT INTO users (name, email, image, avatar_variants, password) VALUES (?, ?, ?, ?, ?)",
         payload.name,
         payload.email,
         image_cloudinary,
+        avatar_variants,
         hashed.unwrap()
     )
     .execute(&db)
@@ -196,7 +239,7 @@ pub async fn store(
 
             // Get the user data based on the ID
             let user = sqlx::query!(
-                r#"SELECT id, name, email, image, created_at, updated_at FROM users WHERE id = ?"#,
+                r#"SELECT id, name, email, image, avatar_variants, created_at, updated_at FROM users WHERE id = ?"#,
                 user_id
             )
             .fetch_one(&db)
@@ -209,6 +252,7 @@ pub async fn store(
                         name: user.name,
                         email: user.email,
                         image: user.image,
+                        avatar_variants: user.avatar_variants,
                         created_at: user.created_at,
                         updated_at: user.updated_at,
                     };
@@ -249,7 +293,7 @@ pub async fn show(
     // get all users data
     let users = match sqlx::query!(
         "
-        SELECT id, name, email, password, image, created_at, updated_at
+        SELECT id, name, email, password, image, avatar_variants, created_at, updated_at
         FROM users
         WHERE id = ?
         AND deleted_at IS NULL
@@ -273,6 +317,7 @@ pub async fn show(
         name: users.name,
         email: users.email,
         image: users.image,
+        avatar_variants: users.avatar_variants,
         created_at: users.created_at,
         updated_at: users.updated_at,
     };
@@ -286,6 +331,7 @@ pub async fn show(
 pub async fn update(
     Path(id): Path<i64>,
     Extension(db): Extension<MySqlPool>,
+    Extension(events): Extension<EventBus>,
     Json(payload): Json<UserUpdateRequest>,
 ) -> (StatusCode, Json<ApiResponse<Value>>) {
     // Validation payload
@@ -335,7 +381,7 @@ pub async fn update(
     // check if is user is exist
     let user_exist = match sqlx::query!(
         "
-        SELECT id, image
+        SELECT id, image, avatar_variants
         FROM users
         WHERE id = ?
         AND deleted_at IS NULL
@@ -382,17 +428,15 @@ pub async fn update(
         );
     }
 
-    //check if image is not empty
-    let image_cloudinary: Option<String> = if let Some(image) = &payload.image {
-        if !image.is_empty() && !image.contains("http") {
-            let image_path = upload_base64_cloudinary(image.clone()).await.unwrap();
-            println!("Image path: {:#?}", image_path);
-            Some(image_path.secure_url.clone())
-        } else {
-            user_exist.image
+    //check if image is not empty, an existing url keeps the current avatar
+    let (image_cloudinary, avatar_variants) = match &payload.image {
+        Some(image) if !image.is_empty() && !image.contains("http") => {
+            match upload_avatar(image).await {
+                Ok((image, variants)) => (Some(image), Some(variants)),
+                Err(response) => return response,
+            }
         }
-    } else {
-        user_exist.image
+        _ => (user_exist.image, user_exist.avatar_variants),
     };
 
     // update user
@@ -404,13 +448,14 @@ pub async fn update(
             sqlx::query!(
                 "
                 UPDATE users
-                SET name = ?, email = ?, password = ?, image = ?
+                SET name = ?, email = ?, password = ?, image = ?, avatar_variants = ?
                 WHERE id = ?
                 ",
                 payload.name,
                 payload.email,
                 hashed.unwrap(),
                 image_cloudinary,
+                avatar_variants,
                 id
             )
             .execute(&db)
@@ -419,10 +464,11 @@ pub async fn update(
         _ => {
             // Update user tanpa password
             sqlx::query!(
-                "UPDATE users SET name = ?, email = ?, image = ? WHERE id = ?",
+                "UPDATE users SET name = ?, email = ?, image = ?, avatar_variants = ? WHERE id = ?",
                 payload.name,
                 payload.email,
                 image_cloudinary,
+                avatar_variants,
                 id
             )
             .execute(&db)
@@ -441,7 +487,7 @@ pub async fn update(
     // Ambil data terbaru
     let user = sqlx::query!(
         r#"
-        SELECT id, name, email, image, created_at, updated_at
+        SELECT id, name, email, image, avatar_variants, created_at, updated_at
         FROM users
         WHERE id = ?
         "#,
@@ -451,11 +497,18 @@ pub async fn update(
     .await
     .unwrap();
 
+    events.publish(Event::UserUpdated {
+        user_id: user.id,
+        name: user.name.clone(),
+        email: user.email.clone(),
+    });
+
     let response = UserStoreResponse {
         id: user.id,
         name: user.name,
         email: user.email,
         image: user.image,
+        avatar_variants: user.avatar_variants,
         created_at: user.created_at,
         updated_at: user.updated_at,
     };
@@ -473,6 +526,7 @@ pub async fn update(
 pub async fn delete(
     Path(id): Path<i32>,
     Extension(db): Extension<MySqlPool>,
+    Extension(events): Extension<EventBus>,
 ) -> (StatusCode, Json<ApiResponse<Value>>) {
     // check if is user is exist
     let user_exist = match sqlx::query!(
@@ -522,6 +576,10 @@ pub async fn delete(
         );
     }
 
+    events.publish(Event::UserDeleted {
+        user_id: user_exist.id,
+    });
+
     (
         // kirim response 200 OK
         StatusCode::OK,
@@ -747,7 +805,7 @@ pub async fn export_excel(
     let users = match sqlx::query_as!(
         User,
         "
-        SELECT id, name, email, password, image, created_at, updated_at, deleted_at
+        SELECT id, name, email, password, image, avatar_variants, created_at, updated_at, deleted_at
         FROM users
         WHERE name LIKE ?
         AND deleted_at IS NULL
@@ -966,7 +1024,7 @@ pub async fn export_csv(
     let users = match sqlx::query_as!(
         User,
         "
-        SELECT id, name, email, password, image, created_at, updated_at, deleted_at
+        SELECT id, name, email, password, image, avatar_variants, created_at, updated_at, deleted_at
         FROM users
         WHERE name LIKE ?
         AND deleted_at IS NULL
@@ -0,0 +1,3 @@
+pub mod websocket_handler;
+
+pub use websocket_handler::*;
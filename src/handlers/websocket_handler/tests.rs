@@ -0,0 +1,865 @@
+use super::*;
+use crate::config::cors::origin_allowed;
+use crate::config::websocket::parse_slow_consumer_policy;
+use crate::schemas::message_schema::OnlineUser;
+use crate::utils::events::{Event, EventBus};
+use crate::utils::hub::{ADMIN_TOPIC, ROOM_CAPACITY, merge_online};
+use crate::utils::moderation::ChatRules;
+use crate::utils::pubsub::{
+    Envelope, MemoryPubSub, MySqlPubSub, PollCursor, PubSub, RedisPubSub, RespValue,
+    encode_command, pushed_message, read_resp,
+};
+use axum::http::HeaderValue;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Helper function to create upgrade request headers
+fn create_test_headers(entries: &[(header::HeaderName, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in entries {
+        headers.append(name.clone(), HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+// Test the token is read from the query first
+#[tokio::test]
+async fn test_token_from_query() {
+    let headers = create_test_headers(&[(header::COOKIE, "token=cookie-jwt")]);
+
+    assert_eq!(
+        extract_token(&headers, Some(" query-jwt ")),
+        Some(("query-jwt".to_string(), TokenSource::Query))
+    );
+    assert_eq!(
+        extract_token(&headers, Some("")),
+        Some(("cookie-jwt".to_string(), TokenSource::Cookie))
+    );
+}
+
+// Test the token follows the access_token subprotocol
+#[tokio::test]
+async fn test_token_from_protocol() {
+    let headers = create_test_headers(&[(
+        header::SEC_WEBSOCKET_PROTOCOL,
+        "chat, access_token, protocol.jwt.value",
+    )]);
+    assert_eq!(
+        extract_token(&headers, None),
+        Some(("protocol.jwt.value".to_string(), TokenSource::Protocol))
+    );
+
+    // the token may also be offered in a separate header line
+    let headers = create_test_headers(&[
+        (header::SEC_WEBSOCKET_PROTOCOL, "access_token"),
+        (header::SEC_WEBSOCKET_PROTOCOL, "split-jwt"),
+    ]);
+    assert_eq!(
+        extract_token(&headers, None),
+        Some(("split-jwt".to_string(), TokenSource::Protocol))
+    );
+
+    // the marker alone carries no token
+    let headers = create_test_headers(&[(header::SEC_WEBSOCKET_PROTOCOL, "access_token")]);
+    assert_eq!(extract_token(&headers, None), None);
+}
+
+// Test the token cookie is found among other cookies
+#[tokio::test]
+async fn test_token_from_cookie() {
+    let headers = create_test_headers(&[(header::COOKIE, "theme=dark; token=cookie-jwt; lang=id")]);
+    assert_eq!(
+        extract_token(&headers, None),
+        Some(("cookie-jwt".to_string(), TokenSource::Cookie))
+    );
+
+    let headers = create_test_headers(&[(header::COOKIE, "session_token=other; token=")]);
+    assert_eq!(extract_token(&headers, None), None);
+    assert_eq!(extract_token(&HeaderMap::new(), None), None);
+}
+
+// Test origins are compared without case and trailing slash
+#[tokio::test]
+async fn test_origin_allowed() {
+    let allowed = vec![
+        "http://localhost:5173".to_string(),
+        "https://app.example.com".to_string(),
+    ];
+
+    assert!(origin_allowed(&allowed, "http://localhost:5173"));
+    assert!(origin_allowed(&allowed, "HTTPS://App.Example.com/"));
+    assert!(!origin_allowed(&allowed, "http://localhost:3000"));
+    assert!(!origin_allowed(
+        &allowed,
+        "https://app.example.com.evil.test"
+    ));
+    assert!(origin_allowed(&["*".to_string()], "https://anything.test"));
+}
+
+// Test a username sent with Join is ignored and rooms default to general
+#[tokio::test]
+async fn test_join_ignores_username() {
+    let message =
+        serde_json::from_str::<ClientMessage>(r#"{"type":"Join","username":"admin"}"#).unwrap();
+    assert!(matches!(message, ClientMessage::Join { room: None }));
+
+    let message =
+        serde_json::from_str::<ClientMessage>(r#"{"type":"Chat","message":"halo"}"#).unwrap();
+    assert!(matches!(message, ClientMessage::Chat { room: None, .. }));
+    assert_eq!(room_name(None).unwrap(), DEFAULT_ROOM);
+    assert_eq!(room_name(Some(" Project-X ")).unwrap(), "project-x");
+    assert!(room_name(Some("../etc")).is_err());
+
+    let message =
+        serde_json::from_str::<ClientMessage>(r#"{"type":"Resume","last_id":42}"#).unwrap();
+    assert!(matches!(message, ClientMessage::Resume { last_id: 42 }));
+
+    let json = serde_json::to_value(ServerMessage::Chat {
+        id: 1,
+        room: "general".to_string(),
+        user_id: 7,
+        username: "Budi".to_string(),
+        message: "halo".to_string(),
+        time: None,
+    })
+    .unwrap();
+    assert_eq!(json["type"], "Chat");
+    assert_eq!(json["room"], "general");
+    assert_eq!(json["user_id"], 7);
+    assert_eq!(json["username"], "Budi");
+}
+
+// Test a connection removed from a private room stops forwarding it
+#[tokio::test]
+async fn test_forward_room_stops_on_removal() {
+    let hub = Arc::new(Hub::new());
+    let (outbound, mut queue) = Outbound::new(8, SlowConsumerPolicy::Wait);
+    let forwarder = forward_room(hub.subscribe("secret"), outbound, 7, None);
+
+    let message = |user_id| ServerMessage::MemberRemoved {
+        room: "secret".to_string(),
+        user_id,
+    };
+    hub.publish("secret", message(8));
+    hub.publish("secret", message(7));
+
+    assert!(matches!(
+        queue.recv().await,
+        Some(ServerMessage::MemberRemoved { user_id: 8, .. })
+    ));
+    assert!(matches!(
+        queue.recv().await,
+        Some(ServerMessage::MemberRemoved { user_id: 7, .. })
+    ));
+    forwarder.await.unwrap();
+
+    // the finished forwarder released the room
+    assert!(hub.active_rooms().is_empty());
+    assert_eq!(queue.recv().await.map(|_| ()), None);
+}
+
+// Test the online list counts every connection of a user once
+#[tokio::test]
+async fn test_presence_counts_connections() {
+    let hub = Hub::new();
+
+    assert!(hub.connect(1, "budi"));
+    assert!(!hub.connect(1, "budi"));
+    assert!(hub.connect(2, "Ani"));
+
+    let online = hub.online_users();
+    let names: Vec<&str> = online.iter().map(|u| u.username.as_str()).collect();
+    assert_eq!(names, vec!["Ani", "budi"]);
+    assert_eq!(online[1].connections, 2);
+
+    // the user stays online until the last connection closes
+    assert!(!hub.disconnect(1));
+    assert!(hub.disconnect(1));
+    assert!(!hub.disconnect(1));
+    assert_eq!(hub.online_users().len(), 1);
+}
+
+// Test a user enters and leaves a room once across connections
+#[tokio::test]
+async fn test_room_presence_counts_connections() {
+    let hub = Hub::new();
+
+    assert!(hub.enter_room("general", 1));
+    assert!(!hub.enter_room("general", 1));
+    assert!(hub.enter_room("general", 2));
+
+    assert!(!hub.exit_room("general", 1));
+    assert!(hub.exit_room("general", 1));
+    assert!(hub.exit_room("general", 2));
+    assert!(!hub.exit_room("general", 2));
+}
+
+// Test typing notices of a room are throttled
+#[tokio::test]
+async fn test_typing_throttle() {
+    let mut typing = HashMap::new();
+    let now = Instant::now();
+
+    assert!(typing_allowed(&mut typing, "general", now));
+    assert!(!typing_allowed(
+        &mut typing,
+        "general",
+        now + Duration::from_secs(1)
+    ));
+    // other rooms are throttled separately
+    assert!(typing_allowed(
+        &mut typing,
+        "random",
+        now + Duration::from_secs(1)
+    ));
+    assert!(typing_allowed(
+        &mut typing,
+        "general",
+        now + TYPING_THROTTLE
+    ));
+}
+
+// Test parsing a typing notice and serializing the online list
+#[tokio::test]
+async fn test_typing_and_presence_messages() {
+    let message: ClientMessage = serde_json::from_str(r#"{"type":"Typing"}"#).unwrap();
+    assert!(matches!(message, ClientMessage::Typing { room: None }));
+
+    let hub = Hub::new();
+    hub.connect(3, "Citra");
+    let presence = serde_json::to_value(ServerMessage::Presence {
+        online: hub.online_users(),
+    })
+    .unwrap();
+    assert_eq!(presence["type"], "Presence");
+    assert_eq!(presence["online"][0]["user_id"], 3);
+    assert_eq!(presence["online"][0]["connections"], 1);
+}
+
+// Test parsing direct messages and read receipts of a client
+#[tokio::test]
+async fn test_direct_message_parsing() {
+    let message: ClientMessage =
+        serde_json::from_str(r#"{"type":"DirectMessage","to_user_id":5,"message":"halo"}"#)
+            .unwrap();
+    assert!(matches!(
+        message,
+        ClientMessage::DirectMessage { to_user_id: 5, ref message } if message == "halo"
+    ));
+
+    let message: ClientMessage =
+        serde_json::from_str(r#"{"type":"MarkRead","from_user_id":5,"last_id":12}"#).unwrap();
+    assert!(matches!(
+        message,
+        ClientMessage::MarkRead {
+            from_user_id: 5,
+            last_id: 12
+        }
+    ));
+}
+
+// Test direct messages reach every connection of the recipient only
+#[tokio::test]
+async fn test_user_topic_delivery() {
+    let hub = Arc::new(Hub::new());
+    let (outbound, mut queue) = Outbound::new(8, SlowConsumerPolicy::Wait);
+    let first = forward_room(hub.subscribe(&user_topic(5)), outbound.clone(), 5, None);
+    let second = forward_room(hub.subscribe(&user_topic(5)), outbound, 5, None);
+    assert_ne!(user_topic(5), user_topic(6));
+
+    let receipt = ServerMessage::DirectRead {
+        user_id: 6,
+        last_id: 3,
+    };
+    assert_eq!(hub.publish(&user_topic(5), receipt.clone()), 2);
+    assert_eq!(hub.publish(&user_topic(6), receipt), 0);
+    for _ in 0..2 {
+        assert!(matches!(
+            queue.recv().await,
+            Some(ServerMessage::DirectRead { user_id: 6, .. })
+        ));
+    }
+
+    first.abort();
+    second.abort();
+}
+
+// Helper function to create a chat message of the general room
+fn create_test_chat(id: i64) -> ServerMessage {
+    ServerMessage::Chat {
+        id,
+        room: DEFAULT_ROOM.to_string(),
+        user_id: 1,
+        username: "Budi".to_string(),
+        message: format!("message {}", id),
+        time: None,
+    }
+}
+
+// Test slow consumer policies are parsed from the environment value
+#[tokio::test]
+async fn test_parse_slow_consumer_policy() {
+    assert_eq!(parse_slow_consumer_policy("drop"), SlowConsumerPolicy::Drop);
+    assert_eq!(
+        parse_slow_consumer_policy(" Disconnect "),
+        SlowConsumerPolicy::Disconnect
+    );
+    assert_eq!(parse_slow_consumer_policy("wait"), SlowConsumerPolicy::Wait);
+    assert_eq!(parse_slow_consumer_policy(""), SlowConsumerPolicy::Wait);
+    assert_eq!(
+        parse_slow_consumer_policy("other"),
+        SlowConsumerPolicy::Wait
+    );
+}
+
+// Test a full queue drops or disconnects depending on the policy
+#[tokio::test]
+async fn test_outbound_full_queue() {
+    let (outbound, mut queue) = Outbound::new(1, SlowConsumerPolicy::Drop);
+    assert_eq!(outbound.send(create_test_chat(1)).await, Delivery::Sent);
+    assert_eq!(outbound.send(create_test_chat(2)).await, Delivery::Dropped);
+    assert!(matches!(
+        queue.recv().await,
+        Some(ServerMessage::Chat { id: 1, .. })
+    ));
+
+    let (outbound, _queue) = Outbound::new(1, SlowConsumerPolicy::Disconnect);
+    assert_eq!(outbound.send(create_test_chat(1)).await, Delivery::Sent);
+    assert_eq!(outbound.send(create_test_chat(2)).await, Delivery::Closed);
+    // the notice is kept until the connection loop waits for it
+    outbound.too_slow().await;
+
+    let (outbound, queue) = Outbound::new(1, SlowConsumerPolicy::Wait);
+    drop(queue);
+    assert_eq!(outbound.send(create_test_chat(1)).await, Delivery::Closed);
+}
+
+// Test a forwarder that fell behind the room tells the client before going on
+#[tokio::test]
+async fn test_forward_room_reports_lag() {
+    let hub = Arc::new(Hub::new());
+    let (outbound, mut queue) = Outbound::new(1, SlowConsumerPolicy::Wait);
+    let forwarder = forward_room(hub.subscribe(DEFAULT_ROOM), outbound, 7, None);
+
+    // channels round their capacity up, twice the capacity always overflows
+    let published = 2 * ROOM_CAPACITY as i64;
+    for id in 1..=published {
+        hub.publish(DEFAULT_ROOM, create_test_chat(id));
+    }
+
+    let Some(ServerMessage::Lagged { room, skipped }) = queue.recv().await else {
+        panic!("expected a lag notice");
+    };
+    assert_eq!(room, DEFAULT_ROOM);
+    assert!(skipped > 0);
+    // without a history the forwarder goes on with the oldest message it still has
+    assert!(matches!(
+        queue.recv().await,
+        Some(ServerMessage::Chat { id, .. }) if id == skipped as i64 + 1
+    ));
+    forwarder.abort();
+}
+
+// Test messages published during a replay wait for it and the replayed ones are skipped
+#[tokio::test]
+async fn test_forward_after_replay() {
+    let hub = Arc::new(Hub::new());
+    let (outbound, mut queue) = Outbound::new(10, SlowConsumerPolicy::Wait);
+    let subscription = hub.subscribe(DEFAULT_ROOM);
+
+    // the replay sent up to 2 while 2 and 3 were published live
+    hub.publish(DEFAULT_ROOM, create_test_chat(2));
+    hub.publish(DEFAULT_ROOM, create_test_chat(3));
+    let forwarder = tokio::spawn(forward(subscription, outbound, 7, None, 2));
+    hub.publish(DEFAULT_ROOM, create_test_chat(4));
+
+    for expected in [3, 4] {
+        assert!(matches!(
+            queue.recv().await,
+            Some(ServerMessage::Chat { id, .. }) if id == expected
+        ));
+    }
+    forwarder.abort();
+}
+
+// Test a resumed room holds live messages until the replay is done and skips the replayed ones
+#[tokio::test]
+async fn test_resume_room() {
+    let hub = Arc::new(Hub::new());
+    let (outbound, mut queue) = Outbound::new(10, SlowConsumerPolicy::Wait);
+    let (replayed, _) = watch::channel(None);
+    let forwarder = resume_room(
+        hub.subscribe(DEFAULT_ROOM),
+        outbound,
+        7,
+        None,
+        replayed.subscribe(),
+    );
+
+    hub.publish(DEFAULT_ROOM, create_test_chat(5));
+    hub.publish(DEFAULT_ROOM, create_test_chat(6));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(queue.try_recv().is_err());
+
+    // the replay sent up to 5
+    replayed.send(Some(5)).unwrap();
+    assert!(matches!(
+        queue.recv().await,
+        Some(ServerMessage::Chat { id: 6, .. })
+    ));
+    forwarder.abort();
+}
+
+// Test only room messages carry a history id
+#[tokio::test]
+async fn test_history_id() {
+    assert_eq!(history_id(&create_test_chat(4)), Some(4));
+    assert_eq!(
+        history_id(&ServerMessage::Resumed {
+            last_id: 4,
+            complete: true
+        }),
+        None
+    );
+}
+
+// Test room messages reach the subscribers of every instance once,
+// online lists only travel as the users of the instance that sends them
+#[tokio::test]
+async fn test_hubs_share_rooms_through_pubsub() {
+    let pubsub = MemoryPubSub::new();
+    let first = Hub::with_pubsub(Arc::new(pubsub.clone()), ChatRules::default());
+    let second = Hub::with_pubsub(Arc::new(pubsub), ChatRules::default());
+    let mut local = first.subscribe(DEFAULT_ROOM);
+    let mut remote = second.subscribe(DEFAULT_ROOM);
+    let mut remote_presence = second.subscribe(PRESENCE_TOPIC);
+
+    assert_eq!(first.publish(DEFAULT_ROOM, create_test_chat(1)), 1);
+    first.publish(PRESENCE_TOPIC, ServerMessage::Presence { online: vec![] });
+
+    let wait = Duration::from_secs(1);
+    assert!(matches!(
+        tokio::time::timeout(wait, remote.recv()).await,
+        Ok(Ok(ServerMessage::Chat { id: 1, .. }))
+    ));
+    assert!(matches!(
+        local.recv().await,
+        Ok(ServerMessage::Chat { id: 1, .. })
+    ));
+
+    // the envelope coming back to the publishing instance is not delivered again
+    let quiet = Duration::from_millis(100);
+    assert!(tokio::time::timeout(quiet, local.recv()).await.is_err());
+    assert!(
+        tokio::time::timeout(quiet, remote_presence.recv())
+            .await
+            .is_err()
+    );
+
+    // the other instance merges the users of this one with its own
+    second.connect(2, "Ani");
+    first.connect(1, "budi");
+    first.publish_presence();
+    match tokio::time::timeout(wait, remote_presence.recv()).await {
+        Ok(Ok(ServerMessage::Presence { online })) => {
+            let ids: Vec<i64> = online.iter().map(|u| u.user_id).collect();
+            assert_eq!(ids, vec![2, 1]);
+        }
+        other => panic!("unexpected presence {:?}", other),
+    }
+    assert_eq!(second.online_users().len(), 2);
+    assert_eq!(first.online_users().len(), 1);
+}
+
+// Test the online lists of instances add up the connections of a user
+#[tokio::test]
+async fn test_merge_online() {
+    let since = chrono::Utc::now();
+    let user = |user_id: i64, username: &str, connections: usize, minutes: i64| OnlineUser {
+        user_id,
+        username: username.to_string(),
+        connections,
+        online_since: since - chrono::Duration::minutes(minutes),
+    };
+
+    let online = merge_online(vec![
+        user(1, "budi", 1, 5),
+        user(2, "Ani", 2, 1),
+        user(1, "budi", 2, 10),
+    ]);
+    let ids: Vec<i64> = online.iter().map(|u| u.user_id).collect();
+    assert_eq!(ids, vec![2, 1]);
+    assert_eq!(online[1].connections, 3);
+    assert_eq!(
+        online[1].online_since,
+        since - chrono::Duration::minutes(10)
+    );
+    assert!(merge_online(Vec::new()).is_empty());
+}
+
+// Test the MySQL poller reads rows again until a gap below them fills or is given up
+#[tokio::test]
+async fn test_poll_cursor() {
+    let start = Instant::now();
+    let grace = Duration::from_secs(10);
+    let mut cursor = PollCursor::new(10);
+
+    // row 12 commits before row 11
+    assert!(!cursor.accept(10, start));
+    assert!(cursor.accept(12, start));
+    cursor.advance(start, grace);
+    assert_eq!((cursor.floor(), cursor.window()), (10, 1));
+
+    // the next poll reads row 12 again and finds row 11
+    assert!(cursor.accept(11, start));
+    assert!(!cursor.accept(12, start));
+    cursor.advance(start, grace);
+    assert_eq!((cursor.floor(), cursor.window()), (12, 0));
+
+    // a gap that never fills, a rolled back insert, is given up after the grace
+    assert!(cursor.accept(14, start));
+    cursor.advance(start + Duration::from_secs(5), grace);
+    assert_eq!(cursor.floor(), 12);
+    cursor.advance(start + grace, grace);
+    assert_eq!((cursor.floor(), cursor.window()), (14, 0));
+    assert!(!cursor.accept(13, start + grace));
+}
+
+// Test the MySQL transport delivers a row that commits after a newer one
+#[tokio::test]
+#[ignore = "needs a MySQL database in TEST_DATABASE_URL"]
+async fn test_mysql_pubsub() {
+    let db = crate::config::database::connect_test().await;
+    let subscriber = MySqlPubSub {
+        db: db.clone(),
+        poll_interval: Duration::from_millis(50),
+    };
+    let mut incoming = subscriber.subscribe();
+    // let the subscription find where it starts
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let envelope = |id: i64| Envelope {
+        origin: "other".to_string(),
+        room: DEFAULT_ROOM.to_string(),
+        message: create_test_chat(id),
+    };
+    let slow = serde_json::to_string(&envelope(1)).unwrap();
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query!(
+        "INSERT INTO pubsub_messages (origin, payload) VALUES (?, ?)",
+        "other",
+        slow
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    subscriber.publish(&envelope(2)).await.unwrap();
+
+    let wait = Duration::from_secs(2);
+    let received = tokio::time::timeout(wait, incoming.recv()).await.unwrap();
+    assert!(matches!(
+        received.map(|e| e.message),
+        Some(ServerMessage::Chat { id: 2, .. })
+    ));
+
+    // the slower insert commits behind the row already delivered
+    tx.commit().await.unwrap();
+    let received = tokio::time::timeout(wait, incoming.recv()).await.unwrap();
+    assert!(matches!(
+        received.map(|e| e.message),
+        Some(ServerMessage::Chat { id: 1, .. })
+    ));
+    let quiet = Duration::from_millis(300);
+    assert!(tokio::time::timeout(quiet, incoming.recv()).await.is_err());
+}
+
+// Test Redis commands are encoded and replies parsed
+#[tokio::test]
+async fn test_resp_encoding() {
+    assert_eq!(
+        encode_command(&[b"PUBLISH", b"chat", b"hi"]),
+        b"*3\r\n$7\r\nPUBLISH\r\n$4\r\nchat\r\n$2\r\nhi\r\n".to_vec()
+    );
+
+    let mut reply: &[u8] =
+        b"*3\r\n$7\r\nmessage\r\n$4\r\nchat\r\n$5\r\nhe\r\no\r\n:1\r\n-ERR wrong\r\n$-1\r\n";
+    let message = read_resp(&mut reply).await.unwrap();
+    assert_eq!(pushed_message(message), Some(b"he\r\no".to_vec()));
+    assert_eq!(read_resp(&mut reply).await.unwrap(), RespValue::Integer(1));
+    assert_eq!(
+        read_resp(&mut reply).await.unwrap(),
+        RespValue::Error("ERR wrong".to_string())
+    );
+    assert_eq!(read_resp(&mut reply).await.unwrap(), RespValue::Bulk(None));
+    assert!(read_resp(&mut reply).await.is_err());
+
+    // the confirmation of a subscription is not a message
+    let mut confirmation: &[u8] = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nchat\r\n:1\r\n";
+    let value = read_resp(&mut confirmation).await.unwrap();
+    assert_eq!(pushed_message(value), None);
+}
+
+// Test the Redis transport publishes the envelope as JSON
+#[tokio::test]
+async fn test_redis_publish() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        // the command ends with the \r\n after the payload
+        while !received.ends_with(b"}\r\n") {
+            let n = socket.read(&mut buf).await.unwrap();
+            received.extend_from_slice(&buf[..n]);
+        }
+        socket.write_all(b":2\r\n").await.unwrap();
+        received
+    });
+
+    let redis = RedisPubSub::new(&format!("redis://{}", address), "chat");
+    let envelope = Envelope {
+        origin: "a".to_string(),
+        room: DEFAULT_ROOM.to_string(),
+        message: create_test_chat(3),
+    };
+    redis.publish(&envelope).await.unwrap();
+
+    let received = String::from_utf8(server.await.unwrap()).unwrap();
+    assert!(received.starts_with("*3\r\n$7\r\nPUBLISH\r\n$4\r\nchat\r\n"));
+    assert!(received.contains(r#""origin":"a""#));
+
+    // nothing listens anymore, the publish fails and the next one reconnects
+    assert!(redis.publish(&envelope).await.is_err());
+}
+
+// Test two instances share a room through a local redis-server (REDIS_ADDRESS)
+#[tokio::test]
+#[ignore = "needs a running redis-server"]
+async fn test_redis_pubsub() {
+    let address = std::env::var("REDIS_ADDRESS").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
+    let first = Hub::with_pubsub(
+        Arc::new(RedisPubSub::new(&address, "chat-test")),
+        ChatRules::default(),
+    );
+    let second = Hub::with_pubsub(
+        Arc::new(RedisPubSub::new(&address, "chat-test")),
+        ChatRules::default(),
+    );
+    let mut remote = second.subscribe(DEFAULT_ROOM);
+
+    // give the subscription time to reach the server
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    first.publish(DEFAULT_ROOM, create_test_chat(5));
+
+    assert!(matches!(
+        tokio::time::timeout(Duration::from_secs(2), remote.recv()).await,
+        Ok(Ok(ServerMessage::Chat { id: 5, .. }))
+    ));
+}
+
+// Test events only reach the topics allowed to see them
+#[tokio::test]
+async fn test_event_topics() {
+    let uploaded = Event::DocumentUploaded {
+        document_id: 10,
+        version: 2,
+        user_id: 4,
+        name: "laporan".to_string(),
+        size: 1024,
+        mime_type: "application/pdf".to_string(),
+        scan_status: "clean".to_string(),
+    };
+    assert_eq!(uploaded.topics(), vec![user_topic(4)]);
+    assert_eq!(
+        Event::UserDeleted { user_id: 4 }.topics(),
+        vec![user_topic(4), ADMIN_TOPIC.to_string()]
+    );
+
+    let message = serde_json::to_value(uploaded.message(chrono::Utc::now())).unwrap();
+    assert_eq!(message["type"], "DocumentUploaded");
+    assert_eq!(message["document_id"], 10);
+    assert_eq!(message["version"], 2);
+}
+
+// Test the event bus delivers user changes to the user and to admins
+#[tokio::test]
+async fn test_event_bus() {
+    let hub = Arc::new(Hub::new());
+    let events = EventBus::new(Arc::clone(&hub));
+    let mut user = hub.subscribe(&user_topic(4));
+    let mut admins = hub.subscribe(ADMIN_TOPIC);
+    let mut other = hub.subscribe(&user_topic(5));
+
+    events.publish(Event::UserUpdated {
+        user_id: 4,
+        name: "Budi".to_string(),
+        email: "budi@example.com".to_string(),
+    });
+
+    for subscription in [&mut user, &mut admins] {
+        assert!(matches!(
+            subscription.recv().await,
+            Ok(ServerMessage::UserUpdated { user_id: 4, .. })
+        ));
+    }
+    let quiet = Duration::from_millis(50);
+    assert!(tokio::time::timeout(quiet, other.recv()).await.is_err());
+}
+
+// Test the Last-Event-ID header wins over the query parameter
+#[test]
+fn test_last_event_id() {
+    let headers = create_test_headers(&[(header::HeaderName::from_static(LAST_EVENT_ID), " 42 ")]);
+    assert_eq!(last_event_id(&headers, Some(7)), Some(42));
+
+    let headers = create_test_headers(&[(header::HeaderName::from_static(LAST_EVENT_ID), "abc")]);
+    assert_eq!(last_event_id(&headers, Some(7)), Some(7));
+    assert_eq!(last_event_id(&HeaderMap::new(), None), None);
+    assert_eq!(last_event_id(&HeaderMap::new(), Some(0)), None);
+}
+
+// Test the rooms of an event stream are normalized and deduplicated
+#[test]
+fn test_event_rooms() {
+    assert_eq!(event_rooms(None), Ok(vec![DEFAULT_ROOM.to_string()]));
+    assert_eq!(event_rooms(Some(" ")), Ok(vec![DEFAULT_ROOM.to_string()]));
+    assert_eq!(
+        event_rooms(Some("General, rust,general")),
+        Ok(vec!["general".to_string(), "rust".to_string()])
+    );
+    assert_eq!(
+        event_rooms(Some("rust,no room")),
+        Err("Invalid room no room".to_string())
+    );
+}
+
+// Test only room messages set the id an event stream resumes from
+#[tokio::test]
+async fn test_sse_event_id() {
+    use axum::response::sse::Sse;
+
+    let chat = ServerMessage::Chat {
+        id: 15,
+        room: "general".to_string(),
+        user_id: 1,
+        username: "Budi".to_string(),
+        message: "hi".to_string(),
+        time: None,
+    };
+    let presence = ServerMessage::Presence { online: Vec::new() };
+    let events = futures::stream::iter([sse_event(&chat), sse_event(&presence)]);
+
+    let body = Sse::new(events).into_response().into_body();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let text = String::from_utf8(bytes.to_vec()).unwrap();
+    let (first, second) = text.split_once("\n\n").unwrap();
+    assert!(first.ends_with("\nid: 15"));
+    assert!(first.contains("\"type\":\"Chat\""));
+    assert!(!second.contains("\nid: "));
+    assert!(second.contains("\"type\":\"Presence\""));
+}
+
+// Helper function to create the user of a connection
+fn create_test_chat_user(id: i64) -> ChatUser {
+    ChatUser {
+        id,
+        name: "Siti".to_string(),
+        admin: false,
+        moderator: false,
+        muted_until: None,
+        banned_until: None,
+    }
+}
+
+// Test moderation commands parse with optional durations and reasons
+#[tokio::test]
+async fn test_moderation_client_messages() {
+    let mute = serde_json::from_str::<ClientMessage>(r#"{"type":"Mute","user_id":3}"#).unwrap();
+    assert!(matches!(
+        mute,
+        ClientMessage::Mute {
+            user_id: 3,
+            seconds: None,
+            reason: None
+        }
+    ));
+
+    let ban = r#"{"type":"Ban","user_id":3,"seconds":600,"reason":"flooding"}"#;
+    match serde_json::from_str::<ClientMessage>(ban).unwrap() {
+        ClientMessage::Ban {
+            user_id,
+            seconds,
+            reason,
+        } => {
+            assert_eq!((user_id, seconds), (3, Some(600)));
+            assert_eq!(reason.as_deref(), Some("flooding"));
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+
+    let edit = r#"{"type":"Edit","id":15,"message":"fixed"}"#;
+    assert!(matches!(
+        serde_json::from_str::<ClientMessage>(edit).unwrap(),
+        ClientMessage::Edit { id: 15, .. }
+    ));
+}
+
+// Test mutes reach the user of a connection and end with an unmute
+#[tokio::test]
+async fn test_apply_mute() {
+    let mut user = create_test_chat_user(3);
+    let now = chrono::Utc::now();
+
+    apply_mute(
+        &mut user,
+        &ServerMessage::UserMuted {
+            user_id: 4,
+            until: None,
+            reason: None,
+        },
+    );
+    assert!(!user.is_muted(now));
+
+    apply_mute(
+        &mut user,
+        &ServerMessage::UserMuted {
+            user_id: 3,
+            until: None,
+            reason: None,
+        },
+    );
+    assert!(user.is_muted(now));
+
+    apply_mute(&mut user, &ServerMessage::UserUnmuted { user_id: 3 });
+    assert!(!user.is_muted(now));
+
+    user.muted_until = Some(now - chrono::Duration::seconds(1));
+    assert!(!user.is_muted(now));
+}
+
+// Test only a deletion or ban of the connection's own user closes it
+#[tokio::test]
+async fn test_closes_connection() {
+    let banned = ServerMessage::UserBanned {
+        user_id: 3,
+        until: None,
+        reason: None,
+    };
+    let deleted = ServerMessage::UserDeleted {
+        user_id: 3,
+        time: chrono::Utc::now(),
+    };
+
+    assert!(closes_connection(&banned, 3));
+    assert!(closes_connection(&deleted, 3));
+    assert!(!closes_connection(&banned, 4));
+    assert!(!closes_connection(
+        &ServerMessage::UserMuted {
+            user_id: 3,
+            until: None,
+            reason: None,
+        },
+        3
+    ));
+}
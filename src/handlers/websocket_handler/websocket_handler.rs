@@ -0,0 +1,1556 @@
+use axum::{
+    Extension, Json,
+    extract::{
+        Query, WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket, close_code},
+    },
+    http::{HeaderMap, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
+};
+use chrono::{DateTime, Utc};
+use futures::{
+    SinkExt, StreamExt,
+    future::join_all,
+    stream::{self, SplitSink},
+};
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{
+    Notify,
+    broadcast::error::RecvError,
+    mpsc::{self, error::TrySendError},
+    watch,
+};
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval_at};
+
+use crate::config::cors::is_allowed_origin;
+use crate::config::websocket::{
+    SlowConsumerPolicy, idle_timeout, outbound_buffer, ping_interval, slow_consumer_policy,
+};
+use crate::handlers::direct_message_handler::{
+    delivered_by_sender, direct_server_message, find_user_name, mark_delivered, mark_read,
+    store_direct_message, undelivered_messages, unread_counts,
+};
+use crate::handlers::moderation_handler::{
+    SANCTION_BAN, SANCTION_MUTE, active_sanctions, check_sanction_reason, delete_room_message,
+    edit_room_message, find_room_message, find_user_role, is_moderator_role, lift_sanction,
+    may_change_message, sanction_expiry, sanction_refusal, sanction_until, store_sanction,
+};
+use crate::handlers::room_handler::{
+    MESSAGE_CHAT, MESSAGE_JOINED, MESSAGE_LEFT, RoomAccess, room_access, room_messages_after,
+    server_message, store_room_message,
+};
+use crate::schemas::message_schema::{ClientMessage, EventsQuery, ServerMessage, WebSocketQuery};
+use crate::schemas::room_schema::ChatMessage;
+use crate::utils::hub::{
+    ADMIN_TOPIC, DEFAULT_ROOM, Hub, PRESENCE_TOPIC, Subscription, normalize_room_name, user_topic,
+};
+use crate::utils::jwt::{Claims, verify_token};
+use crate::utils::moderation::filter_message;
+use crate::utils::response::ApiResponse;
+
+#[cfg(test)]
+#[path = "./tests.rs"]
+mod tests;
+
+// header a reconnecting EventSource sends with the id of the last event it got
+pub const LAST_EVENT_ID: &str = "last-event-id";
+// comment sent to an idle event stream so proxies keep it open
+const KEEP_ALIVE_TEXT: &str = "keep-alive";
+
+// subprotocol a browser client offers followed by its JWT, e.g. ["access_token", "<jwt>"],
+// it is echoed back on the upgrade so the handshake succeeds
+pub const TOKEN_PROTOCOL: &str = "access_token";
+// cookie holding the JWT for same-site browser clients
+pub const TOKEN_COOKIE: &str = "token";
+// most history entries replayed on Resume, older ones are paged from the history endpoint
+const REPLAY_LIMIT: i64 = 500;
+// stored direct messages delivered per batch on connect, batches follow until none are left
+const PENDING_BATCH: i64 = 500;
+// shortest interval between two typing notices of a connection in one room
+const TYPING_THROTTLE: Duration = Duration::from_secs(2);
+
+// type alias for handler response
+type HandlerResponse = (StatusCode, Json<ApiResponse<Value>>);
+
+#[derive(Debug, PartialEq)]
+pub enum TokenSource {
+    Query,
+    Protocol,
+    Cookie,
+}
+
+// authenticated user of a connection, messages are attributed with its real name
+#[derive(Debug, Clone)]
+pub struct ChatUser {
+    pub id: i64,
+    pub name: String,
+    // admins also receive the changes of other users
+    pub admin: bool,
+    // admins and moderators may change any room message, mute and ban, the role is read on connect
+    pub moderator: bool,
+    // kept up to date by the mutes the connection receives, only drops typing notices,
+    // messages are checked against the stored sanctions
+    pub muted_until: Option<DateTime<Utc>>,
+    // only checked when connecting, a ban closes the open connections
+    pub banned_until: Option<DateTime<Utc>>,
+}
+
+impl ChatUser {
+    pub fn is_muted(&self, now: DateTime<Utc>) -> bool {
+        self.muted_until.is_some_and(|until| until > now)
+    }
+
+    pub fn is_banned(&self, now: DateTime<Utc>) -> bool {
+        self.banned_until.is_some_and(|until| until > now)
+    }
+}
+
+// function for loading the user of a connection,
+// a deleted user keeps a valid token until it expires, but may not chat anymore
+async fn find_chat_user(db: &MySqlPool, user_id: i64) -> Result<Option<ChatUser>, sqlx::Error> {
+    let user = sqlx::query!(
+        "SELECT id, name, role FROM users WHERE id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let Some(user) = user else {
+        return Ok(None);
+    };
+    let sanctions = active_sanctions(db, user.id).await?;
+
+    Ok(Some(ChatUser {
+        id: user.id,
+        admin: user.role == "admin",
+        moderator: is_moderator_role(&user.role),
+        name: user.name,
+        muted_until: sanction_until(&sanctions, SANCTION_MUTE),
+        banned_until: sanction_until(&sanctions, SANCTION_BAN),
+    }))
+}
+
+// function for building an error response before the upgrade
+fn reject(status: StatusCode, message: &str) -> Response {
+    (status, Json(ApiResponse::<()>::error(message))).into_response()
+}
+
+// function for finding the JWT of an upgrade request in the query, the subprotocols or a cookie
+pub fn extract_token(headers: &HeaderMap, query: Option<&str>) -> Option<(String, TokenSource)> {
+    if let Some(token) = query.map(str::trim).filter(|token| !token.is_empty()) {
+        return Some((token.to_string(), TokenSource::Query));
+    }
+
+    let protocols = headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<&str>>();
+    let token = protocols
+        .iter()
+        .position(|protocol| *protocol == TOKEN_PROTOCOL)
+        .and_then(|index| protocols.get(index + 1))
+        .filter(|token| !token.is_empty());
+    if let Some(token) = token {
+        return Some((token.to_string(), TokenSource::Protocol));
+    }
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, value)| *name == TOKEN_COOKIE && !value.is_empty())
+        .map(|(_, value)| (value.to_string(), TokenSource::Cookie))
+}
+
+// function for authenticating a WebSocket upgrade before the connection is accepted
+pub async fn websocket(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(query): Query<WebSocketQuery>,
+    Extension(db): Extension<MySqlPool>,
+    Extension(hub): Extension<Arc<Hub>>,
+) -> Response {
+    // Browsers always send Origin, other clients are trusted to authenticate with the token only
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let allowed = origin.to_str().is_ok_and(is_allowed_origin);
+        if !allowed {
+            println!("Rejected WS origin: {:?}", origin);
+            return reject(StatusCode::FORBIDDEN, "Origin not allowed");
+        }
+    }
+
+    let Some((token, source)) = extract_token(&headers, query.token.as_deref()) else {
+        return reject(StatusCode::UNAUTHORIZED, "Missing token");
+    };
+    let claims = match verify_token(&token) {
+        Ok(claims) => claims,
+        Err(e) => {
+            println!("JWT Verification Error: {:?}", e);
+            return reject(StatusCode::UNAUTHORIZED, "Invalid or expired token");
+        }
+    };
+
+    let user = match find_chat_user(&db, claims.sub).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return reject(StatusCode::UNAUTHORIZED, "User not found"),
+        Err(e) => {
+            println!("User Lookup Error: {:?}", e);
+            return reject(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+        }
+    };
+    if user.is_banned(Utc::now()) {
+        return reject(StatusCode::FORBIDDEN, "You are banned from the chat");
+    }
+
+    let ws = if source == TokenSource::Protocol {
+        ws.protocols([TOKEN_PROTOCOL])
+    } else {
+        ws
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, db, hub, user))
+}
+
+// queue of the messages waiting for the socket of one connection
+#[derive(Clone)]
+struct Outbound {
+    tx: mpsc::Sender<ServerMessage>,
+    policy: SlowConsumerPolicy,
+    // wakes the connection loop when the disconnect policy gives up on the client
+    slow: Arc<Notify>,
+}
+
+// outcome of queueing a message for a connection
+#[derive(Debug, PartialEq)]
+enum Delivery {
+    Sent,
+    Dropped,
+    Closed,
+}
+
+impl Outbound {
+    fn new(capacity: usize, policy: SlowConsumerPolicy) -> (Self, mpsc::Receiver<ServerMessage>) {
+        let (tx, queue) = mpsc::channel(capacity);
+        let outbound = Outbound {
+            tx,
+            policy,
+            slow: Arc::new(Notify::new()),
+        };
+
+        (outbound, queue)
+    }
+
+    // function for queueing a message published to a room, a full queue is handled by the policy
+    async fn send(&self, message: ServerMessage) -> Delivery {
+        if self.policy == SlowConsumerPolicy::Wait {
+            return match self.tx.send(message).await {
+                Ok(()) => Delivery::Sent,
+                Err(_) => Delivery::Closed,
+            };
+        }
+
+        match self.tx.try_send(message) {
+            Ok(()) => Delivery::Sent,
+            Err(TrySendError::Closed(_)) => Delivery::Closed,
+            Err(TrySendError::Full(_)) if self.policy == SlowConsumerPolicy::Drop => {
+                Delivery::Dropped
+            }
+            Err(TrySendError::Full(_)) => {
+                self.slow.notify_one();
+                Delivery::Closed
+            }
+        }
+    }
+
+    // function for queueing an answer to a request of the client, it always waits for the queue
+    async fn reply(&self, message: ServerMessage) -> bool {
+        self.tx.send(message).await.is_ok()
+    }
+
+    // function for waiting until the disconnect policy gives up on the client
+    async fn too_slow(&self) {
+        self.slow.notified().await
+    }
+}
+
+// history a room forwarder replays from after the connection missed messages
+struct RoomHistory {
+    db: MySqlPool,
+    room_id: i64,
+}
+
+// function for getting the history id of a room message, other messages are not replayed
+fn history_id(message: &ServerMessage) -> Option<i64> {
+    match message {
+        ServerMessage::UserJoined { id, .. }
+        | ServerMessage::UserLeft { id, .. }
+        | ServerMessage::Chat { id, .. } => Some(*id),
+        _ => None,
+    }
+}
+
+// function for telling a connection it missed messages of a room and replaying them after last_id,
+// without a history or a known last id the client resumes by itself
+async fn catch_up(
+    outbound: &Outbound,
+    room: &str,
+    skipped: u64,
+    history: Option<&RoomHistory>,
+    last_id: &mut i64,
+) -> Delivery {
+    let notice = ServerMessage::Lagged {
+        room: room.to_string(),
+        skipped,
+    };
+    let delivery = outbound.send(notice).await;
+    let Some(history) = history.filter(|_| *last_id > 0) else {
+        return delivery;
+    };
+    if delivery != Delivery::Sent {
+        return delivery;
+    }
+
+    // one extra row tells whether the history goes on past the limit
+    let room_ids = [history.room_id];
+    let rows = room_messages_after(&history.db, &room_ids, *last_id, REPLAY_LIMIT + 1);
+    let mut messages = match rows.await {
+        Ok(messages) => messages,
+        Err(e) => {
+            println!("Chat History Error: {:?}", e);
+            return Delivery::Sent;
+        }
+    };
+    let complete = messages.len() as i64 <= REPLAY_LIMIT;
+    messages.truncate(REPLAY_LIMIT as usize);
+
+    for message in messages {
+        let id = message.id;
+        if let Some(message) = server_message(message) {
+            match outbound.send(message).await {
+                Delivery::Sent => *last_id = id,
+                delivery => return delivery,
+            }
+        }
+    }
+    let resumed = ServerMessage::Resumed {
+        last_id: *last_id,
+        complete,
+    };
+    outbound.send(resumed).await
+}
+
+// function for forwarding the messages of a room to a connection until it leaves the room,
+// a connection removed from a private room stops on the notice of its removal,
+// missed messages are replayed from the history once the connection keeps up again
+fn forward_room(
+    subscription: Subscription,
+    outbound: Outbound,
+    user_id: i64,
+    history: Option<RoomHistory>,
+) -> JoinHandle<()> {
+    tokio::spawn(forward(subscription, outbound, user_id, history, 0))
+}
+
+// function for forwarding the messages of a room after the room message last_id,
+// the ones up to it were sent already
+async fn forward(
+    mut subscription: Subscription,
+    outbound: Outbound,
+    user_id: i64,
+    history: Option<RoomHistory>,
+    mut last_id: i64,
+) {
+    let mut skipped: u64 = 0;
+
+    loop {
+        let message = match subscription.recv().await {
+            Ok(message) => message,
+            Err(RecvError::Lagged(missed)) => {
+                println!(
+                    "Room {} skipped {} messages for user {}",
+                    subscription.room(),
+                    missed,
+                    user_id
+                );
+                skipped += missed;
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        if skipped > 0 {
+            let room = subscription.room().to_string();
+            match catch_up(&outbound, &room, skipped, history.as_ref(), &mut last_id).await {
+                Delivery::Sent => skipped = 0,
+                // still behind, the next message tries again
+                Delivery::Dropped => {}
+                Delivery::Closed => break,
+            }
+        }
+
+        let id = history_id(&message);
+        // already sent by the replay
+        if id.is_some_and(|id| id <= last_id) {
+            continue;
+        }
+        let removed = matches!(
+            &message,
+            ServerMessage::MemberRemoved { user_id: removed, .. } if *removed == user_id
+        );
+        match outbound.send(message).await {
+            Delivery::Sent => last_id = id.unwrap_or(last_id),
+            Delivery::Dropped => skipped += 1,
+            Delivery::Closed => break,
+        }
+        if removed {
+            break;
+        }
+    }
+}
+
+// function for forwarding the messages of a room once the replay of a Resume is done,
+// live messages wait in the subscription meanwhile and the replayed ones are skipped
+fn resume_room(
+    subscription: Subscription,
+    outbound: Outbound,
+    user_id: i64,
+    history: Option<RoomHistory>,
+    mut replayed: watch::Receiver<Option<i64>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let replayed_id = match replayed.wait_for(Option::is_some).await {
+            Ok(replayed_id) => replayed_id.unwrap_or_default(),
+            Err(_) => return,
+        };
+        forward(subscription, outbound, user_id, history, replayed_id).await;
+    })
+}
+
+// room a connection has joined
+struct JoinedRoom {
+    id: i64,
+    forwarder: JoinHandle<()>,
+}
+
+// function for finding a room the connection is still subscribed to
+fn joined_room<'a>(rooms: &'a HashMap<String, JoinedRoom>, room: &str) -> Option<&'a JoinedRoom> {
+    rooms
+        .get(room)
+        .filter(|joined| !joined.forwarder.is_finished())
+}
+
+// function for subscribing a connection to a room it is allowed to join, returns the room id
+// and whether this is the first connection of the user in the room
+async fn join_room(
+    db: &MySqlPool,
+    hub: &Arc<Hub>,
+    user: &ChatUser,
+    room: &str,
+    rooms: &mut HashMap<String, JoinedRoom>,
+    outbound: &Outbound,
+) -> Result<(i64, bool), String> {
+    let room_id = match room_access(db, room, user.id).await {
+        Ok(RoomAccess::Allowed(room_id)) => room_id,
+        Ok(RoomAccess::NotFound) => return Err(format!("Room {} not found", room)),
+        Ok(RoomAccess::Forbidden) => return Err(format!("You are not a member of {}", room)),
+        Err(e) => {
+            println!("Room Access Error: {:?}", e);
+            return Err("Internal server error".to_string());
+        }
+    };
+
+    let mut entered = false;
+    if joined_room(rooms, room).is_none() {
+        // A room the connection was removed from is still counted until it is left
+        if !rooms.contains_key(room) {
+            entered = hub.enter_room(room, user.id);
+        }
+        let history = RoomHistory {
+            db: db.clone(),
+            room_id,
+        };
+        let forwarder = forward_room(
+            hub.subscribe(room),
+            outbound.clone(),
+            user.id,
+            Some(history),
+        );
+        rooms.insert(
+            room.to_string(),
+            JoinedRoom {
+                id: room_id,
+                forwarder,
+            },
+        );
+    }
+    Ok((room_id, entered))
+}
+
+// function for storing a room message in the history and sending it with its id,
+// nothing is sent when it can not be stored so replays never miss a message
+async fn publish_room_message(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    (room, room_id): (&str, i64),
+    kind: &str,
+    message: Option<String>,
+) -> Result<ServerMessage, String> {
+    let time = Utc::now();
+    let id = match store_room_message(db, room_id, user.id, kind, message.as_deref(), time).await {
+        Ok(id) => id,
+        Err(e) => {
+            println!("Chat History Error: {:?}", e);
+            return Err("Failed to store message".to_string());
+        }
+    };
+
+    let stored = server_message(ChatMessage {
+        id,
+        room: room.to_string(),
+        user_id: user.id,
+        username: user.name.clone(),
+        kind: kind.to_string(),
+        message,
+        created_at: Some(time),
+        edited_at: None,
+    })
+    .ok_or_else(|| format!("Unknown message kind {}", kind))?;
+    hub.publish(room, stored.clone());
+
+    Ok(stored)
+}
+
+// function for replaying the history of the joined rooms after last_id, returns the last id it sent,
+// the forwarders of the rooms hold live messages until it is done
+async fn replay(
+    db: &MySqlPool,
+    room_ids: &[i64],
+    last_id: i64,
+    outbound: &Outbound,
+) -> Result<i64, String> {
+    // one extra row tells whether the history goes on past the limit
+    let mut messages = match room_messages_after(db, room_ids, last_id, REPLAY_LIMIT + 1).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            println!("Chat History Error: {:?}", e);
+            return Err("Failed to load history".to_string());
+        }
+    };
+    let complete = messages.len() as i64 <= REPLAY_LIMIT;
+    messages.truncate(REPLAY_LIMIT as usize);
+
+    let mut replayed_id = last_id;
+    for message in messages {
+        replayed_id = message.id;
+        if let Some(message) = server_message(message) {
+            if !outbound.reply(message).await {
+                return Ok(replayed_id);
+            }
+        }
+    }
+    let _ = outbound
+        .reply(ServerMessage::Resumed {
+            last_id: replayed_id,
+            complete,
+        })
+        .await;
+
+    Ok(replayed_id)
+}
+
+// function for sending the direct messages that came while the user was offline,
+// a message sent during the lookup may arrive twice, clients drop ids they already have
+async fn deliver_pending(
+    db: &MySqlPool,
+    hub: &Hub,
+    user_id: i64,
+    outbound: &Outbound,
+) -> Result<(), String> {
+    loop {
+        let messages = match undelivered_messages(db, user_id, PENDING_BATCH).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                println!("Direct Message Error: {:?}", e);
+                return Err("Failed to load direct messages".to_string());
+            }
+        };
+
+        for message in &messages {
+            if !outbound.reply(direct_server_message(message.clone())).await {
+                return Ok(());
+            }
+        }
+        let ids = messages.iter().map(|m| m.id).collect::<Vec<i64>>();
+        // The same batch would be loaded again if it could not be marked
+        if let Err(e) = mark_delivered(db, &ids, Utc::now()).await {
+            println!("Direct Message Error: {:?}", e);
+            return Err("Failed to mark direct messages delivered".to_string());
+        }
+        for (sender_id, ids) in delivered_by_sender(&messages) {
+            hub.publish(
+                &user_topic(sender_id),
+                ServerMessage::DirectDelivered {
+                    to_user_id: user_id,
+                    ids,
+                },
+            );
+        }
+
+        if (messages.len() as i64) < PENDING_BATCH {
+            break;
+        }
+    }
+
+    match unread_counts(db, user_id).await {
+        Ok(counts) => {
+            let _ = outbound.reply(ServerMessage::Unread { counts }).await;
+            Ok(())
+        }
+        Err(e) => {
+            println!("Direct Message Error: {:?}", e);
+            Err("Failed to count unread messages".to_string())
+        }
+    }
+}
+
+// function for storing a direct message and sending it to the connections of both users
+async fn send_direct_message(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    to_user_id: i64,
+    message: String,
+) -> Result<(), String> {
+    if message.trim().is_empty() {
+        return Err("Message can not be empty".to_string());
+    }
+    if to_user_id == user.id {
+        return Err("You can not message yourself".to_string());
+    }
+    match find_user_name(db, to_user_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(format!("User {} not found", to_user_id)),
+        Err(e) => {
+            println!("User Lookup Error: {:?}", e);
+            return Err("Internal server error".to_string());
+        }
+    }
+
+    let time = Utc::now();
+    let id = match store_direct_message(db, user.id, to_user_id, &message, time).await {
+        Ok(id) => id,
+        Err(e) => {
+            println!("Direct Message Error: {:?}", e);
+            return Err("Failed to store message".to_string());
+        }
+    };
+    let direct = ServerMessage::DirectMessage {
+        id,
+        from_user_id: user.id,
+        from_username: user.name.clone(),
+        to_user_id,
+        message,
+        time: Some(time),
+    };
+
+    // An offline recipient gets the message from the database when it connects,
+    // a connected one acknowledges it on whichever instance it is connected to
+    hub.publish(&user_topic(to_user_id), direct.clone());
+    hub.publish(&user_topic(user.id), direct);
+
+    Ok(())
+}
+
+// function for marking the direct messages that reach the user's topic on this instance as delivered,
+// the connection that marks a message first tells its sender, the others find it marked already
+fn acknowledge_direct(
+    db: MySqlPool,
+    hub: Arc<Hub>,
+    mut subscription: Subscription,
+    user_id: i64,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let (id, from_user_id) = match subscription.recv().await {
+                Ok(ServerMessage::DirectMessage {
+                    id,
+                    from_user_id,
+                    to_user_id,
+                    ..
+                }) if to_user_id == user_id => (id, from_user_id),
+                // a missed message stays undelivered and is sent again on the next connect
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            match mark_delivered(&db, &[id], Utc::now()).await {
+                Ok(0) => {}
+                Ok(_) => {
+                    hub.publish(
+                        &user_topic(from_user_id),
+                        ServerMessage::DirectDelivered {
+                            to_user_id: user_id,
+                            ids: vec![id],
+                        },
+                    );
+                }
+                Err(e) => println!("Direct Message Error: {:?}", e),
+            }
+        }
+    })
+}
+
+// function for marking the direct messages from a user as read and telling the sender
+async fn read_direct_messages(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    from_user_id: i64,
+    last_id: i64,
+) -> Result<(), String> {
+    let marked = match mark_read(db, user.id, from_user_id, last_id, Utc::now()).await {
+        Ok(marked) => marked,
+        Err(e) => {
+            println!("Direct Message Error: {:?}", e);
+            return Err("Failed to mark messages read".to_string());
+        }
+    };
+    if marked == 0 {
+        return Ok(());
+    }
+
+    hub.publish(
+        &user_topic(from_user_id),
+        ServerMessage::DirectRead {
+            user_id: user.id,
+            last_id,
+        },
+    );
+    // The other connections of the user update their counts as well
+    match unread_counts(db, user.id).await {
+        Ok(counts) => {
+            hub.publish(&user_topic(user.id), ServerMessage::Unread { counts });
+            Ok(())
+        }
+        Err(e) => {
+            println!("Direct Message Error: {:?}", e);
+            Err("Failed to count unread messages".to_string())
+        }
+    }
+}
+
+// function for checking a typing notice may be sent to a room, repeats are dropped
+fn typing_allowed(typing: &mut HashMap<String, Instant>, room: &str, now: Instant) -> bool {
+    let throttled = typing
+        .get(room)
+        .is_some_and(|last| now.duration_since(*last) < TYPING_THROTTLE);
+    if !throttled {
+        typing.insert(room.to_string(), now);
+    }
+
+    !throttled
+}
+
+// function for listing the users currently connected over the WebSocket
+pub async fn presence(Extension(hub): Extension<Arc<Hub>>) -> HandlerResponse {
+    (
+        StatusCode::OK,
+        Json(ApiResponse::success(
+            "List Online Users",
+            json!(hub.online_users()),
+        )),
+    )
+}
+
+// function for handling a message of the client, the answer and errors go to the connection only,
+// the loop writes them to the socket itself since it is the one draining the queue
+async fn handle_client_message(
+    message: ClientMessage,
+    db: &MySqlPool,
+    hub: &Arc<Hub>,
+    user: &ChatUser,
+    rooms: &mut HashMap<String, JoinedRoom>,
+    typing: &mut HashMap<String, Instant>,
+    outbound: &Outbound,
+) -> Result<Option<ServerMessage>, String> {
+    match message {
+        ClientMessage::Join { room } => {
+            let room = room_name(room.as_deref())?;
+            let (room_id, entered) = join_room(db, hub, user, &room, rooms, outbound).await?;
+            // Other connections of the user already announced it in the room
+            if entered {
+                publish_room_message(db, hub, user, (&room, room_id), MESSAGE_JOINED, None).await?;
+            }
+        }
+        ClientMessage::Leave { room } => {
+            let room = room_name(Some(&room))?;
+            let Some(joined) = rooms.remove(&room) else {
+                return Err(format!("You have not joined {}", room));
+            };
+            joined.forwarder.abort();
+            // Other connections of the user keep it in the room
+            if hub.exit_room(&room, user.id) {
+                // The leaving connection no longer receives the room, tell it directly
+                let left =
+                    publish_room_message(db, hub, user, (&room, joined.id), MESSAGE_LEFT, None)
+                        .await?;
+                return Ok(Some(left));
+            }
+        }
+        ClientMessage::Chat { room, message } => {
+            let room = room_name(room.as_deref())?;
+            let Some(room_id) = joined_room(rooms, &room).map(|joined| joined.id) else {
+                return Err(format!("You have not joined {}", room));
+            };
+            let message = check_message(db, hub, user, &message).await?;
+            publish_room_message(db, hub, user, (&room, room_id), MESSAGE_CHAT, Some(message))
+                .await?;
+        }
+        ClientMessage::Resume { last_id } => {
+            // The forwarders restart on new subscriptions that hold live messages until the replay
+            // is done, so ids keep increasing, a message published in between is in the replay
+            let (replayed, _) = watch::channel(None);
+            let mut room_ids = Vec::new();
+            for (room, joined) in rooms.iter_mut() {
+                if joined.forwarder.is_finished() {
+                    continue;
+                }
+                joined.forwarder.abort();
+                room_ids.push(joined.id);
+                let history = RoomHistory {
+                    db: db.clone(),
+                    room_id: joined.id,
+                };
+                joined.forwarder = resume_room(
+                    hub.subscribe(room),
+                    outbound.clone(),
+                    user.id,
+                    Some(history),
+                    replayed.subscribe(),
+                );
+            }
+
+            // The replay fills the queue the connection loop drains, so it runs beside the loop
+            let (db, outbound) = (db.clone(), outbound.clone());
+            tokio::spawn(async move {
+                let replayed_id = match replay(&db, &room_ids, last_id, &outbound).await {
+                    Ok(replayed_id) => replayed_id,
+                    Err(message) => {
+                        let _ = outbound.reply(ServerMessage::Error { message }).await;
+                        last_id
+                    }
+                };
+                let _ = replayed.send(Some(replayed_id));
+            });
+        }
+        ClientMessage::Typing { room } => {
+            let room = room_name(room.as_deref())?;
+            if joined_room(rooms, &room).is_none() {
+                return Err(format!("You have not joined {}", room));
+            }
+            // A muted user can not send anything, so its typing notices are dropped
+            if !user.is_muted(Utc::now()) && typing_allowed(typing, &room, Instant::now()) {
+                hub.publish(
+                    &room,
+                    ServerMessage::Typing {
+                        room: room.clone(),
+                        user_id: user.id,
+                        username: user.name.clone(),
+                    },
+                );
+            }
+        }
+        ClientMessage::DirectMessage {
+            to_user_id,
+            message,
+        } => {
+            let message = check_message(db, hub, user, &message).await?;
+            send_direct_message(db, hub, user, to_user_id, message).await?;
+        }
+        ClientMessage::MarkRead {
+            from_user_id,
+            last_id,
+        } => {
+            read_direct_messages(db, hub, user, from_user_id, last_id).await?;
+        }
+        ClientMessage::Edit { id, message } => {
+            return change_room_message(db, hub, user, rooms, id, Some(message)).await;
+        }
+        ClientMessage::Delete { id } => {
+            return change_room_message(db, hub, user, rooms, id, None).await;
+        }
+        ClientMessage::Mute {
+            user_id,
+            seconds,
+            reason,
+        } => {
+            let muted = sanction_user(db, hub, user, user_id, SANCTION_MUTE, seconds, reason);
+            return Ok(Some(muted.await?));
+        }
+        ClientMessage::Unmute { user_id } => {
+            return Ok(Some(
+                lift_user_sanction(db, hub, user, user_id, SANCTION_MUTE).await?,
+            ));
+        }
+        ClientMessage::Ban {
+            user_id,
+            seconds,
+            reason,
+        } => {
+            let banned = sanction_user(db, hub, user, user_id, SANCTION_BAN, seconds, reason);
+            return Ok(Some(banned.await?));
+        }
+        ClientMessage::Unban { user_id } => {
+            return Ok(Some(
+                lift_user_sanction(db, hub, user, user_id, SANCTION_BAN).await?,
+            ));
+        }
+    }
+
+    Ok(None)
+}
+
+// function for checking a message a user sends against the chat rules: mutes and bans, the maximum
+// length, the rate limit shared by the user's connections and the content filters,
+// returns the message as the filters left it
+async fn check_message(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    message: &str,
+) -> Result<String, String> {
+    // The notice of a new sanction may never reach this connection, so the stored ones decide
+    match active_sanctions(db, user.id).await {
+        Ok(sanctions) => sanction_refusal(&sanctions)?,
+        Err(e) => {
+            println!("Sanction Lookup Error: {:?}", e);
+            return Err("Internal server error".to_string());
+        }
+    }
+
+    let rules = hub.rules();
+    if message.chars().count() > rules.max_length {
+        return Err(format!(
+            "Message is longer than {} characters",
+            rules.max_length
+        ));
+    }
+    if let Err(wait) = hub.take_message(user.id, rules.rate_limit, Instant::now()) {
+        return Err(format!(
+            "Too many messages, try again in {} seconds",
+            wait.as_secs() + 1
+        ));
+    }
+
+    filter_message(&rules.filters, message)
+}
+
+// function for changing a room message or deleting it when there is no new text,
+// the room gets the change, a connection that has not joined the room gets it as the answer
+async fn change_room_message(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    rooms: &HashMap<String, JoinedRoom>,
+    id: i64,
+    message: Option<String>,
+) -> Result<Option<ServerMessage>, String> {
+    let stored = match find_room_message(db, id).await {
+        Ok(Some(stored)) if stored.kind == MESSAGE_CHAT => stored,
+        Ok(_) => return Err(format!("Message {} not found", id)),
+        Err(e) => {
+            println!("Chat History Error: {:?}", e);
+            return Err("Internal server error".to_string());
+        }
+    };
+    if !may_change_message(user.id, user.moderator, stored.user_id) {
+        return Err("Only the author or a moderator can change this message".to_string());
+    }
+    // An author removed from a private room can not change what it wrote there anymore
+    if !user.moderator {
+        match room_access(db, &stored.room, user.id).await {
+            Ok(RoomAccess::Allowed(_)) => {}
+            Ok(_) => return Err(format!("You are not a member of {}", stored.room)),
+            Err(e) => {
+                println!("Room Access Error: {:?}", e);
+                return Err("Internal server error".to_string());
+            }
+        }
+    }
+
+    let time = Utc::now();
+    let (changed, notice) = match message {
+        Some(message) => {
+            if message.trim().is_empty() {
+                return Err("Message can not be empty".to_string());
+            }
+            let message = check_message(db, hub, user, &message).await?;
+            let changed = edit_room_message(db, id, &message, time).await;
+            let notice = ServerMessage::MessageEdited {
+                id,
+                room: stored.room.clone(),
+                user_id: stored.user_id,
+                message,
+                edited_by: user.id,
+                time,
+            };
+            (changed, notice)
+        }
+        None => {
+            let changed = delete_room_message(db, id, user.id, time).await;
+            let notice = ServerMessage::MessageDeleted {
+                id,
+                room: stored.room.clone(),
+                deleted_by: user.id,
+                time,
+            };
+            (changed, notice)
+        }
+    };
+    if let Err(e) = changed {
+        println!("Chat History Error: {:?}", e);
+        return Err("Failed to store message".to_string());
+    }
+
+    hub.publish(&stored.room, notice.clone());
+    Ok(joined_room(rooms, &stored.room).is_none().then_some(notice))
+}
+
+// function for muting or banning a user, only moderators may do this and they can not sanction
+// each other, the connections of the user get the notice through its own topic on every instance
+async fn sanction_user(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    user_id: i64,
+    kind: &str,
+    seconds: Option<u64>,
+    reason: Option<String>,
+) -> Result<ServerMessage, String> {
+    if !user.moderator {
+        return Err("Only moderators can mute or ban users".to_string());
+    }
+    check_sanction_reason(reason.as_deref())?;
+    match find_user_role(db, user_id).await {
+        Ok(Some(role)) if is_moderator_role(&role) => {
+            return Err("Moderators can not be muted or banned".to_string());
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(format!("User {} not found", user_id)),
+        Err(e) => {
+            println!("User Lookup Error: {:?}", e);
+            return Err("Internal server error".to_string());
+        }
+    }
+
+    let until = sanction_expiry(Utc::now(), seconds);
+    if let Err(e) = store_sanction(db, user_id, kind, reason.as_deref(), user.id, until).await {
+        println!("Sanction Error: {:?}", e);
+        return Err("Failed to store sanction".to_string());
+    }
+
+    let notice = if kind == SANCTION_BAN {
+        ServerMessage::UserBanned {
+            user_id,
+            until,
+            reason,
+        }
+    } else {
+        ServerMessage::UserMuted {
+            user_id,
+            until,
+            reason,
+        }
+    };
+    hub.publish(&user_topic(user_id), notice.clone());
+
+    Ok(notice)
+}
+
+// function for lifting the mute or ban of a user before it runs out, only moderators may do this
+async fn lift_user_sanction(
+    db: &MySqlPool,
+    hub: &Hub,
+    user: &ChatUser,
+    user_id: i64,
+    kind: &str,
+) -> Result<ServerMessage, String> {
+    if !user.moderator {
+        return Err("Only moderators can lift a mute or ban".to_string());
+    }
+    match lift_sanction(db, user_id, kind).await {
+        Ok(true) => {}
+        Ok(false) if kind == SANCTION_BAN => return Err(format!("User {} is not banned", user_id)),
+        Ok(false) => return Err(format!("User {} is not muted", user_id)),
+        Err(e) => {
+            println!("Sanction Error: {:?}", e);
+            return Err("Failed to lift sanction".to_string());
+        }
+    }
+
+    // A banned user has no connections to tell
+    if kind == SANCTION_BAN {
+        return Ok(ServerMessage::UserUnbanned { user_id });
+    }
+    let notice = ServerMessage::UserUnmuted { user_id };
+    hub.publish(&user_topic(user_id), notice.clone());
+
+    Ok(notice)
+}
+
+// function for resolving the room a client message addresses
+fn room_name(room: Option<&str>) -> Result<String, String> {
+    match room {
+        None => Ok(DEFAULT_ROOM.to_string()),
+        Some(room) => normalize_room_name(room).ok_or_else(|| format!("Invalid room {}", room)),
+    }
+}
+
+// function for writing a message to the socket, false once the client is gone
+async fn write_message(
+    sender: &mut SplitSink<WebSocket, Message>,
+    message: &ServerMessage,
+) -> bool {
+    match serde_json::to_string(message) {
+        Ok(json) => sender.send(Message::Text(json.into())).await.is_ok(),
+        Err(e) => {
+            println!("JSON serialize error: {:?}", e);
+            true
+        }
+    }
+}
+
+// function for subscribing a connection to what every transport receives besides rooms:
+// the online list, the user's own topic, admin events and the direct messages stored while offline
+async fn open_feed(
+    db: &MySqlPool,
+    hub: &Arc<Hub>,
+    user: &ChatUser,
+    outbound: &Outbound,
+) -> Vec<JoinHandle<()>> {
+    // A connection that misses a snapshot is brought up to date by the next one
+    let mut feed = vec![forward_room(
+        hub.subscribe(PRESENCE_TOPIC),
+        outbound.clone(),
+        user.id,
+        None,
+    )];
+    // The first connection of a user announces it to everyone
+    if hub.connect(user.id, &user.name) {
+        hub.publish_presence();
+    } else {
+        let _ = outbound
+            .reply(ServerMessage::Presence {
+                online: hub.online_users(),
+            })
+            .await;
+    }
+
+    // Direct messages and events reach every connection of the user, stored ones are sent beside
+    feed.push(forward_room(
+        hub.subscribe(&user_topic(user.id)),
+        outbound.clone(),
+        user.id,
+        None,
+    ));
+    feed.push(acknowledge_direct(
+        db.clone(),
+        Arc::clone(hub),
+        hub.subscribe(&user_topic(user.id)),
+        user.id,
+    ));
+    {
+        let (db, hub, outbound) = (db.clone(), Arc::clone(hub), outbound.clone());
+        let user_id = user.id;
+        tokio::spawn(async move {
+            if let Err(message) = deliver_pending(&db, &hub, user_id, &outbound).await {
+                let _ = outbound.reply(ServerMessage::Error { message }).await;
+            }
+        });
+    }
+
+    // Admins follow the changes of every user
+    if user.admin {
+        feed.push(forward_room(
+            hub.subscribe(ADMIN_TOPIC),
+            outbound.clone(),
+            user.id,
+            None,
+        ));
+    }
+
+    feed
+}
+
+// function for ending the feed of a closed connection, the last one takes the user offline
+fn close_feed(hub: &Hub, user_id: i64, feed: &[JoinHandle<()>]) {
+    for forwarder in feed {
+        forwarder.abort();
+    }
+    if hub.disconnect(user_id) {
+        hub.publish_presence();
+    }
+}
+
+// function for checking a message tells a connection its user was deleted or banned,
+// a deleted or banned user keeps a valid token until it expires, its connections end now
+fn closes_connection(message: &ServerMessage, user_id: i64) -> bool {
+    match message {
+        ServerMessage::UserDeleted {
+            user_id: closed, ..
+        }
+        | ServerMessage::UserBanned {
+            user_id: closed, ..
+        } => *closed == user_id,
+        _ => false,
+    }
+}
+
+// function for keeping the mute of a connection's user up to date
+fn apply_mute(user: &mut ChatUser, message: &ServerMessage) {
+    match message {
+        ServerMessage::UserMuted { user_id, until, .. } if *user_id == user.id => {
+            user.muted_until = Some(until.unwrap_or(DateTime::<Utc>::MAX_UTC));
+        }
+        ServerMessage::UserUnmuted { user_id } if *user_id == user.id => {
+            user.muted_until = None;
+        }
+        _ => {}
+    }
+}
+
+pub async fn handle_socket(socket: WebSocket, db: MySqlPool, hub: Arc<Hub>, mut user: ChatUser) {
+    println!("Client connected: {} ({})", user.name, user.id);
+
+    let (mut sender, mut receiver) = socket.split();
+    // Every joined room forwards into this queue, the loop below writes it to the socket
+    let (outbound, mut queue) = Outbound::new(outbound_buffer(), slow_consumer_policy());
+    let mut rooms: HashMap<String, JoinedRoom> = HashMap::new();
+    let mut typing: HashMap<String, Instant> = HashMap::new();
+
+    let feed = open_feed(&db, &hub, &user, &outbound).await;
+
+    // Every connection listens to the general room like before rooms existed
+    match join_room(&db, &hub, &user, DEFAULT_ROOM, &mut rooms, &outbound).await {
+        Ok((room_id, true)) => {
+            let joined = (DEFAULT_ROOM, room_id);
+            let announced =
+                publish_room_message(&db, &hub, &user, joined, MESSAGE_JOINED, None).await;
+            if let Err(e) = announced {
+                println!(
+                    "Failed to announce {} joining {}: {}",
+                    user.name, DEFAULT_ROOM, e
+                );
+            }
+        }
+        Ok(_) => {}
+        Err(e) => println!("Failed to join {}: {}", DEFAULT_ROOM, e),
+    }
+
+    // The client answers pings with pongs, a connection silent for too long is dead
+    let (ping_every, idle_after) = (ping_interval(), idle_timeout());
+    let mut heartbeat = interval_at(tokio::time::Instant::now() + ping_every, ping_every);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_seen = Instant::now();
+
+    loop {
+        tokio::select! {
+
+            // Receive room messages → send to client
+            Some(msg) = queue.recv() => {
+                if !write_message(&mut sender, &msg).await {
+                    break;
+                }
+
+                if closes_connection(&msg, user.id) {
+                    println!("Closing connection of {}", user.name);
+                    break;
+                }
+                apply_mute(&mut user, &msg);
+            }
+
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() >= idle_after {
+                    println!("Closing idle connection of {}", user.name);
+                    break;
+                }
+                if sender.send(Message::Ping(Default::default())).await.is_err() {
+                    break;
+                }
+            }
+
+            _ = outbound.too_slow() => {
+                println!("Closing slow connection of {}", user.name);
+                let frame = CloseFrame {
+                    code: close_code::POLICY,
+                    reason: "Too slow".into(),
+                };
+                let _ = sender.send(Message::Close(Some(frame))).await;
+                break;
+            }
+
+            // Receive client messages
+            result = receiver.next() => {
+                if let Some(Ok(_)) = &result {
+                    last_seen = Instant::now();
+                }
+                match result {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<ClientMessage>(&text) {
+                            Ok(msg) => {
+                                let handled = handle_client_message(
+                                    msg, &db, &hub, &user, &mut rooms, &mut typing, &outbound,
+                                )
+                                .await;
+                                let answer = match handled {
+                                    Ok(answer) => answer,
+                                    Err(message) => Some(ServerMessage::Error { message }),
+                                };
+                                let written = match answer {
+                                    Some(answer) => write_message(&mut sender, &answer).await,
+                                    None => true,
+                                };
+                                if !written {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                println!("JSON parse error: {:?}", e);
+                            }
+                        }
+                    }
+
+                    Some(Ok(Message::Close(_))) => {
+                        println!("Client disconnected");
+                        break;
+                    }
+
+                    Some(Ok(_)) => {}
+
+                    Some(Err(e)) => {
+                        println!("WebSocket error: {:?}", e);
+                        break;
+                    }
+
+                    None => break,
+                }
+            }
+        }
+    }
+
+    // Stopping the forwarders drops their subscriptions, empty rooms are released
+    for (room, joined) in &rooms {
+        joined.forwarder.abort();
+
+        // Other connections of the user keep it in the room
+        if hub.exit_room(room, user.id) {
+            let left =
+                publish_room_message(&db, &hub, &user, (room, joined.id), MESSAGE_LEFT, None).await;
+            if let Err(e) = left {
+                println!("Failed to announce {} leaving {}: {}", user.name, room, e);
+            }
+        }
+    }
+    close_feed(&hub, user.id, &feed);
+
+    println!("Connection fully closed");
+}
+
+// function for reading the id of the last event a reconnecting client got,
+// the header an EventSource sends wins over the query parameter
+pub fn last_event_id(headers: &HeaderMap, query: Option<i64>) -> Option<i64> {
+    headers
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .or(query)
+        .filter(|id| *id > 0)
+}
+
+// function for resolving the comma separated rooms an event stream follows
+pub fn event_rooms(rooms: Option<&str>) -> Result<Vec<String>, String> {
+    let Some(rooms) = rooms.filter(|rooms| !rooms.trim().is_empty()) else {
+        return Ok(vec![DEFAULT_ROOM.to_string()]);
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for room in rooms.split(',') {
+        let name = room_name(Some(room))?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+// function for turning a message into an event,
+// room messages carry their history id so a reconnecting client resumes after it
+pub fn sse_event(message: &ServerMessage) -> Result<SseEvent, axum::Error> {
+    let event = SseEvent::default().json_data(message)?;
+
+    Ok(match history_id(message) {
+        Some(id) => event.id(id.to_string()),
+        None => event,
+    })
+}
+
+// feed of one event stream, released once the client goes away and the stream is dropped
+struct EventFeed {
+    hub: Arc<Hub>,
+    user_id: i64,
+    queue: mpsc::Receiver<ServerMessage>,
+    outbound: Outbound,
+    forwarders: Vec<JoinHandle<()>>,
+    ended: bool,
+}
+
+impl Drop for EventFeed {
+    fn drop(&mut self) {
+        close_feed(&self.hub, self.user_id, &self.forwarders);
+    }
+}
+
+// function for waiting for the next event of a stream, None ends it
+async fn next_event(mut feed: EventFeed) -> Option<(Result<SseEvent, axum::Error>, EventFeed)> {
+    if feed.ended {
+        return None;
+    }
+
+    let message = tokio::select! {
+        message = feed.queue.recv() => message?,
+        _ = feed.outbound.too_slow() => {
+            println!("Closing slow event stream of user {}", feed.user_id);
+            return None;
+        }
+    };
+    feed.ended = closes_connection(&message, feed.user_id);
+
+    Some((sse_event(&message), feed))
+}
+
+// function for streaming the messages a WebSocket connection gets as server-sent events,
+// a reconnecting client first gets the room messages after its Last-Event-ID
+pub async fn events(
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+    Extension(db): Extension<MySqlPool>,
+    Extension(hub): Extension<Arc<Hub>>,
+    Extension(claims): Extension<Claims>,
+) -> Response {
+    let user = match find_chat_user(&db, claims.sub).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return reject(StatusCode::UNAUTHORIZED, "User not found"),
+        Err(e) => {
+            println!("User Lookup Error: {:?}", e);
+            return reject(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+        }
+    };
+    if user.is_banned(Utc::now()) {
+        return reject(StatusCode::FORBIDDEN, "You are banned from the chat");
+    }
+
+    let rooms = match event_rooms(query.rooms.as_deref()) {
+        Ok(rooms) => rooms,
+        Err(message) => return reject(StatusCode::UNPROCESSABLE_ENTITY, &message),
+    };
+    let mut room_ids = Vec::with_capacity(rooms.len());
+    for room in &rooms {
+        match room_access(&db, room, user.id).await {
+            Ok(RoomAccess::Allowed(room_id)) => room_ids.push(room_id),
+            Ok(RoomAccess::NotFound) => {
+                return reject(StatusCode::NOT_FOUND, &format!("Room {} not found", room));
+            }
+            Ok(RoomAccess::Forbidden) => {
+                let message = format!("You are not a member of {}", room);
+                return reject(StatusCode::FORBIDDEN, &message);
+            }
+            Err(e) => {
+                println!("Room Access Error: {:?}", e);
+                return reject(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+            }
+        }
+    }
+
+    println!("Event stream opened: {} ({})", user.name, user.id);
+    let (outbound, queue) = Outbound::new(outbound_buffer(), slow_consumer_policy());
+    let mut forwarders = open_feed(&db, &hub, &user, &outbound).await;
+
+    // The stream only listens, it does not announce itself in the rooms
+    let subscriptions = rooms
+        .iter()
+        .zip(&room_ids)
+        .map(|(room, room_id)| {
+            let history = RoomHistory {
+                db: db.clone(),
+                room_id: *room_id,
+            };
+            (hub.subscribe(room), history)
+        })
+        .collect::<Vec<(Subscription, RoomHistory)>>();
+
+    match last_event_id(&headers, query.last_event_id) {
+        None => {
+            for (subscription, history) in subscriptions {
+                forwarders.push(forward_room(
+                    subscription,
+                    outbound.clone(),
+                    user.id,
+                    Some(history),
+                ));
+            }
+        }
+        // Live messages wait in the subscriptions until the replay is done, so ids keep increasing,
+        // the ones the replay sent are skipped
+        Some(last_id) => {
+            let (db, outbound, user_id) = (db.clone(), outbound.clone(), user.id);
+            forwarders.push(tokio::spawn(async move {
+                let replayed_id = match replay(&db, &room_ids, last_id, &outbound).await {
+                    Ok(replayed_id) => replayed_id,
+                    Err(message) => {
+                        let _ = outbound.reply(ServerMessage::Error { message }).await;
+                        last_id
+                    }
+                };
+                let rooms = subscriptions.into_iter().map(|(subscription, history)| {
+                    forward(
+                        subscription,
+                        outbound.clone(),
+                        user_id,
+                        Some(history),
+                        replayed_id,
+                    )
+                });
+                join_all(rooms).await;
+            }));
+        }
+    }
+
+    let feed = EventFeed {
+        hub,
+        user_id: user.id,
+        queue,
+        outbound,
+        forwarders,
+        ended: false,
+    };
+    Sse::new(stream::unfold(feed, next_event))
+        .keep_alive(
+            KeepAlive::new()
+                .interval(ping_interval())
+                .text(KEEP_ALIVE_TEXT),
+        )
+        .into_response()
+}
@@ -1,10 +1,10 @@
 use axum::{
     Extension, Router,
-    http::{HeaderValue, Method, header},
+    http::{HeaderName, HeaderValue, Method, header},
 };
 use dotenvy::dotenv;
 use std::{env, net::SocketAddr};
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
 mod config;
 mod handlers;
@@ -22,19 +22,56 @@ async fn main() {
     // Connect to database
     let db = config::database::connect().await;
 
+    // Start background cleanup of abandoned uploads
+    utils::upload_janitor::spawn(db.clone());
+
+    // Rooms of the WebSocket chat, shared by the socket and the REST handlers,
+    // the pub/sub transport carries their messages to the other instances
+    let pubsub = config::pubsub::pubsub(&db);
+    println!("Chat pub/sub: {}", pubsub.name());
+    let hub = utils::hub::Hub::with_pubsub(pubsub, config::moderation::chat_rules());
+    let events = utils::events::EventBus::new(hub.clone());
+
     // Cors Configuration
+    let origins = config::cors::allowed_origins();
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| origin.parse::<HeaderValue>().ok()),
+        )
+    };
     let cors = CorsLayer::new()
-        .allow_origin("http://localhost:5173".parse::<HeaderValue>().unwrap())
+        .allow_origin(allow_origin)
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::RANGE,
+            // sent by event stream clients resuming after a reconnect
+            HeaderName::from_static("last-event-id"),
+        ])
+        .expose_headers([
+            header::CONTENT_RANGE,
+            header::ACCEPT_RANGES,
+            header::CONTENT_DISPOSITION,
+        ]);
 
     // Create a base router
     let app = Router::new()
         .merge(routes::auth_routes::auth_routes())
         .merge(routes::user_routes::user_routes())
         .merge(routes::document_routes::document_routes())
+        .merge(routes::folder_routes::folder_routes())
+        .merge(routes::room_routes::room_routes())
+        .merge(routes::direct_message_routes::direct_message_routes())
+        .merge(routes::moderation_routes::moderation_routes())
         .merge(routes::websocket_routes::websocket_routes())
         .layer(Extension(db))
+        .layer(Extension(hub))
+        .layer(Extension(events))
         .layer(cors);
 
     let port = env::var("APP_PORT")
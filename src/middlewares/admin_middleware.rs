@@ -0,0 +1,48 @@
+use crate::utils::jwt::Claims;
+use crate::utils::response::ApiResponse;
+
+use axum::{
+    Extension, Json, extract::Request, http::StatusCode, middleware::Next, response::Response,
+};
+use sqlx::MySqlPool;
+
+// type alias for error response
+type AdminError = (StatusCode, Json<ApiResponse<()>>);
+
+// function for checking the user has the admin role
+pub async fn is_admin(db: &MySqlPool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let user = sqlx::query!(
+        "SELECT role FROM users WHERE id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(user.is_some_and(|user| user.role == "admin"))
+}
+
+// Middleware admin, must run after the auth middleware
+pub async fn admin(
+    Extension(db): Extension<MySqlPool>,
+    Extension(claims): Extension<Claims>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AdminError> {
+    // Get role of the authenticated user
+    let admin = is_admin(&db, claims.sub).await.map_err(|e| {
+        println!("Role Lookup Error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse::<()>::error("Internal server error")),
+        )
+    })?;
+
+    if admin {
+        Ok(next.run(req).await)
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::error("Admin access required")),
+        ))
+    }
+}
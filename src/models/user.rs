@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use serde_json::Value;
 
 #[derive(Serialize)]
 pub struct User {
@@ -8,6 +9,8 @@ pub struct User {
     pub email: String,
     pub password: String,
     pub image: Option<String>,
+    // url of every generated avatar size, keyed by variant name
+    pub avatar_variants: Option<Value>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
@@ -0,0 +1,12 @@
+use crate::{handlers::direct_message_handler, middlewares::auth_middleware::auth};
+use axum::{Router, middleware, routing::get};
+
+pub fn direct_message_routes() -> Router {
+    Router::new()
+        .route("/direct-message", get(direct_message_handler::unread))
+        .route(
+            "/direct-message/{user_id}",
+            get(direct_message_handler::conversation),
+        )
+        .layer(middleware::from_fn(auth))
+}
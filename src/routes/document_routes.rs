@@ -1,15 +1,86 @@
-use crate::{handlers::document_handler, middlewares::auth_middleware::auth};
-use axum::{Router, middleware, routing::post};
+use crate::{
+    config::upload::max_chunk_size,
+    handlers::document_handler,
+    middlewares::{admin_middleware::admin, auth_middleware::auth},
+};
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post, put},
+};
 
 pub fn document_routes() -> Router {
+    let admin_routes = Router::new()
+        .route("/document/uploads", get(document_handler::upload_sessions))
+        .route("/document/dedup-stats", get(document_handler::dedup_stats))
+        .route("/document/quarantine", get(document_handler::quarantine))
+        .route("/document/{id}/scan", post(document_handler::rescan))
+        .route("/document/rescan", post(document_handler::rescan_all))
+        .route("/document/reindex", post(document_handler::reindex))
+        .route(
+            "/document/uploads/cleanup",
+            post(document_handler::cleanup_uploads),
+        )
+        .route_layer(middleware::from_fn(admin));
+
     Router::new()
+        .route("/document", get(document_handler::index))
+        .route("/document/search", get(document_handler::search))
+        .route(
+            "/document/{id}",
+            get(document_handler::show).delete(document_handler::delete),
+        )
+        .route("/document/{id}/rename", put(document_handler::rename))
+        .route("/document/{id}/move", put(document_handler::move_document))
+        .route("/document/{id}/tags", put(document_handler::tags))
+        .route("/document/{id}/metadata", put(document_handler::metadata))
         .route(
             "/document/upload-chunk",
-            post(document_handler::upload_chunk),
+            // leave room for the multipart boundaries and text fields
+            post(document_handler::upload_chunk)
+                .layer(DefaultBodyLimit::max(max_chunk_size() as usize + 64 * 1024)),
         )
         .route(
             "/document/complete-upload",
             post(document_handler::complete_upload),
         )
+        .route(
+            "/document/direct-upload",
+            post(document_handler::create_direct_upload),
+        )
+        .route(
+            "/document/direct-upload/{id}/confirm",
+            post(document_handler::confirm_direct_upload),
+        )
+        .route("/document/quota", get(document_handler::quota))
+        .route(
+            "/document/download-zip",
+            post(document_handler::download_zip),
+        )
+        .route("/document/{id}/download", get(document_handler::download))
+        .route("/document/{id}/thumbnail", get(document_handler::thumbnail))
+        .route(
+            "/document/{id}/versions",
+            get(document_handler::list_versions),
+        )
+        .route(
+            "/document/{id}/versions/{version}/download",
+            get(document_handler::download_version),
+        )
+        .route(
+            "/document/{id}/versions/{version}/rollback",
+            post(document_handler::rollback_version),
+        )
+        .merge(admin_routes)
         .layer(middleware::from_fn(auth))
+        // signed download links carry their own credential and skip the auth middleware
+        .route(
+            "/document/{id}/signed",
+            get(document_handler::signed_download),
+        )
+        .route(
+            "/document/{id}/versions/{version}/signed",
+            get(document_handler::signed_version_download),
+        )
 }
@@ -0,0 +1,13 @@
+use crate::{handlers::folder_handler, middlewares::auth_middleware::auth};
+use axum::{
+    Router, middleware,
+    routing::{get, post, put},
+};
+
+pub fn folder_routes() -> Router {
+    Router::new()
+        .route("/folder", post(folder_handler::store))
+        .route("/folder/tree", get(folder_handler::tree))
+        .route("/folder/{id}", put(folder_handler::update))
+        .layer(middleware::from_fn(auth))
+}
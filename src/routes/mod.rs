@@ -1,4 +1,8 @@
 pub mod auth_routes;
+pub mod direct_message_routes;
 pub mod document_routes;
+pub mod folder_routes;
+pub mod moderation_routes;
+pub mod room_routes;
 pub mod user_routes;
-pub mod websocket_routes;
\ No newline at end of file
+pub mod websocket_routes;
@@ -0,0 +1,8 @@
+use crate::{handlers::moderation_handler, middlewares::auth_middleware::auth};
+use axum::{Router, middleware, routing::get};
+
+pub fn moderation_routes() -> Router {
+    Router::new()
+        .route("/moderation/sanctions", get(moderation_handler::sanctions))
+        .layer(middleware::from_fn(auth))
+}
@@ -0,0 +1,20 @@
+use crate::{handlers::room_handler, middlewares::auth_middleware::auth};
+use axum::{
+    Router, middleware,
+    routing::{delete, get},
+};
+
+pub fn room_routes() -> Router {
+    Router::new()
+        .route("/room", get(room_handler::index).post(room_handler::store))
+        .route(
+            "/room/{id}/members",
+            get(room_handler::members).post(room_handler::add_member),
+        )
+        .route("/room/{id}/messages", get(room_handler::messages))
+        .route(
+            "/room/{id}/members/{user_id}",
+            delete(room_handler::remove_member),
+        )
+        .layer(middleware::from_fn(auth))
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::schemas::document_schema::Pagination;
+
+// message between two users, kept until both could read it
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DirectMessage {
+    pub id: i64,
+    pub from_user_id: i64,
+    pub from_username: String,
+    pub to_user_id: i64,
+    pub message: String,
+    // set once a connection of the recipient received it
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirectHistoryResponse {
+    pub data: Vec<DirectMessage>,
+    pub pagination: Pagination,
+}
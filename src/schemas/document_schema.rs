@@ -1,16 +1,35 @@
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use serde_json::{Map, Value};
+use sqlx::FromRow;
+use validator::{Validate, ValidationError};
 
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, Utc};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Document {
     pub id: i64,
     pub name: String,
+    // storage url, only ever served through the download endpoints
+    #[serde(skip_serializing)]
     pub file_id: String,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
-    pub deleted_at: Option<NaiveDateTime>,
+    pub content_hash: Option<String>,
+    pub user_id: Option<i64>,
+    pub size: i64,
+    pub mime_type: String,
+    // pending, clean or infected, only clean documents can be downloaded
+    pub scan_status: String,
+    pub current_version: i32,
+    pub description: Option<String>,
+    pub metadata: Option<Value>,
+    pub folder_id: Option<i64>,
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
+    // served by this app, set when the current version has a thumbnail
+    #[sqlx(skip)]
+    pub thumbnail_url: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +60,61 @@ pub struct DocumentQuery {
     pub page: Option<i64>,
     pub limit: Option<i64>,
     pub keyword: Option<String>,
+    pub tag: Option<String>,
+    pub folder_id: Option<i64>,
+    // exact type or a wildcard such as image/*
+    pub mime_type: Option<String>,
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SearchQuery {
+    #[serde(default)]
+    #[validate(length(min = 1, max = 255, message = "Kata kunci wajib diisi"))]
+    pub q: String,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DocumentSearchResult {
+    pub id: i64,
+    pub name: String,
+    pub user_id: Option<i64>,
+    pub size: i64,
+    pub mime_type: String,
+    pub scan_status: String,
+    pub current_version: i32,
+    pub folder_id: Option<i64>,
+    // relevance, names weigh more than content
+    pub score: f64,
+    // extracted text of the current version, only used to build the snippet
+    #[serde(skip_serializing)]
+    pub content: Option<String>,
+    // HTML escaped with matched words wrapped in <mark>
+    #[sqlx(skip)]
+    pub highlighted_name: String,
+    #[sqlx(skip)]
+    pub snippet: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocumentSearchResponse {
+    pub data: Vec<DocumentSearchResult>,
+    pub pagination: Pagination,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ZipDownloadRequest {
+    // explicit documents, combined with the filters when both are given
+    #[serde(default)]
+    #[validate(length(max = 1000, message = "Maksimal 1000 dokumen"))]
+    pub ids: Vec<i64>,
+    pub folder_id: Option<i64>,
+    pub tag: Option<String>,
 }
 
 #[derive(Deserialize, Validate)]
@@ -50,4 +124,130 @@ pub struct CompletePayload {
     #[validate(length(min = 1, max = 255, message = "Nama wajib diisi"))]
     pub name: String,
     pub extention: String,
+    #[validate(length(equal = 64, message = "Checksum SHA-256 wajib diisi"))]
+    pub checksum: String,
+    // append a new version to this document instead of creating a new one
+    pub document_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DirectUploadRequest {
+    #[validate(length(min = 1, max = 255, message = "Nama wajib diisi"))]
+    pub name: String,
+    #[validate(length(min = 1, max = 20, message = "Ekstensi wajib diisi"))]
+    pub extention: String,
+    #[validate(range(min = 1, message = "Ukuran file wajib diisi"))]
+    pub size: u64,
+    #[validate(length(min = 1, max = 127, message = "Tipe file wajib diisi"))]
+    pub mime_type: String,
+    #[validate(length(equal = 64, message = "Checksum SHA-256 wajib diisi"))]
+    pub checksum: String,
+    // append a new version to this document instead of creating a new one
+    pub document_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DirectUploadConfirm {
+    // ETags of a multipart S3 upload, empty for single requests
+    #[serde(default)]
+    pub parts: Vec<UploadedPart>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    // redirect to a signed url instead of streaming the file directly
+    pub redirect: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RescanQuery {
+    // continue after this version id, taken from `next` of the previous batch
+    pub after: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedDownloadQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocumentVersionResponse {
+    pub version: i32,
+    pub size: i64,
+    pub content_hash: Option<String>,
+    pub mime_type: String,
+    pub scan_status: String,
+    pub uploaded_by: Option<i64>,
+    pub is_current: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RenameRequest {
+    #[validate(length(min = 1, max = 255, message = "Nama wajib diisi"))]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveRequest {
+    // null moves the document to the root
+    pub folder_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TagsRequest {
+    #[validate(
+        length(max = 20, message = "Maksimal 20 tag"),
+        custom(function = "validate_tags")
+    )]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MetadataRequest {
+    #[validate(length(max = 5000, message = "Deskripsi maksimal 5000 karakter"))]
+    pub description: Option<String>,
+    #[validate(custom(function = "validate_metadata"))]
+    pub metadata: Option<Map<String, Value>>,
+}
+
+// function for validating tag names, each tag is 1 to 64 characters once trimmed
+fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags
+        .iter()
+        .any(|tag| tag.trim().is_empty() || tag.trim().chars().count() > 64)
+    {
+        return Err(
+            ValidationError::new("tags").with_message("Tag harus 1 sampai 64 karakter".into())
+        );
+    }
+
+    Ok(())
+}
+
+// function for validating metadata, at most 50 keys and 16 KiB once serialized
+fn validate_metadata(metadata: &Map<String, Value>) -> Result<(), ValidationError> {
+    if metadata.len() > 50 {
+        return Err(
+            ValidationError::new("metadata").with_message("Metadata maksimal 50 key".into())
+        );
+    }
+    if metadata.keys().any(|key| key.is_empty() || key.len() > 64) {
+        return Err(ValidationError::new("metadata")
+            .with_message("Key metadata harus 1 sampai 64 karakter".into()));
+    }
+    if serde_json::to_vec(metadata).map_or(0, |bytes| bytes.len()) > 16 * 1024 {
+        return Err(
+            ValidationError::new("metadata").with_message("Metadata maksimal 16 KiB".into())
+        );
+    }
+
+    Ok(())
 }
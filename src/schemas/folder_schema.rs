@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct FolderRequest {
+    #[validate(length(min = 1, max = 255, message = "Nama wajib diisi"))]
+    pub name: String,
+    // null places the folder at the root
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderNode {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub document_count: i64,
+    pub children: Vec<FolderNode>,
+}
@@ -1,18 +1,239 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Deserialize)]
+pub struct WebSocketQuery {
+    // JWT for clients that can not set headers on the upgrade request
+    pub token: Option<String>,
+}
+
+// query parameters of the event stream
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    // comma separated rooms to follow, the general room when missing
+    pub rooms: Option<String>,
+    // resume point for clients that can not set the Last-Event-ID header
+    pub last_event_id: Option<i64>,
+}
 
+// messages without a room go to the general room
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
-    Join { username: String },
-    Chat { message: String },
+    // the name comes from the authenticated user, a username sent by older clients is ignored
+    Join {
+        #[serde(default)]
+        room: Option<String>,
+    },
+    Leave {
+        room: String,
+    },
+    Chat {
+        #[serde(default)]
+        room: Option<String>,
+        message: String,
+    },
+    // replay the messages of the joined rooms that came after last_id
+    Resume {
+        last_id: i64,
+    },
+    // sent while the user types, the server drops repeats within a short interval
+    Typing {
+        #[serde(default)]
+        room: Option<String>,
+    },
+    // sent to the connections of one user only, stored until they come online
+    DirectMessage {
+        to_user_id: i64,
+        message: String,
+    },
+    // the direct messages from a user were read up to last_id
+    MarkRead {
+        from_user_id: i64,
+        last_id: i64,
+    },
+    // only the author of a room message or a moderator may change or remove it
+    Edit {
+        id: i64,
+        message: String,
+    },
+    Delete {
+        id: i64,
+    },
+    // moderator commands, without seconds the mute or ban lasts until it is lifted
+    Mute {
+        user_id: i64,
+        #[serde(default)]
+        seconds: Option<u64>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Unmute {
+        user_id: i64,
+    },
+    Ban {
+        user_id: i64,
+        #[serde(default)]
+        seconds: Option<u64>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Unban {
+        user_id: i64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnlineUser {
+    pub user_id: i64,
+    pub username: String,
+    // open connections of the user on every server
+    pub connections: usize,
+    pub online_since: DateTime<Utc>,
 }
 
+// unread direct messages from one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnreadCount {
+    pub user_id: i64,
+    pub username: String,
+    pub count: i64,
+    pub last_id: i64,
+}
+
+// room messages carry the id of their history entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
-    UserJoined { username: String, time: Option<DateTime<Utc>> },
-    Chat { username: String, message: String, time: Option<DateTime<Utc>> },
+    UserJoined {
+        id: i64,
+        room: String,
+        user_id: i64,
+        username: String,
+        time: Option<DateTime<Utc>>,
+    },
+    UserLeft {
+        id: i64,
+        room: String,
+        user_id: i64,
+        username: String,
+        time: Option<DateTime<Utc>>,
+    },
+    Chat {
+        id: i64,
+        room: String,
+        user_id: i64,
+        username: String,
+        message: String,
+        time: Option<DateTime<Utc>>,
+    },
+    // not stored, clients show it for a few seconds
+    Typing {
+        room: String,
+        user_id: i64,
+        username: String,
+    },
+    // everyone online, sent on connect and whenever a user comes online or goes offline
+    Presence {
+        online: Vec<OnlineUser>,
+    },
+    // sent to the connections of both users, the sender learns the id of its message
+    DirectMessage {
+        id: i64,
+        from_user_id: i64,
+        from_username: String,
+        to_user_id: i64,
+        message: String,
+        time: Option<DateTime<Utc>>,
+    },
+    // direct messages of the sender reached a connection of to_user_id
+    DirectDelivered {
+        to_user_id: i64,
+        ids: Vec<i64>,
+    },
+    // user_id read the direct messages of the sender up to last_id
+    DirectRead {
+        user_id: i64,
+        last_id: i64,
+    },
+    // sent on connect and after messages were marked read, new messages are counted by the client
+    Unread {
+        counts: Vec<UnreadCount>,
+    },
+    // a document upload of the user was stored, the malware scan may still be pending
+    DocumentUploaded {
+        document_id: i64,
+        version: i32,
+        name: String,
+        size: i64,
+        mime_type: String,
+        scan_status: String,
+        time: DateTime<Utc>,
+    },
+    // sent to the user and to admins
+    UserUpdated {
+        user_id: i64,
+        name: String,
+        email: String,
+        time: DateTime<Utc>,
+    },
+    // sent to the user and to admins, the connections of the deleted user are closed after it
+    UserDeleted {
+        user_id: i64,
+        time: DateTime<Utc>,
+    },
+    // a room message was changed by its author or a moderator, id is that of its history entry
+    MessageEdited {
+        id: i64,
+        room: String,
+        user_id: i64,
+        message: String,
+        edited_by: i64,
+        time: DateTime<Utc>,
+    },
+    MessageDeleted {
+        id: i64,
+        room: String,
+        deleted_by: i64,
+        time: DateTime<Utc>,
+    },
+    // sent to the user and to the moderator, until is missing while the mute lasts until lifted
+    UserMuted {
+        user_id: i64,
+        until: Option<DateTime<Utc>>,
+        reason: Option<String>,
+    },
+    UserUnmuted {
+        user_id: i64,
+    },
+    // sent to the user and to the moderator, the connections of the banned user are closed after it
+    UserBanned {
+        user_id: i64,
+        until: Option<DateTime<Utc>>,
+        reason: Option<String>,
+    },
+    // only sent to the moderator, the user has no connections while banned
+    UserUnbanned {
+        user_id: i64,
+    },
+    // the user lost access to a private room, its connections stop receiving the room
+    MemberRemoved {
+        room: String,
+        user_id: i64,
+    },
+    // the connection missed skipped messages of a room or topic, for rooms the missed
+    // messages are replayed after it and a Resumed follows
+    Lagged {
+        room: String,
+        skipped: u64,
+    },
+    // sent after a replay, when complete is false the client pages the rest from the history
+    Resumed {
+        last_id: i64,
+        complete: bool,
+    },
+    // a request of this connection failed, only sent to the connection itself
+    Error {
+        message: String,
+    },
 }
-
@@ -1,5 +1,10 @@
+pub mod direct_message_schema;
 pub mod document_schema;
+pub mod folder_schema;
 pub mod login_schema;
+pub mod message_schema;
+pub mod moderation_schema;
 pub mod register_schema;
+pub mod room_schema;
+pub mod upload_schema;
 pub mod user_schema;
-pub mod message_schema;
@@ -0,0 +1,16 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+// mute or ban of a user, it ends at expires_at or when a moderator lifts it
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ChatSanction {
+    pub user_id: i64,
+    pub username: String,
+    // mute or ban
+    pub kind: String,
+    pub reason: Option<String>,
+    pub created_by: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::{Validate, ValidationError};
+
+use crate::schemas::document_schema::Pagination;
+use crate::utils::hub::normalize_room_name;
+
+#[derive(Debug, Serialize)]
+pub struct Room {
+    pub id: i64,
+    pub name: String,
+    // private rooms can only be joined by their members
+    pub is_private: bool,
+    pub created_by: Option<i64>,
+    pub member_count: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RoomRequest {
+    #[validate(custom(function = "validate_room_name"))]
+    pub name: String,
+    #[serde(default)]
+    pub is_private: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoomMemberRequest {
+    pub user_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomMember {
+    pub user_id: i64,
+    pub name: String,
+    // owner or member, owners manage the members of a private room
+    pub role: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// message stored in the history of a room, joins and leaves are kept as well
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ChatMessage {
+    // increases with every message, clients resume after the last id they saw
+    pub id: i64,
+    pub room: String,
+    pub user_id: i64,
+    pub username: String,
+    // chat, joined or left
+    pub kind: String,
+    pub message: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    // set once the author or a moderator changed the message, deleted messages are left out
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    // only messages newer than this id
+    pub after_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatHistoryResponse {
+    pub data: Vec<ChatMessage>,
+    pub pagination: Pagination,
+}
+
+// function for validating room names, they are used to address rooms over the WebSocket
+fn validate_room_name(name: &str) -> Result<(), ValidationError> {
+    if normalize_room_name(name).is_none() {
+        return Err(ValidationError::new("name")
+            .with_message("Nama room harus 1 sampai 64 huruf kecil, angka, - atau _".into()));
+    }
+
+    Ok(())
+}
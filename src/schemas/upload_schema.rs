@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadSessionResponse {
+    pub id: String,
+    pub user_id: i64,
+    pub chunk_count: i32,
+    pub received_bytes: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JanitorReport {
+    pub expired_sessions: u64,
+    pub orphaned_chunk_dirs: u64,
+    pub orphaned_merged_files: u64,
+    pub expired_direct_uploads: u64,
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuotaResponse {
+    pub quota: u64,
+    pub used: u64,
+    pub in_flight: u64,
+    pub remaining: u64,
+    pub max_file_size: u64,
+    pub max_chunk_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DedupStats {
+    pub blobs: i64,
+    pub references: i64,
+    pub stored_bytes: i64,
+    pub logical_bytes: i64,
+    pub saved_bytes: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuarantinedFileResponse {
+    pub id: i64,
+    pub document_id: Option<i64>,
+    pub version: Option<i32>,
+    pub content_hash: String,
+    pub size: i64,
+    pub signature: String,
+    pub scanner: String,
+    pub uploaded_by: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+}
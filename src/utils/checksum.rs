@@ -0,0 +1,16 @@
+use sha2::{Digest, Sha256};
+
+// function for hashing bytes with sha256 (hex encoded)
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+// function for checking a client supplied checksum against the computed one
+pub fn checksum_matches(expected: &str, actual: &str) -> bool {
+    expected.trim().eq_ignore_ascii_case(actual)
+}
+
+// function for checking a checksum is a sha256 hex string
+pub fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
@@ -0,0 +1,96 @@
+use crate::schemas::message_schema::ServerMessage;
+use crate::utils::hub::{ADMIN_TOPIC, Hub, user_topic};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+// change a handler announces to the WebSocket clients allowed to see it
+#[derive(Debug, Clone)]
+pub enum Event {
+    // a document or a new version of it was stored, user_id is the uploader
+    DocumentUploaded {
+        document_id: i64,
+        version: i32,
+        user_id: i64,
+        name: String,
+        size: i64,
+        mime_type: String,
+        scan_status: String,
+    },
+    UserUpdated {
+        user_id: i64,
+        name: String,
+        email: String,
+    },
+    UserDeleted {
+        user_id: i64,
+    },
+}
+
+impl Event {
+    // function for listing the topics whose subscribers may see the event,
+    // documents only reach their uploader, user changes the user and the admins
+    pub fn topics(&self) -> Vec<String> {
+        match self {
+            Event::DocumentUploaded { user_id, .. } => vec![user_topic(*user_id)],
+            Event::UserUpdated { user_id, .. } | Event::UserDeleted { user_id } => {
+                vec![user_topic(*user_id), ADMIN_TOPIC.to_string()]
+            }
+        }
+    }
+
+    // function for turning the event into the message clients receive
+    pub fn message(self, time: DateTime<Utc>) -> ServerMessage {
+        match self {
+            Event::DocumentUploaded {
+                document_id,
+                version,
+                user_id: _,
+                name,
+                size,
+                mime_type,
+                scan_status,
+            } => ServerMessage::DocumentUploaded {
+                document_id,
+                version,
+                name,
+                size,
+                mime_type,
+                scan_status,
+                time,
+            },
+            Event::UserUpdated {
+                user_id,
+                name,
+                email,
+            } => ServerMessage::UserUpdated {
+                user_id,
+                name,
+                email,
+                time,
+            },
+            Event::UserDeleted { user_id } => ServerMessage::UserDeleted { user_id, time },
+        }
+    }
+}
+
+// typed bus handlers publish events to, they reach the connections of every instance through the hub
+#[derive(Clone)]
+pub struct EventBus {
+    hub: Arc<Hub>,
+}
+
+impl EventBus {
+    pub fn new(hub: Arc<Hub>) -> Self {
+        EventBus { hub }
+    }
+
+    // function for sending an event to the topics allowed to see it
+    pub fn publish(&self, event: Event) {
+        let topics = event.topics();
+        let message = event.message(Utc::now());
+
+        for topic in topics {
+            self.hub.publish(&topic, message.clone());
+        }
+    }
+}
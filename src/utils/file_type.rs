@@ -0,0 +1,489 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+// number of leading bytes inspected for signatures and text detection
+pub const HEAD_LEN: usize = 8192;
+// largest ZIP end of central directory record plus its comment
+const ZIP_EOCD_SEARCH_LEN: u64 = 22 + 65535;
+// upper bound of central directory bytes read to classify a ZIP
+const ZIP_MAX_CENTRAL_DIRECTORY: u64 = 4 * 1024 * 1024;
+// upper bound of directory sectors followed in an OLE container
+const OLE_MAX_DIRECTORY_SECTORS: usize = 256;
+
+pub const OCTET_STREAM: &str = "application/octet-stream";
+pub const ZIP: &str = "application/zip";
+pub const OLE_STORAGE: &str = "application/x-ole-storage";
+
+// MIME types announced by the "mimetype" entry of EPUB and OpenDocument files
+const ZIP_MIMETYPES: &[&str] = &[
+    "application/epub+zip",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.oasis.opendocument.presentation",
+    "application/vnd.oasis.opendocument.graphics",
+];
+
+// function for detecting the MIME type of a seekable reader
+pub fn sniff<R: Read + Seek>(reader: &mut R) -> std::io::Result<&'static str> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    reader
+        .by_ref()
+        .take(HEAD_LEN as u64)
+        .read_to_end(&mut head)?;
+
+    let mime = match sniff_head(&head) {
+        ZIP => sniff_zip(reader, &head).unwrap_or(ZIP),
+        OLE_STORAGE => sniff_ole(reader).unwrap_or(OLE_STORAGE),
+        mime => mime,
+    };
+
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(mime)
+}
+
+// function for detecting the MIME type of bytes held in memory
+pub fn sniff_bytes(bytes: &[u8]) -> &'static str {
+    sniff(&mut Cursor::new(bytes)).unwrap_or(OCTET_STREAM)
+}
+
+// function for checking a MIME type is a container that needs the whole file to classify
+pub fn is_container(mime: &str) -> bool {
+    mime == ZIP || mime == OLE_STORAGE
+}
+
+// function for classifying the leading bytes of a file
+fn sniff_head(head: &[u8]) -> &'static str {
+    // Images
+    if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return "image/jpeg";
+    }
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return "image/png";
+    }
+    if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        return "image/gif";
+    }
+    if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+        return "image/tiff";
+    }
+    if head.len() >= 14 && head.starts_with(b"BM") && head[6..10] == [0, 0, 0, 0] {
+        return "image/bmp";
+    }
+
+    // RIFF containers
+    if head.len() >= 12 && head.starts_with(b"RIFF") {
+        match &head[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+
+    // ISO base media (MP4, MOV, HEIC, AVIF, ...)
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return sniff_ftyp(head);
+    }
+
+    // Other media
+    if head.starts_with(b"ID3") {
+        return "audio/mpeg";
+    }
+    if head.starts_with(b"OggS") {
+        return "audio/ogg";
+    }
+    if head.starts_with(b"fLaC") {
+        return "audio/flac";
+    }
+    if head.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return if contains(head, b"webm") {
+            "video/webm"
+        } else {
+            "video/x-matroska"
+        };
+    }
+    if head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0 {
+        // MPEG audio frame sync, layer bits 00 are used by AAC ADTS
+        return if head[1] & 0x06 == 0 {
+            "audio/aac"
+        } else {
+            "audio/mpeg"
+        };
+    }
+
+    // Documents and archives
+    if head.starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    if head.starts_with(b"PK\x03\x04")
+        || head.starts_with(b"PK\x05\x06")
+        || head.starts_with(b"PK\x07\x08")
+    {
+        return ZIP;
+    }
+    if head.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        return OLE_STORAGE;
+    }
+    if head.starts_with(&[0x1F, 0x8B]) {
+        return "application/gzip";
+    }
+    if head.starts_with(b"7z\xBC\xAF\x27\x1C") {
+        return "application/x-7z-compressed";
+    }
+    if head.starts_with(b"Rar!\x1A\x07") {
+        return "application/vnd.rar";
+    }
+    if head.starts_with(b"{\\rtf") {
+        return "application/rtf";
+    }
+
+    sniff_text(head)
+}
+
+// function for classifying an ISO base media file by its major and compatible brands
+fn sniff_ftyp(head: &[u8]) -> &'static str {
+    let box_size = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+    let box_end = box_size.clamp(12, head.len());
+    let brands = std::iter::once(&head[8..12])
+        .chain(head.get(16..box_end).unwrap_or_default().chunks_exact(4))
+        .collect::<Vec<&[u8]>>();
+    let has = |names: &[&[u8]]| brands.iter().any(|brand| names.contains(brand));
+
+    if has(&[b"avif", b"avis"]) {
+        "image/avif"
+    } else if has(&[b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis"]) {
+        "image/heic"
+    } else if has(&[b"mif1", b"msf1"]) {
+        "image/heif"
+    } else if has(&[b"M4A ", b"M4B "]) {
+        "audio/mp4"
+    } else if &head[8..12] == b"qt  " {
+        "video/quicktime"
+    } else if head[8..11] == *b"3gp" {
+        "video/3gpp"
+    } else {
+        "video/mp4"
+    }
+}
+
+// function for classifying text content, including SVG and XML
+fn sniff_text(head: &[u8]) -> &'static str {
+    if head.is_empty() || head.contains(&0) {
+        return OCTET_STREAM;
+    }
+
+    // The head may end in the middle of a multi-byte character
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return OCTET_STREAM,
+    };
+    if text
+        .chars()
+        .any(|c| c.is_control() && !c.is_ascii_whitespace())
+    {
+        return OCTET_STREAM;
+    }
+
+    let trimmed = text.trim_start_matches('\u{FEFF}').trim_start();
+    let lower = trimmed
+        .get(..trimmed.len().min(1024))
+        .unwrap_or(trimmed)
+        .to_ascii_lowercase();
+
+    if lower.starts_with("<svg")
+        || ((lower.starts_with("<?xml") || lower.starts_with("<!doctype svg"))
+            && lower.contains("<svg"))
+    {
+        "image/svg+xml"
+    } else if lower.starts_with("<?xml") {
+        "application/xml"
+    } else if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        "text/html"
+    } else {
+        "text/plain"
+    }
+}
+
+// function for classifying a ZIP from its first entry and central directory
+fn sniff_zip<R: Read + Seek>(reader: &mut R, head: &[u8]) -> Option<&'static str> {
+    // EPUB and OpenDocument store an uncompressed "mimetype" entry first
+    if head.len() >= 30 && head.starts_with(b"PK\x03\x04") {
+        let method = read_u16(head, 8)?;
+        let compressed_size = read_u32(head, 18)? as usize;
+        let name_len = read_u16(head, 26)? as usize;
+        let extra_len = read_u16(head, 28)? as usize;
+        let data_start = 30 + name_len + extra_len;
+        if method == 0 && head.get(30..30 + name_len) == Some(b"mimetype".as_slice()) {
+            let content = head.get(data_start..data_start + compressed_size)?;
+            if let Some(mime) = ZIP_MIMETYPES
+                .iter()
+                .find(|mime| mime.as_bytes() == content.trim_ascii())
+            {
+                return Some(mime);
+            }
+        }
+    }
+
+    let names = zip_entry_names(reader)?;
+    let has_prefix = |prefix: &str| names.iter().any(|name| name.starts_with(prefix));
+    let has_name = |wanted: &str| names.iter().any(|name| name == wanted);
+
+    if has_name("[Content_Types].xml") {
+        if has_prefix("word/") {
+            return Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document");
+        }
+        if has_prefix("xl/") {
+            return Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet");
+        }
+        if has_prefix("ppt/") {
+            return Some(
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            );
+        }
+    }
+    if has_name("AndroidManifest.xml") && has_name("classes.dex") {
+        return Some("application/vnd.android.package-archive");
+    }
+    if has_name("META-INF/MANIFEST.MF") {
+        return Some("application/java-archive");
+    }
+    if has_name("META-INF/container.xml") && has_name("mimetype") {
+        return Some("application/epub+zip");
+    }
+
+    Some(ZIP)
+}
+
+// function for listing entry names from the ZIP central directory (ZIP64 aware)
+fn zip_entry_names<R: Read + Seek>(reader: &mut R) -> Option<Vec<String>> {
+    let file_len = reader.seek(SeekFrom::End(0)).ok()?;
+    let tail_len = file_len.min(ZIP_EOCD_SEARCH_LEN);
+    let tail_start = file_len - tail_len;
+    let tail = read_at(reader, tail_start, tail_len as usize)?;
+
+    // The end of central directory record is the last "PK\x05\x06" in the tail
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| tail[i..].starts_with(b"PK\x05\x06"))?;
+    let mut entries = read_u16(&tail, eocd + 10)? as u64;
+    let mut cd_size = read_u32(&tail, eocd + 12)? as u64;
+    let mut cd_offset = read_u32(&tail, eocd + 16)? as u64;
+
+    // ZIP64 stores the real values in a separate record found through a locator
+    if cd_offset == 0xFFFF_FFFF || cd_size == 0xFFFF_FFFF || entries == 0xFFFF {
+        let locator = eocd.checked_sub(20)?;
+        if !tail[locator..].starts_with(b"PK\x06\x07") {
+            return None;
+        }
+        let record_offset = read_u64(&tail, locator + 8)?;
+        let record = read_at(reader, record_offset, 56)?;
+        if !record.starts_with(b"PK\x06\x06") {
+            return None;
+        }
+        entries = read_u64(&record, 32)?;
+        cd_size = read_u64(&record, 40)?;
+        cd_offset = read_u64(&record, 48)?;
+    }
+
+    let cd = read_at(
+        reader,
+        cd_offset,
+        cd_size.min(ZIP_MAX_CENTRAL_DIRECTORY) as usize,
+    )?;
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while (names.len() as u64) < entries && cd[pos..].starts_with(b"PK\x01\x02") {
+        let name_len = read_u16(&cd, pos + 28)? as usize;
+        let extra_len = read_u16(&cd, pos + 30)? as usize;
+        let comment_len = read_u16(&cd, pos + 32)? as usize;
+        let name = cd.get(pos + 46..pos + 46 + name_len)?;
+        names.push(String::from_utf8_lossy(name).to_string());
+        pos += 46 + name_len + extra_len + comment_len;
+        if pos >= cd.len() {
+            break;
+        }
+    }
+
+    Some(names)
+}
+
+// function for classifying an OLE compound file by its directory stream names
+fn sniff_ole<R: Read + Seek>(reader: &mut R) -> Option<&'static str> {
+    let names = ole_entry_names(reader)?;
+    let has_name = |wanted: &str| names.iter().any(|name| name == wanted);
+
+    if has_name("WordDocument") {
+        Some("application/msword")
+    } else if has_name("Workbook") || has_name("Book") {
+        Some("application/vnd.ms-excel")
+    } else if has_name("PowerPoint Document") {
+        Some("application/vnd.ms-powerpoint")
+    } else if names.iter().any(|name| name.starts_with("__substg1.0_")) {
+        Some("application/vnd.ms-outlook")
+    } else if has_name("VisioDocument") {
+        Some("application/vnd.visio")
+    } else {
+        Some(OLE_STORAGE)
+    }
+}
+
+// function for listing directory entry names of an OLE compound file
+fn ole_entry_names<R: Read + Seek>(reader: &mut R) -> Option<Vec<String>> {
+    let header = read_at(reader, 0, 512)?;
+    let sector_shift = read_u16(&header, 0x1E)?;
+    if sector_shift != 9 && sector_shift != 12 {
+        return None;
+    }
+    let sector_size = 1u64 << sector_shift;
+    let first_directory_sector = read_u32(&header, 0x30)?;
+
+    // FAT sectors listed in the header DIFAT, enough to walk the directory chain of most files
+    let fat_sectors = (0..109)
+        .filter_map(|i| read_u32(&header, 0x4C + i * 4))
+        .take_while(|&sector| sector < 0xFFFF_FFFA)
+        .collect::<Vec<u32>>();
+    let entries_per_fat_sector = sector_size / 4;
+    let sector_offset = |sector: u32| (sector as u64 + 1) * sector_size;
+
+    let mut names = Vec::new();
+    let mut sector = first_directory_sector;
+    for _ in 0..OLE_MAX_DIRECTORY_SECTORS {
+        if sector >= 0xFFFF_FFFA {
+            break;
+        }
+
+        let Some(directory) = read_at(reader, sector_offset(sector), sector_size as usize) else {
+            break;
+        };
+        for entry in directory.chunks_exact(128) {
+            let name_len = (read_u16(entry, 64)? as usize).min(64);
+            let object_type = entry[66];
+            if name_len < 2 || object_type == 0 {
+                continue;
+            }
+            let name = entry[..name_len - 2]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<u16>>();
+            names.push(String::from_utf16_lossy(&name));
+        }
+
+        // Follow the FAT chain to the next directory sector, keep what was found if it ends early
+        let Some(&fat_sector) = fat_sectors.get((sector as u64 / entries_per_fat_sector) as usize)
+        else {
+            break;
+        };
+        let fat_entry_offset =
+            sector_offset(fat_sector) + (sector as u64 % entries_per_fat_sector) * 4;
+        match read_at(reader, fat_entry_offset, 4).and_then(|next| read_u32(&next, 0)) {
+            Some(next) => sector = next,
+            None => break,
+        }
+    }
+
+    Some(names)
+}
+
+// function for reading exactly len bytes at offset, or fewer at the end of the file
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> Option<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset)).ok()?;
+    let mut buffer = Vec::with_capacity(len);
+    reader
+        .by_ref()
+        .take(len as u64)
+        .read_to_end(&mut buffer)
+        .ok()?;
+    Some(buffer)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+// function for getting the usual file extension of a MIME type
+pub fn extension_for(mime: &str) -> &'static str {
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tiff",
+        "image/webp" => "webp",
+        "image/heic" => "heic",
+        "image/heif" => "heif",
+        "image/avif" => "avif",
+        "image/svg+xml" => "svg",
+        "audio/mpeg" => "mp3",
+        "audio/aac" => "aac",
+        "audio/mp4" => "m4a",
+        "audio/ogg" => "ogg",
+        "audio/flac" => "flac",
+        "audio/wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/quicktime" => "mov",
+        "video/3gpp" => "3gp",
+        "video/webm" => "webm",
+        "video/x-matroska" => "mkv",
+        "video/x-msvideo" => "avi",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" => "gz",
+        "application/x-7z-compressed" => "7z",
+        "application/vnd.rar" => "rar",
+        "application/rtf" => "rtf",
+        "application/msword" => "doc",
+        "application/vnd.ms-excel" => "xls",
+        "application/vnd.ms-powerpoint" => "ppt",
+        "application/vnd.ms-outlook" => "msg",
+        "application/vnd.visio" => "vsd",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation" => "pptx",
+        "application/vnd.oasis.opendocument.text" => "odt",
+        "application/vnd.oasis.opendocument.spreadsheet" => "ods",
+        "application/vnd.oasis.opendocument.presentation" => "odp",
+        "application/vnd.oasis.opendocument.graphics" => "odg",
+        "application/epub+zip" => "epub",
+        "application/java-archive" => "jar",
+        "application/vnd.android.package-archive" => "apk",
+        "application/xml" => "xml",
+        "text/html" => "html",
+        "text/plain" => "txt",
+        _ => "bin",
+    }
+}
+
+// function for getting the cloudinary resource type of a MIME type
+pub fn resource_type_for(mime: &str) -> &'static str {
+    match mime {
+        // cloudinary renders these as images, svg is kept raw so it is never transformed
+        "image/jpeg" | "image/png" | "image/gif" | "image/bmp" | "image/tiff" | "image/webp"
+        | "image/heic" | "image/heif" | "image/avif" => "image",
+        // cloudinary stores audio under the video resource type
+        mime if mime.starts_with("video/") || mime.starts_with("audio/") => "video",
+        _ => "raw",
+    }
+}
@@ -0,0 +1,421 @@
+use crate::schemas::message_schema::{OnlineUser, ServerMessage};
+use crate::utils::moderation::{ChatRules, RateBucket, RateLimit};
+use crate::utils::pubsub::{Envelope, PubSub};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+
+// messages a room buffers for its slowest subscriber
+pub const ROOM_CAPACITY: usize = 100;
+// public room every connection is subscribed to, also used when a client names no room
+pub const DEFAULT_ROOM: &str = "general";
+// longest room name in characters
+pub const MAX_ROOM_NAME_LEN: usize = 64;
+// channel every connection listens to for online-list snapshots,
+// it can not collide with a room since room names never start with @
+pub const PRESENCE_TOPIC: &str = "@presence";
+// channel the connections of admins listen to for changes of other users
+pub const ADMIN_TOPIC: &str = "@admins";
+// envelopes waiting for the pub/sub transport before new ones are dropped
+const RELAY_BUFFER: usize = 1024;
+// how often an instance sends its online list to the others even when nobody came or went
+pub const PRESENCE_HEARTBEAT: Duration = Duration::from_secs(30);
+// how long the online list of another instance counts without a new one, it may have stopped
+pub const PRESENCE_EXPIRY: Duration = Duration::from_secs(90);
+
+// registry of room channels, a channel is created on the first subscription
+// and dropped again when its last subscriber leaves,
+// presence is counted per connection so users with several tabs stay online until the last closes,
+// with a pub/sub transport room messages reach the other instances too and every instance
+// sends its own online list, the lists of all instances are merged
+#[derive(Default)]
+pub struct Hub {
+    rooms: Mutex<HashMap<String, broadcast::Sender<ServerMessage>>>,
+    online: Mutex<HashMap<i64, OnlineUser>>,
+    // online lists of the other instances by instance id and when they arrived
+    remote_online: Mutex<HashMap<String, (Vec<OnlineUser>, Instant)>>,
+    room_users: Mutex<HashMap<String, HashMap<i64, usize>>>,
+    // messages left per user, kept after disconnecting so reconnecting does not refill them,
+    // until they have refilled
+    rates: Mutex<HashMap<i64, RateBucket>>,
+    // length, rate limit and filters of chat messages
+    rules: ChatRules,
+    // id of this instance, envelopes it published itself are not delivered twice
+    instance: String,
+    // queue of the envelopes for the transport, None when messages stay in this instance
+    relay: Option<mpsc::Sender<Envelope>>,
+}
+
+// subscription to a room, the room is released when it is dropped
+pub struct Subscription {
+    hub: Arc<Hub>,
+    room: String,
+    rx: Option<broadcast::Receiver<ServerMessage>>,
+}
+
+impl Hub {
+    // hub that keeps its rooms in this instance, the server always shares them through a transport
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // function for creating a hub that shares its rooms with the other instances through a transport
+    pub fn with_pubsub(pubsub: Arc<dyn PubSub>, rules: ChatRules) -> Arc<Self> {
+        let (relay, mut outgoing) = mpsc::channel::<Envelope>(RELAY_BUFFER);
+        let hub = Arc::new(Hub {
+            instance: instance_id(),
+            relay: Some(relay),
+            rules,
+            ..Default::default()
+        });
+
+        // Envelopes are published in order, a slow transport never holds up a connection
+        let publisher = Arc::clone(&pubsub);
+        tokio::spawn(async move {
+            while let Some(envelope) = outgoing.recv().await {
+                if let Err(e) = publisher.publish(&envelope).await {
+                    println!("Pub/Sub Error ({}): {:?}", publisher.name(), e);
+                }
+            }
+        });
+
+        // The subscription ends with the hub, it does not keep the hub alive
+        let mut incoming = pubsub.subscribe();
+        let weak = Arc::downgrade(&hub);
+        tokio::spawn(async move {
+            while let Some(envelope) = incoming.recv().await {
+                let Some(hub) = weak.upgrade() else {
+                    break;
+                };
+                if envelope.origin == hub.instance {
+                    continue;
+                }
+                match envelope.message {
+                    ServerMessage::Presence { online } if envelope.room == PRESENCE_TOPIC => {
+                        hub.remote_online
+                            .lock()
+                            .unwrap()
+                            .insert(envelope.origin, (online, Instant::now()));
+                        hub.deliver_presence();
+                    }
+                    message => {
+                        hub.deliver(&envelope.room, message);
+                    }
+                }
+            }
+        });
+
+        // The other instances keep this one's users online as long as it keeps telling them
+        let weak = Arc::downgrade(&hub);
+        tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + PRESENCE_HEARTBEAT;
+            let mut heartbeat = tokio::time::interval_at(start, PRESENCE_HEARTBEAT);
+            loop {
+                heartbeat.tick().await;
+                let Some(hub) = weak.upgrade() else {
+                    break;
+                };
+                hub.relay_presence();
+                if hub.expire_presence(Instant::now()) {
+                    hub.deliver_presence();
+                }
+            }
+        });
+
+        hub
+    }
+
+    // function for subscribing to a room, creating its channel when nobody listens yet
+    pub fn subscribe(self: &Arc<Self>, room: &str) -> Subscription {
+        let mut rooms = self.rooms.lock().unwrap();
+        let rx = rooms
+            .entry(room.to_string())
+            .or_insert_with(|| broadcast::channel(ROOM_CAPACITY).0)
+            .subscribe();
+
+        Subscription {
+            hub: Arc::clone(self),
+            room: room.to_string(),
+            rx: Some(rx),
+        }
+    }
+
+    // function for sending a message to every subscriber of a room on every instance,
+    // returns how many subscribers of this instance got it,
+    // online lists only go out through publish_presence
+    pub fn publish(&self, room: &str, message: ServerMessage) -> usize {
+        if room != PRESENCE_TOPIC {
+            self.relay(room, message.clone());
+        }
+
+        self.deliver(room, message)
+    }
+
+    // function for handing a message to the transport, a full relay keeps it in this instance
+    fn relay(&self, room: &str, message: ServerMessage) {
+        let Some(relay) = self.relay.as_ref() else {
+            return;
+        };
+        let envelope = Envelope {
+            origin: self.instance.clone(),
+            room: room.to_string(),
+            message,
+        };
+        if relay.try_send(envelope).is_err() {
+            println!("Pub/Sub relay is full, a message of {} stays local", room);
+        }
+    }
+
+    // function for sending the online list to every connection of every instance,
+    // the other instances get the users of this one and merge them with their own
+    pub fn publish_presence(&self) {
+        self.relay_presence();
+        self.deliver_presence();
+    }
+
+    // function for sending the users of this instance to the others
+    fn relay_presence(&self) {
+        let online = self.online.lock().unwrap().values().cloned().collect();
+        self.relay(PRESENCE_TOPIC, ServerMessage::Presence { online });
+    }
+
+    // function for sending the merged online list to the connections of this instance
+    fn deliver_presence(&self) {
+        self.deliver(
+            PRESENCE_TOPIC,
+            ServerMessage::Presence {
+                online: self.online_users(),
+            },
+        );
+    }
+
+    // function for dropping the online lists of instances that stopped sending them,
+    // true when one was dropped
+    fn expire_presence(&self, now: Instant) -> bool {
+        let mut remote_online = self.remote_online.lock().unwrap();
+        let before = remote_online.len();
+        remote_online
+            .retain(|_, (_, received)| now.saturating_duration_since(*received) < PRESENCE_EXPIRY);
+
+        remote_online.len() < before
+    }
+
+    // function for sending a message to the subscribers of a room on this instance
+    fn deliver(&self, room: &str, message: ServerMessage) -> usize {
+        let rooms = self.rooms.lock().unwrap();
+
+        rooms
+            .get(room)
+            .and_then(|tx| tx.send(message).ok())
+            .unwrap_or(0)
+    }
+
+    // function for listing the rooms that currently have subscribers
+    #[cfg(test)]
+    pub fn active_rooms(&self) -> Vec<String> {
+        let mut rooms = self
+            .rooms
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        rooms.sort();
+        rooms
+    }
+
+    // function for counting a new connection of a user, true when the user just came online
+    pub fn connect(&self, user_id: i64, username: &str) -> bool {
+        let mut online = self.online.lock().unwrap();
+        let user = online.entry(user_id).or_insert_with(|| OnlineUser {
+            user_id,
+            username: username.to_string(),
+            connections: 0,
+            online_since: Utc::now(),
+        });
+        user.connections += 1;
+
+        user.connections == 1
+    }
+
+    // function for counting a closed connection of a user, true when the user went offline
+    pub fn disconnect(&self, user_id: i64) -> bool {
+        let mut online = self.online.lock().unwrap();
+        let Some(user) = online.get_mut(&user_id) else {
+            return false;
+        };
+        user.connections = user.connections.saturating_sub(1);
+        if user.connections > 0 {
+            return false;
+        }
+
+        online.remove(&user_id);
+        true
+    }
+
+    // function for listing the users with at least one open connection on any instance, sorted by name
+    pub fn online_users(&self) -> Vec<OnlineUser> {
+        let mut users = self
+            .online
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<OnlineUser>>();
+        for (online, _) in self.remote_online.lock().unwrap().values() {
+            users.extend(online.iter().cloned());
+        }
+
+        merge_online(users)
+    }
+
+    // function for getting the rules chat messages are checked against
+    pub fn rules(&self) -> &ChatRules {
+        &self.rules
+    }
+
+    // function for counting a message of a user against the limit all its connections share,
+    // returns how long the user has to wait once the limit is reached
+    pub fn take_message(
+        &self,
+        user_id: i64,
+        limit: RateLimit,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut rates = self.rates.lock().unwrap();
+
+        // Buckets of users that stopped chatting are dropped once they have refilled
+        if !rates.contains_key(&user_id) {
+            rates.retain(|_, bucket| !bucket.is_full(limit, now));
+        }
+        rates
+            .entry(user_id)
+            .or_insert_with(|| RateBucket::new(limit, now))
+            .take(limit, now)
+    }
+
+    // function for counting a connection of a user entering a room, true for the user's first
+    pub fn enter_room(&self, room: &str, user_id: i64) -> bool {
+        let mut room_users = self.room_users.lock().unwrap();
+        let connections = room_users
+            .entry(room.to_string())
+            .or_default()
+            .entry(user_id)
+            .or_insert(0);
+        *connections += 1;
+
+        *connections == 1
+    }
+
+    // function for counting a connection of a user leaving a room, true for the user's last
+    pub fn exit_room(&self, room: &str, user_id: i64) -> bool {
+        let mut room_users = self.room_users.lock().unwrap();
+        let Some(users) = room_users.get_mut(room) else {
+            return false;
+        };
+        let Some(connections) = users.get_mut(&user_id) else {
+            return false;
+        };
+        *connections -= 1;
+        if *connections > 0 {
+            return false;
+        }
+
+        users.remove(&user_id);
+        if users.is_empty() {
+            room_users.remove(room);
+        }
+        true
+    }
+
+    // function for dropping the channel of a room nobody is subscribed to anymore
+    fn release(&self, room: &str) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if rooms.get(room).is_some_and(|tx| tx.receiver_count() == 0) {
+            rooms.remove(room);
+        }
+    }
+}
+
+impl Subscription {
+    pub fn room(&self) -> &str {
+        &self.room
+    }
+
+    // function for waiting for the next message of the room
+    pub async fn recv(&mut self) -> Result<ServerMessage, RecvError> {
+        match self.rx.as_mut() {
+            Some(rx) => rx.recv().await,
+            None => Err(RecvError::Closed),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // the receiver has to be gone before the room can see it has no subscribers
+        self.rx.take();
+        self.hub.release(&self.room);
+    }
+}
+
+// function for generating the random id of an instance
+fn instance_id() -> String {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).expect("operating system random number generator");
+
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// function for merging the online lists of several instances, the connections of a user are added up
+// and it is online since its earliest connection, sorted by name
+pub fn merge_online(users: Vec<OnlineUser>) -> Vec<OnlineUser> {
+    let mut merged: HashMap<i64, OnlineUser> = HashMap::new();
+    for user in users {
+        match merged.get_mut(&user.user_id) {
+            Some(existing) => {
+                existing.connections += user.connections;
+                existing.online_since = existing.online_since.min(user.online_since);
+            }
+            None => {
+                merged.insert(user.user_id, user);
+            }
+        }
+    }
+
+    let mut users = merged.into_values().collect::<Vec<OnlineUser>>();
+    users.sort_by(|a, b| {
+        a.username
+            .to_lowercase()
+            .cmp(&b.username.to_lowercase())
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    users
+}
+
+// function for naming the channel of the connections of one user, direct messages go there
+pub fn user_topic(user_id: i64) -> String {
+    format!("@user:{}", user_id)
+}
+
+// function for normalizing a room name, None when it is not 1 to 64 lowercase letters,
+// digits, dashes or underscores starting with a letter or digit
+pub fn normalize_room_name(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    let valid = name.chars().count() <= MAX_ROOM_NAME_LEN
+        && name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    valid.then_some(name)
+}
@@ -0,0 +1,178 @@
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, Limits,
+    codecs::jpeg::JpegEncoder, imageops::FilterType,
+};
+use std::io::Cursor;
+
+// square sizes every avatar is resized to
+pub const AVATAR_SIZES: [u32; 3] = [64, 128, 256];
+// longest side of the sanitized full-size avatar
+pub const AVATAR_MAX_SIZE: u32 = 1024;
+// bounding box of document thumbnails
+pub const THUMBNAIL_SIZE: u32 = 320;
+// largest source file a thumbnail is generated for
+pub const THUMBNAIL_MAX_SOURCE_SIZE: u64 = 50 * 1024 * 1024;
+// limits applied while decoding untrusted images
+const MAX_IMAGE_DIMENSION: u32 = 12_000;
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+// quality of re-encoded JPEG variants
+const JPEG_QUALITY: u8 = 85;
+
+pub struct ImageVariant {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+// function for decoding an untrusted image with size limits, EXIF orientation is applied
+pub fn decode(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    Ok(image)
+}
+
+// function for encoding an image, re-encoding drops EXIF and every other metadata block,
+// images with transparency stay PNG and everything else becomes JPEG
+pub fn encode(image: &DynamicImage) -> ImageResult<(Vec<u8>, &'static str)> {
+    let mut bytes = Vec::new();
+
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+        Ok((bytes, "image/png"))
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY);
+        image.to_rgb8().write_with_encoder(encoder)?;
+        Ok((bytes, "image/jpeg"))
+    }
+}
+
+// function for building a named variant from an image
+fn variant(name: &str, image: &DynamicImage) -> ImageResult<ImageVariant> {
+    let (bytes, mime_type) = encode(image)?;
+
+    Ok(ImageVariant {
+        name: name.to_string(),
+        width: image.width(),
+        height: image.height(),
+        mime_type,
+        bytes,
+    })
+}
+
+// function for producing the sanitized avatar followed by a square crop for every avatar size
+pub fn avatar_variants(bytes: &[u8]) -> ImageResult<Vec<ImageVariant>> {
+    let image = decode(bytes)?;
+    let original = if image.width() > AVATAR_MAX_SIZE || image.height() > AVATAR_MAX_SIZE {
+        image.resize(AVATAR_MAX_SIZE, AVATAR_MAX_SIZE, FilterType::Lanczos3)
+    } else {
+        image.clone()
+    };
+
+    let mut variants = vec![variant("original", &original)?];
+    for size in AVATAR_SIZES {
+        let resized = image.resize_to_fill(size, size, FilterType::Lanczos3);
+        variants.push(variant(&size.to_string(), &resized)?);
+    }
+
+    Ok(variants)
+}
+
+// function for producing a thumbnail of an image that fits the thumbnail box
+pub fn image_thumbnail(bytes: &[u8]) -> ImageResult<ImageVariant> {
+    thumbnail_variant(decode(bytes)?)
+}
+
+// function for building the thumbnail variant, small images are never scaled up
+fn thumbnail_variant(image: DynamicImage) -> ImageResult<ImageVariant> {
+    let image = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    } else {
+        image
+    };
+
+    variant("thumbnail", &image)
+}
+
+// function for producing a thumbnail of the first page of a PDF,
+// PDFs are not rendered, the largest image embedded on the first page is used instead
+// (scanned documents and most brochures have one), None when there is nothing usable
+pub fn pdf_thumbnail(bytes: &[u8]) -> Option<ImageVariant> {
+    let document = lopdf::Document::load_mem(bytes).ok()?;
+    let (_, page_id) = document.get_pages().into_iter().next()?;
+    let mut images = document.get_page_images(page_id).ok()?;
+    images.sort_by_key(|image| std::cmp::Reverse(image.width.saturating_mul(image.height)));
+
+    images.into_iter().find_map(|embedded| {
+        let filters = embedded.filters.clone().unwrap_or_default();
+        let image = if filters == ["DCTDecode"] {
+            // DCTDecode streams are plain JPEG files
+            decode(embedded.content).ok()?
+        } else if filters.iter().all(|filter| filter == "FlateDecode")
+            && within_limits(embedded.width, embedded.height)
+        {
+            let content = document
+                .get_object(embedded.id)
+                .and_then(|object| object.as_stream())
+                .and_then(|stream| stream.get_plain_content())
+                .ok()?;
+            raw_image(
+                embedded.width as u32,
+                embedded.height as u32,
+                embedded.bits_per_component,
+                embedded.color_space.as_deref(),
+                content,
+            )?
+        } else {
+            return None;
+        };
+
+        thumbnail_variant(image).ok()
+    })
+}
+
+// function for producing the thumbnail of a stored document, None for types without one
+pub fn document_thumbnail(bytes: &[u8], mime_type: &str) -> Option<ImageVariant> {
+    match mime_type {
+        "application/pdf" => pdf_thumbnail(bytes),
+        mime_type if mime_type.starts_with("image/") => image_thumbnail(bytes).ok(),
+        _ => None,
+    }
+}
+
+// function for checking image dimensions before anything is decompressed
+fn within_limits(width: i64, height: i64) -> bool {
+    (1..=MAX_IMAGE_DIMENSION as i64).contains(&width)
+        && (1..=MAX_IMAGE_DIMENSION as i64).contains(&height)
+}
+
+// function for building an image from uncompressed 8-bit gray or RGB samples
+fn raw_image(
+    width: u32,
+    height: u32,
+    bits_per_component: Option<i64>,
+    color_space: Option<&str>,
+    content: Vec<u8>,
+) -> Option<DynamicImage> {
+    if bits_per_component != Some(8) {
+        return None;
+    }
+
+    match color_space? {
+        "DeviceGray" => image::GrayImage::from_raw(width, height, content).map(DynamicImage::from),
+        "DeviceRGB" => image::RgbImage::from_raw(width, height, content).map(DynamicImage::from),
+        _ => None,
+    }
+}
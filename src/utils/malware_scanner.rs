@@ -0,0 +1,202 @@
+use futures::future::BoxFuture;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// scan status stored on blobs, document versions and documents
+pub const SCAN_PENDING: &str = "pending";
+pub const SCAN_CLEAN: &str = "clean";
+pub const SCAN_INFECTED: &str = "infected";
+// stored while scanning is disabled and for files that predate scanning, such files are
+// served but never trusted as clean until a scanner has looked at them
+pub const SCAN_UNSCANNED: &str = "unscanned";
+
+// size of the chunks a file is read and streamed in
+const SCAN_BUFFER_SIZE: usize = 64 * 1024;
+
+// the EICAR anti-virus test file, harmless but reported as a virus by every scanner
+pub const EICAR_SIGNATURE: &[u8] =
+    br"X5O!P%@AP[4\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+#[derive(Debug, PartialEq)]
+pub enum ScanResult {
+    Clean,
+    // name of the signature that matched
+    Infected(String),
+    // no scanner looked at the file
+    Skipped,
+}
+
+impl ScanResult {
+    // function for getting the scan status stored for a result
+    pub fn status(&self) -> &'static str {
+        match self {
+            ScanResult::Clean => SCAN_CLEAN,
+            ScanResult::Infected(_) => SCAN_INFECTED,
+            ScanResult::Skipped => SCAN_UNSCANNED,
+        }
+    }
+}
+
+// a malware scanner, errors leave a file pending instead of clean or infected
+pub trait Scanner: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<ScanResult>>;
+}
+
+// scanner used when scanning is disabled, every file is skipped
+pub struct DisabledScanner;
+
+impl Scanner for DisabledScanner {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn scan<'a>(&'a self, _path: &'a Path) -> BoxFuture<'a, io::Result<ScanResult>> {
+        Box::pin(async { Ok(ScanResult::Skipped) })
+    }
+}
+
+// test scanner that only flags files containing the EICAR test string
+pub struct EicarScanner;
+
+impl Scanner for EicarScanner {
+    fn name(&self) -> &'static str {
+        "eicar"
+    }
+
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<ScanResult>> {
+        Box::pin(async move {
+            let mut file = File::open(path).await?;
+            let mut buffer = vec![0u8; SCAN_BUFFER_SIZE];
+            // keep the tail of the previous read so a match across two reads is found
+            let mut window: Vec<u8> = Vec::with_capacity(SCAN_BUFFER_SIZE + EICAR_SIGNATURE.len());
+
+            loop {
+                let read = file.read(&mut buffer).await?;
+                if read == 0 {
+                    return Ok(ScanResult::Clean);
+                }
+
+                window.extend_from_slice(&buffer[..read]);
+                if window
+                    .windows(EICAR_SIGNATURE.len())
+                    .any(|candidate| candidate == EICAR_SIGNATURE)
+                {
+                    return Ok(ScanResult::Infected("Eicar-Test-Signature".to_string()));
+                }
+
+                let keep = window.len().min(EICAR_SIGNATURE.len() - 1);
+                window.drain(..window.len() - keep);
+            }
+        })
+    }
+}
+
+// address of a clamd daemon
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClamdAddress {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl ClamdAddress {
+    // function for parsing an address, absolute paths are unix sockets and everything else is host:port
+    pub fn parse(address: &str) -> Self {
+        match address.strip_prefix("unix://") {
+            Some(path) => ClamdAddress::Unix(PathBuf::from(path)),
+            None if address.starts_with('/') => ClamdAddress::Unix(PathBuf::from(address)),
+            None => ClamdAddress::Tcp(address.to_string()),
+        }
+    }
+}
+
+// scanner that streams files to a ClamAV daemon with the INSTREAM command
+pub struct ClamdScanner {
+    pub address: ClamdAddress,
+    pub timeout: Duration,
+}
+
+impl ClamdScanner {
+    // function for sending a file to clamd and reading its reply
+    async fn instream<S>(stream: &mut S, path: &Path) -> io::Result<ScanResult>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut file = File::open(path).await?;
+        let mut buffer = vec![0u8; SCAN_BUFFER_SIZE];
+
+        stream.write_all(b"zINSTREAM\0").await?;
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            // every chunk is prefixed with its length as a 4 byte big endian integer
+            stream.write_all(&(read as u32).to_be_bytes()).await?;
+            stream.write_all(&buffer[..read]).await?;
+        }
+        // a zero length chunk ends the stream
+        stream.write_all(&0u32.to_be_bytes()).await?;
+        stream.flush().await?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+
+        parse_clamd_reply(&String::from_utf8_lossy(&reply))
+    }
+}
+
+impl Scanner for ClamdScanner {
+    fn name(&self) -> &'static str {
+        "clamd"
+    }
+
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, io::Result<ScanResult>> {
+        Box::pin(async move {
+            let scan = async {
+                match &self.address {
+                    ClamdAddress::Tcp(address) => {
+                        let mut stream = TcpStream::connect(address).await?;
+                        Self::instream(&mut stream, path).await
+                    }
+                    #[cfg(unix)]
+                    ClamdAddress::Unix(socket) => {
+                        let mut stream = tokio::net::UnixStream::connect(socket).await?;
+                        Self::instream(&mut stream, path).await
+                    }
+                    #[cfg(not(unix))]
+                    ClamdAddress::Unix(_) => Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "unix sockets are not supported on this platform",
+                    )),
+                }
+            };
+
+            tokio::time::timeout(self.timeout, scan)
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "clamd scan timed out"))?
+        })
+    }
+}
+
+// function for parsing a clamd reply such as "stream: OK" or "stream: Eicar-Signature FOUND"
+pub fn parse_clamd_reply(reply: &str) -> io::Result<ScanResult> {
+    let reply = reply.trim_end_matches(['\0', '\n']).trim();
+    let result = reply.strip_prefix("stream:").unwrap_or(reply).trim();
+
+    if result == "OK" {
+        return Ok(ScanResult::Clean);
+    }
+    if let Some(signature) = result.strip_suffix("FOUND") {
+        return Ok(ScanResult::Infected(signature.trim().to_string()));
+    }
+
+    Err(io::Error::other(format!("clamd error: {}", reply)))
+}
@@ -1,3 +1,19 @@
+pub mod checksum;
+pub mod events;
+pub mod file_type;
+pub mod hub;
+pub mod image_pipeline;
 pub mod jwt;
+pub mod malware_scanner;
+pub mod moderation;
 pub mod password;
+pub mod pubsub;
 pub mod response;
+pub mod s3_presign;
+pub mod search;
+pub mod signed_url;
+pub mod storage;
+pub mod text_extract;
+pub mod upload_janitor;
+pub mod upload_path;
+pub mod zip_stream;
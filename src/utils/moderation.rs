@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+// outcome of checking a chat message against a filter
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    // the message may be sent, possibly rewritten by the filter
+    Allow(String),
+    // the message is refused, carries the reason told to the sender
+    Reject(String),
+}
+
+// hook that checks chat messages before they are stored, filters run in the configured order
+pub trait ContentFilter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn check(&self, message: &str) -> Verdict;
+}
+
+// what the word filter does with a message containing a blocked word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordAction {
+    // the word is replaced by asterisks
+    Mask,
+    Reject,
+}
+
+// filter for blocked words, matched as whole words regardless of case
+pub struct WordFilter {
+    // lowercase
+    pub words: Vec<String>,
+    pub action: WordAction,
+}
+
+impl ContentFilter for WordFilter {
+    fn name(&self) -> &'static str {
+        "words"
+    }
+
+    fn check(&self, message: &str) -> Verdict {
+        let mut filtered = String::with_capacity(message.len());
+        let mut blocked = false;
+        let mut rest = message;
+
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            let (word, tail) = rest.split_at(end);
+            if !word.is_empty() && self.words.contains(&word.to_lowercase()) {
+                blocked = true;
+                filtered.extend(word.chars().map(|_| '*'));
+            } else {
+                filtered.push_str(word);
+            }
+
+            let end = tail.find(char::is_alphanumeric).unwrap_or(tail.len());
+            let (separator, tail) = tail.split_at(end);
+            filtered.push_str(separator);
+            rest = tail;
+        }
+
+        match (blocked, self.action) {
+            (true, WordAction::Reject) => {
+                Verdict::Reject("Message contains a blocked word".to_string())
+            }
+            _ => Verdict::Allow(filtered),
+        }
+    }
+}
+
+// which links a chat message may contain
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkPolicy {
+    Allow,
+    Deny,
+    // only links to these domains and their subdomains, lowercase
+    Domains(Vec<String>),
+}
+
+impl ContentFilter for LinkPolicy {
+    fn name(&self) -> &'static str {
+        "links"
+    }
+
+    fn check(&self, message: &str) -> Verdict {
+        let hosts = link_hosts(message);
+        let refused = match self {
+            LinkPolicy::Allow => None,
+            LinkPolicy::Deny => hosts.first(),
+            LinkPolicy::Domains(domains) => hosts
+                .iter()
+                .find(|host| !domains.iter().any(|domain| host_in_domain(host, domain))),
+        };
+
+        match (self, refused) {
+            (_, None) => Verdict::Allow(message.to_string()),
+            (LinkPolicy::Deny, Some(_)) => Verdict::Reject("Links are not allowed".to_string()),
+            (_, Some(host)) => Verdict::Reject(format!("Links to {} are not allowed", host)),
+        }
+    }
+}
+
+// function for finding the hosts of the links in a message,
+// words starting with http://, https:// or www. are links
+pub fn link_hosts(message: &str) -> Vec<String> {
+    message
+        .split_whitespace()
+        .filter_map(|word| {
+            let lower = word.to_lowercase();
+            let address = ["http://", "https://"]
+                .iter()
+                .find_map(|scheme| lower.strip_prefix(scheme))
+                .map(str::to_string)
+                .or_else(|| lower.starts_with("www.").then(|| lower.clone()))?;
+
+            let host = address
+                .split(['/', '?', '#', ':'])
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches(['.', ',', ')', '!']);
+            (!host.is_empty()).then(|| host.to_string())
+        })
+        .collect()
+}
+
+// function for checking a host is the domain or one of its subdomains
+fn host_in_domain(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+// function for running a message through the filters, a rewrite is passed on to the next filter
+pub fn filter_message(filters: &[Box<dyn ContentFilter>], message: &str) -> Result<String, String> {
+    let mut message = message.to_string();
+    for filter in filters {
+        match filter.check(&message) {
+            Verdict::Allow(allowed) => message = allowed,
+            Verdict::Reject(reason) => return Err(reason),
+        }
+    }
+
+    Ok(message)
+}
+
+// how many messages a user may send within a window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub messages: u32,
+    pub window: Duration,
+}
+
+// messages a user has left, refilled evenly over the window up to the limit
+#[derive(Debug, Clone)]
+pub struct RateBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateBucket {
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        RateBucket {
+            tokens: limit.messages as f64,
+            updated: now,
+        }
+    }
+
+    // function for taking one message from the bucket,
+    // when it is empty returns how long until the next message is allowed
+    pub fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let per_second = limit.messages as f64 / limit.window.as_secs_f64();
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(limit.messages as f64);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+    }
+
+    // function for checking the bucket has refilled to the limit, a full bucket is the same as none
+    pub fn is_full(&self, limit: RateLimit, now: Instant) -> bool {
+        let per_second = limit.messages as f64 / limit.window.as_secs_f64();
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * per_second >= limit.messages as f64
+    }
+}
+
+// rules every chat message is checked against, built once from the configuration
+pub struct ChatRules {
+    // in characters
+    pub max_length: usize,
+    pub rate_limit: RateLimit,
+    pub filters: Vec<Box<dyn ContentFilter>>,
+}
+
+impl Default for ChatRules {
+    // no limits and no filters, the server builds its rules from the configuration
+    fn default() -> Self {
+        ChatRules {
+            max_length: usize::MAX,
+            rate_limit: RateLimit {
+                messages: u32::MAX,
+                window: Duration::from_secs(1),
+            },
+            filters: Vec::new(),
+        }
+    }
+}
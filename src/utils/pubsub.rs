@@ -0,0 +1,447 @@
+use crate::schemas::message_schema::ServerMessage;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    sync::{Mutex, broadcast, mpsc},
+};
+
+// envelopes a subscriber buffers before the hub takes them
+const SUBSCRIBER_BUFFER: usize = 1024;
+// most rows the MySQL poller reads at once
+const POLL_BATCH: i64 = 500;
+// how long published rows are kept for instances that poll late
+const MYSQL_RETENTION_SECONDS: i64 = 300;
+// how long the MySQL poller waits for a row below one it already read, ids are taken on insert
+// but rows become visible on commit, so a slower transaction can show up behind a newer row
+pub const MYSQL_GAP_GRACE: Duration = Duration::from_secs(10);
+// pause before a lost Redis subscription is opened again
+const REDIS_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// message of a room as it travels between instances,
+// the origin tells an instance which envelopes it published itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub origin: String,
+    pub room: String,
+    pub message: ServerMessage,
+}
+
+// transport that carries hub messages to every instance of the server, errors are logged by the hub
+pub trait PubSub: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, io::Result<()>>;
+
+    // receives the envelopes of every instance, this one included, until the receiver is dropped
+    fn subscribe(&self) -> mpsc::Receiver<Envelope>;
+}
+
+// transport for a single instance, cloned handles share one channel
+#[derive(Clone)]
+pub struct MemoryPubSub {
+    tx: broadcast::Sender<Envelope>,
+}
+
+impl MemoryPubSub {
+    pub fn new() -> Self {
+        MemoryPubSub {
+            tx: broadcast::channel(SUBSCRIBER_BUFFER).0,
+        }
+    }
+}
+
+impl Default for MemoryPubSub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PubSub for MemoryPubSub {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, io::Result<()>> {
+        // nobody listening is not an error
+        let _ = self.tx.send(envelope.clone());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<Envelope> {
+        let mut rx = self.tx.subscribe();
+        let (tx, envelopes) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(envelope) => {
+                        if tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!("Memory pub/sub skipped {} envelopes", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        envelopes
+    }
+}
+
+// position of a MySQL subscriber, rows are read again from the first id that may still show up
+// and the ones already delivered above it are skipped
+#[derive(Debug)]
+pub struct PollCursor {
+    // every row up to this id was delivered or given up on
+    floor: i64,
+    // rows delivered above the floor and when they were read
+    seen: BTreeMap<i64, Instant>,
+}
+
+impl PollCursor {
+    pub fn new(floor: i64) -> Self {
+        PollCursor {
+            floor,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    // function for getting the id the next poll reads after
+    pub fn floor(&self) -> i64 {
+        self.floor
+    }
+
+    // function for getting how many rows of the next poll may already have been delivered
+    pub fn window(&self) -> i64 {
+        self.seen.len() as i64
+    }
+
+    // function for recording a row that was read, false when it was delivered before
+    pub fn accept(&mut self, id: i64, now: Instant) -> bool {
+        if id <= self.floor || self.seen.contains_key(&id) {
+            return false;
+        }
+
+        self.seen.insert(id, now);
+        true
+    }
+
+    // function for moving the floor past the rows that were delivered without a gap below them,
+    // a gap is given up once the row above it was read longer than the grace ago
+    pub fn advance(&mut self, now: Instant, grace: Duration) {
+        while let Some((&id, &read)) = self.seen.first_key_value() {
+            if id != self.floor + 1 && now.saturating_duration_since(read) < grace {
+                break;
+            }
+            self.floor = id;
+            self.seen.remove(&id);
+        }
+    }
+}
+
+// transport over the database every instance already uses, subscribers poll for new rows
+pub struct MySqlPubSub {
+    pub db: MySqlPool,
+    pub poll_interval: Duration,
+}
+
+impl MySqlPubSub {
+    // function for reading the rows after the floor of a cursor, oldest first,
+    // the rows it already delivered do not count against the batch
+    async fn poll(db: &MySqlPool, cursor: &PollCursor) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id, payload FROM pubsub_messages WHERE id > ? ORDER BY id LIMIT ?",
+            cursor.floor(),
+            POLL_BATCH + cursor.window()
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.id, row.payload)).collect())
+    }
+
+    // function for deleting rows every instance had time to read
+    async fn prune(db: &MySqlPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM pubsub_messages WHERE created_at < NOW() - INTERVAL ? SECOND",
+            MYSQL_RETENTION_SECONDS
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl PubSub for MySqlPubSub {
+    fn name(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let payload = serde_json::to_string(envelope).map_err(io::Error::other)?;
+            sqlx::query!(
+                "INSERT INTO pubsub_messages (origin, payload) VALUES (?, ?)",
+                envelope.origin,
+                payload
+            )
+            .execute(&self.db)
+            .await
+            .map_err(io::Error::other)?;
+
+            Ok(())
+        })
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<Envelope> {
+        let (db, poll_interval) = (self.db.clone(), self.poll_interval);
+        let (tx, envelopes) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+        tokio::spawn(async move {
+            // only rows published after the subscription are delivered
+            let mut cursor = loop {
+                match sqlx::query!("SELECT COALESCE(MAX(id), 0) AS `id!: i64` FROM pubsub_messages")
+                    .fetch_one(&db)
+                    .await
+                {
+                    Ok(row) => break PollCursor::new(row.id),
+                    Err(e) => {
+                        println!("MySQL Pub/Sub Error: {:?}", e);
+                        tokio::time::sleep(poll_interval).await;
+                    }
+                }
+            };
+
+            let mut interval = tokio::time::interval(poll_interval);
+            let mut polls: u64 = 0;
+            loop {
+                interval.tick().await;
+                if tx.is_closed() {
+                    break;
+                }
+
+                let rows = match Self::poll(&db, &cursor).await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        println!("MySQL Pub/Sub Error: {:?}", e);
+                        continue;
+                    }
+                };
+                let now = Instant::now();
+                for (id, payload) in rows {
+                    if !cursor.accept(id, now) {
+                        continue;
+                    }
+                    match serde_json::from_str::<Envelope>(&payload) {
+                        Ok(envelope) => {
+                            if tx.send(envelope).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => println!("MySQL Pub/Sub Payload Error: {:?}", e),
+                    }
+                }
+                cursor.advance(now, MYSQL_GAP_GRACE);
+
+                polls += 1;
+                if polls.is_multiple_of(100) {
+                    let pruned = Self::prune(&db).await;
+                    if let Err(e) = pruned {
+                        println!("MySQL Pub/Sub Error: {:?}", e);
+                    }
+                }
+            }
+        });
+        envelopes
+    }
+}
+
+// reply of a Redis server
+#[derive(Debug, PartialEq)]
+pub enum RespValue {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<RespValue>>),
+}
+
+// function for encoding a Redis command as an array of bulk strings
+pub fn encode_command(args: &[&[u8]]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        command.extend_from_slice(arg);
+        command.extend_from_slice(b"\r\n");
+    }
+
+    command
+}
+
+// function for reading one reply of a Redis server
+pub fn read_resp<'a, R>(reader: &'a mut R) -> BoxFuture<'a, io::Result<RespValue>>
+where
+    R: AsyncBufRead + Unpin + Send,
+{
+    Box::pin(async move {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "redis closed the connection",
+            ));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid redis reply");
+        let (kind, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+
+        match kind {
+            "+" => Ok(RespValue::Simple(rest.to_string())),
+            "-" => Ok(RespValue::Error(rest.to_string())),
+            ":" => Ok(RespValue::Integer(rest.parse().map_err(|_| invalid())?)),
+            "$" => {
+                let len: i64 = rest.parse().map_err(|_| invalid())?;
+                if len < 0 {
+                    return Ok(RespValue::Bulk(None));
+                }
+                // the value is followed by \r\n
+                let mut data = vec![0u8; len as usize + 2];
+                reader.read_exact(&mut data).await?;
+                data.truncate(len as usize);
+                Ok(RespValue::Bulk(Some(data)))
+            }
+            "*" => {
+                let len: i64 = rest.parse().map_err(|_| invalid())?;
+                if len < 0 {
+                    return Ok(RespValue::Array(None));
+                }
+                let mut items = Vec::with_capacity(len as usize);
+                for _ in 0..len {
+                    items.push(read_resp(reader).await?);
+                }
+                Ok(RespValue::Array(Some(items)))
+            }
+            _ => Err(invalid()),
+        }
+    })
+}
+
+// function for getting the payload of a message pushed to a Redis subscriber
+pub fn pushed_message(value: RespValue) -> Option<Vec<u8>> {
+    let RespValue::Array(Some(mut items)) = value else {
+        return None;
+    };
+    if items.len() != 3 || items[0] != RespValue::Bulk(Some(b"message".to_vec())) {
+        return None;
+    }
+
+    match items.pop() {
+        Some(RespValue::Bulk(Some(payload))) => Some(payload),
+        _ => None,
+    }
+}
+
+// transport over Redis PUBLISH and SUBSCRIBE, one connection publishes and each subscriber has its own
+pub struct RedisPubSub {
+    pub address: String,
+    pub channel: String,
+    connection: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl RedisPubSub {
+    pub fn new(address: &str, channel: &str) -> Self {
+        RedisPubSub {
+            address: address.trim_start_matches("redis://").to_string(),
+            channel: channel.to_string(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    // function for opening a subscription and forwarding its messages until it fails
+    async fn listen(address: &str, channel: &str, tx: &mpsc::Sender<Envelope>) -> io::Result<()> {
+        let mut stream = BufStream::new(TcpStream::connect(address).await?);
+        stream
+            .write_all(&encode_command(&[b"SUBSCRIBE", channel.as_bytes()]))
+            .await?;
+        stream.flush().await?;
+
+        loop {
+            let Some(payload) = pushed_message(read_resp(&mut stream).await?) else {
+                // the confirmation of the subscription
+                continue;
+            };
+            match serde_json::from_slice::<Envelope>(&payload) {
+                Ok(envelope) => {
+                    if tx.send(envelope).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Err(e) => println!("Redis Pub/Sub Payload Error: {:?}", e),
+            }
+        }
+    }
+}
+
+impl PubSub for RedisPubSub {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let payload = serde_json::to_vec(envelope).map_err(io::Error::other)?;
+            let mut connection = self.connection.lock().await;
+
+            let publish = async {
+                if connection.is_none() {
+                    *connection = Some(BufStream::new(TcpStream::connect(&self.address).await?));
+                }
+                let stream = connection.as_mut().unwrap();
+                stream
+                    .write_all(&encode_command(&[
+                        b"PUBLISH",
+                        self.channel.as_bytes(),
+                        &payload,
+                    ]))
+                    .await?;
+                stream.flush().await?;
+
+                match read_resp(stream).await? {
+                    RespValue::Error(e) => Err(io::Error::other(e)),
+                    _ => Ok(()),
+                }
+            };
+            let published = publish.await;
+
+            // a broken connection is opened again by the next publish
+            if published.is_err() {
+                *connection = None;
+            }
+            published
+        })
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<Envelope> {
+        let (address, channel) = (self.address.clone(), self.channel.clone());
+        let (tx, envelopes) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+        tokio::spawn(async move {
+            while !tx.is_closed() {
+                if let Err(e) = Self::listen(&address, &channel, &tx).await {
+                    println!("Redis Pub/Sub Error: {:?}", e);
+                }
+                tokio::time::sleep(REDIS_RECONNECT_DELAY).await;
+            }
+        });
+        envelopes
+    }
+}
@@ -1,4 +1,7 @@
 use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use validator::ValidationErrors;
 
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
@@ -25,3 +28,26 @@ impl<T> ApiResponse<T> {
         }
     }
 }
+
+impl ApiResponse<Value> {
+    // function for collecting validation errors per field
+    pub fn validation(errors: &ValidationErrors) -> Self {
+        let mut field_errors: HashMap<String, Vec<String>> = HashMap::new();
+
+        for (field, errors) in errors.field_errors() {
+            let message = errors
+                .iter()
+                .filter_map(|e| e.message.as_ref())
+                .map(|m| m.to_string())
+                .collect::<Vec<String>>();
+
+            field_errors.insert(field.to_string(), message);
+        }
+
+        Self {
+            status: false,
+            message: "Validation failed".to_string(),
+            data: Some(json!(field_errors)),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// longest validity AWS accepts for a presigned url, seven days
+pub const MAX_PRESIGN_EXPIRES: u64 = 7 * 24 * 3600;
+
+pub struct S3Credentials<'a> {
+    pub access_key: &'a str,
+    pub secret_key: &'a str,
+    pub region: &'a str,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// function for percent-encoding a value the way SigV4 canonicalizes it, slashes are kept in paths
+pub fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+// function for getting the Host header of an endpoint, default ports are left out like clients do
+pub fn endpoint_host(endpoint: &str) -> &str {
+    let (scheme, rest) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+    let host = rest.split('/').next().unwrap_or_default();
+
+    match (scheme, host.rsplit_once(':')) {
+        ("http", Some((name, "80"))) | ("https", Some((name, "443"))) => name,
+        _ => host,
+    }
+}
+
+// function for building a SigV4 presigned url, only the host header is signed and the payload
+// is unsigned so the holder can send any body, path is the unencoded absolute object path
+pub fn presign(
+    method: &str,
+    endpoint: &str,
+    path: &str,
+    query: &[(&str, &str)],
+    credentials: &S3Credentials,
+    now: DateTime<Utc>,
+    expires: u64,
+) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+
+    let mut params = query
+        .iter()
+        .map(|(key, value)| (uri_encode(key, false), uri_encode(value, false)))
+        .collect::<Vec<(String, String)>>();
+    params.extend([
+        (
+            "X-Amz-Algorithm".to_string(),
+            "AWS4-HMAC-SHA256".to_string(),
+        ),
+        (
+            "X-Amz-Credential".to_string(),
+            uri_encode(&format!("{}/{}", credentials.access_key, scope), false),
+        ),
+        ("X-Amz-Date".to_string(), timestamp.clone()),
+        (
+            "X-Amz-Expires".to_string(),
+            expires.min(MAX_PRESIGN_EXPIRES).to_string(),
+        ),
+        ("X-Amz-SignedHeaders".to_string(), "host".to_string()),
+    ]);
+    params.sort();
+    let canonical_query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<String>>()
+        .join("&");
+    let canonical_path = uri_encode(path, true);
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+        method,
+        canonical_path,
+        canonical_query,
+        endpoint_host(endpoint)
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = hmac(format!("AWS4{}", credentials.secret_key).as_bytes(), &date);
+    let key = hmac(&key, credentials.region);
+    let key = hmac(&key, "s3");
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    format!(
+        "{}{}?{}&X-Amz-Signature={}",
+        endpoint.trim_end_matches('/'),
+        canonical_path,
+        canonical_query,
+        signature
+    )
+}
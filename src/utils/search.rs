@@ -0,0 +1,152 @@
+// characters of context kept on each side of the first match in a snippet
+pub const SNIPPET_RADIUS: usize = 80;
+
+// markers wrapped around matched words, everything else is HTML escaped
+pub const HIGHLIGHT_START: &str = "<mark>";
+pub const HIGHLIGHT_END: &str = "</mark>";
+
+// function for folding a character for case-insensitive matching
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// function for splitting a search query into the distinct lowercase words that are highlighted
+pub fn search_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+
+    for word in query.split(|c: char| !c.is_alphanumeric()) {
+        let term = word.chars().map(fold).collect::<String>();
+        if !term.is_empty() && !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+
+    terms
+}
+
+// function for finding whole-word matches of the terms, returned as char ranges in text order
+fn find_matches(chars: &[char], terms: &[String]) -> Vec<(usize, usize)> {
+    let folded = chars.iter().map(|c| fold(*c)).collect::<Vec<char>>();
+    let mut terms = terms
+        .iter()
+        .map(|term| term.chars().collect::<Vec<char>>())
+        .filter(|term| !term.is_empty())
+        .collect::<Vec<Vec<char>>>();
+    // prefer the longest term when several start at the same word
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < folded.len() {
+        let at_word_start = i == 0 || !folded[i - 1].is_alphanumeric();
+        let matched = at_word_start
+            .then(|| {
+                terms.iter().find(|term| {
+                    folded[i..].starts_with(term)
+                        && folded
+                            .get(i + term.len())
+                            .is_none_or(|next| !next.is_alphanumeric())
+                })
+            })
+            .flatten();
+
+        match matched {
+            Some(term) => {
+                matches.push((i, i + term.len()));
+                i += term.len();
+            }
+            None => i += 1,
+        }
+    }
+
+    matches
+}
+
+// function for escaping text placed in HTML
+fn push_escaped(output: &mut String, chars: &[char]) {
+    for c in chars {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(*c),
+        }
+    }
+}
+
+// function for HTML escaping chars and wrapping the given matches in highlight markers
+fn highlight_chars(chars: &[char], matches: &[(usize, usize)]) -> String {
+    let mut output = String::new();
+    let mut position = 0;
+
+    for (start, end) in matches {
+        push_escaped(&mut output, &chars[position..*start]);
+        output.push_str(HIGHLIGHT_START);
+        push_escaped(&mut output, &chars[*start..*end]);
+        output.push_str(HIGHLIGHT_END);
+        position = *end;
+    }
+    push_escaped(&mut output, &chars[position..]);
+
+    output
+}
+
+// function for HTML escaping text and highlighting every whole-word match of the terms
+pub fn highlight(text: &str, terms: &[String]) -> String {
+    let chars = text.chars().collect::<Vec<char>>();
+    let matches = find_matches(&chars, terms);
+
+    highlight_chars(&chars, &matches)
+}
+
+// function for cutting a highlighted excerpt around the first match of the terms,
+// None when the text does not contain any of them
+pub fn snippet(text: &str, terms: &[String], radius: usize) -> Option<String> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let matches = find_matches(&chars, terms);
+    let (first_start, first_end) = *matches.first()?;
+
+    // widen to the radius, then shrink to whole words unless the text ends there
+    let mut start = first_start.saturating_sub(radius);
+    let mut end = (first_end + radius).min(chars.len());
+    if start > 0 {
+        while start < first_start && !chars[start - 1].is_whitespace() {
+            start += 1;
+        }
+    }
+    if end < chars.len() {
+        while end > first_end && !chars[end].is_whitespace() {
+            end -= 1;
+        }
+    }
+
+    let window = matches
+        .iter()
+        .filter(|(match_start, match_end)| *match_start >= start && *match_end <= end)
+        .map(|(match_start, match_end)| (match_start - start, match_end - start))
+        .collect::<Vec<(usize, usize)>>();
+    let excerpt = highlight_chars(&chars[start..end], &window);
+
+    Some(format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        excerpt.trim(),
+        if end < chars.len() { "…" } else { "" }
+    ))
+}
+
+// function for escaping the wildcards of a LIKE pattern so a keyword matches literally,
+// backslash is the default escape character of MySQL
+pub fn escape_like(keyword: &str) -> String {
+    let mut escaped = String::with_capacity(keyword.len());
+    for c in keyword.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
@@ -0,0 +1,89 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// function for getting the secret used to sign download urls (DOWNLOAD_URL_SECRET, falls back to JWT_SECRET)
+fn signing_secret() -> String {
+    env::var("DOWNLOAD_URL_SECRET")
+        .or_else(|_| env::var("JWT_SECRET"))
+        .unwrap_or_else(|_| "secret".to_string())
+}
+
+// function for getting how long a signed download url stays valid (DOWNLOAD_URL_TTL, seconds)
+pub fn download_url_ttl() -> i64 {
+    env::var("DOWNLOAD_URL_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse::<i64>().ok())
+        .filter(|ttl| *ttl > 0)
+        .unwrap_or(300)
+}
+
+// function for creating the mac over a path and its expiry
+fn mac_for(secret: &str, path: &str, expires: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+// function for signing a path with a given secret (hex encoded)
+pub fn sign_with(secret: &str, path: &str, expires: i64) -> String {
+    mac_for(secret, path, expires)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// function for checking a signature in constant time, expired urls are always rejected
+pub fn verify_with(secret: &str, path: &str, expires: i64, signature: &str, now: i64) -> bool {
+    if expires < now {
+        return false;
+    }
+
+    match decode_hex(signature) {
+        Some(bytes) => mac_for(secret, path, expires).verify_slice(&bytes).is_ok(),
+        None => false,
+    }
+}
+
+// function for building a signed url for a path that expires after the configured ttl
+pub fn signed_path(path: &str) -> String {
+    let expires = Utc::now().timestamp() + download_url_ttl();
+
+    format!(
+        "{}?expires={}&signature={}",
+        path,
+        expires,
+        sign_with(&signing_secret(), path, expires)
+    )
+}
+
+// function for verifying a signed url against the configured secret
+pub fn verify(path: &str, expires: i64, signature: &str) -> bool {
+    verify_with(
+        &signing_secret(),
+        path,
+        expires,
+        signature,
+        Utc::now().timestamp(),
+    )
+}
+
+// function for decoding a hex string, returns None when it is not valid hex
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.is_ascii() {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
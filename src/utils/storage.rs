@@ -0,0 +1,196 @@
+use crate::config::storage::{S3, S3Config, s3_config};
+use crate::utils::s3_presign::{S3Credentials, presign};
+use chrono::Utc;
+use reqwest::multipart;
+use sha1::{Digest, Sha1};
+use std::env;
+
+// function for signing cloudinary request parameters
+pub fn generate_signature(params: &[(&str, String)], api_secret: &str) -> String {
+    let mut sorted = params.to_vec();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+
+    let param_string = sorted
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{}{}", param_string, api_secret));
+
+    format!("{:x}", hasher.finalize())
+}
+
+// function for deleting a cloudinary resource
+pub async fn destroy_cloudinary(
+    public_id: &str,
+    resource_type: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cloud_name = env::var("CLOUDINARY_CLOUD_NAME").unwrap();
+    let api_key = env::var("CLOUDINARY_API_KEY").unwrap();
+    let api_secret = env::var("CLOUDINARY_API_SECRET").unwrap();
+
+    let timestamp = Utc::now().timestamp();
+    let signature = generate_signature(
+        &[
+            ("public_id", public_id.to_string()),
+            ("timestamp", timestamp.to_string()),
+        ],
+        &api_secret,
+    );
+
+    let url = format!(
+        "https://api.cloudinary.com/v1_1/{}/{}/destroy",
+        cloud_name, resource_type
+    );
+
+    let client = reqwest::Client::new();
+
+    let form = multipart::Form::new()
+        .text("public_id", public_id.to_string())
+        .text("timestamp", timestamp.to_string())
+        .text("api_key", api_key)
+        .text("signature", signature);
+
+    let res = client.post(url).multipart(form).send().await?;
+
+    // handle error
+    if res.status().is_client_error() || res.status().is_server_error() {
+        return Err(format!("Cloudinary Error: {}", res.status()).into());
+    }
+
+    Ok(())
+}
+
+// how long the presigned urls the server sends itself stay valid
+pub const S3_REQUEST_TTL: u64 = 300;
+
+// function for reading the S3 settings or failing when they are missing
+pub fn require_s3() -> Result<S3Config, Box<dyn std::error::Error>> {
+    s3_config().ok_or_else(|| "S3 storage is not configured".into())
+}
+
+// function for finding the key of a stored file url, None for files kept outside the bucket,
+// keys are generated from unreserved characters only so they need no decoding
+pub fn s3_key_for_url(config: &S3Config, url: &str) -> Option<String> {
+    url.strip_prefix(&format!("{}/{}/", config.endpoint, config.bucket))
+        .filter(|key| !key.is_empty())
+        .map(|key| key.to_string())
+}
+
+// function for presigning a request on an S3 object
+pub fn s3_presigned_url(
+    config: &S3Config,
+    method: &str,
+    key: &str,
+    query: &[(&str, &str)],
+    expires: u64,
+) -> String {
+    presign(
+        method,
+        &config.endpoint,
+        &format!("/{}/{}", config.bucket, key),
+        query,
+        &S3Credentials {
+            access_key: &config.access_key,
+            secret_key: &config.secret_key,
+            region: &config.region,
+        },
+        Utc::now(),
+        expires,
+    )
+}
+
+// function for deleting an S3 object, a missing object counts as deleted
+pub async fn destroy_s3(key: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .delete(s3_presigned_url(
+            &config,
+            "DELETE",
+            key,
+            &[],
+            S3_REQUEST_TTL,
+        ))
+        .send()
+        .await?;
+
+    if res.status() != reqwest::StatusCode::NOT_FOUND
+        && (res.status().is_client_error() || res.status().is_server_error())
+    {
+        return Err(format!("S3 Error: {}", res.status()).into());
+    }
+
+    Ok(())
+}
+
+// function for aborting a multipart upload so its parts are removed
+pub async fn s3_abort_multipart(
+    key: &str,
+    upload_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = require_s3()?;
+
+    let client = reqwest::Client::new();
+
+    let res = client
+        .delete(s3_presigned_url(
+            &config,
+            "DELETE",
+            key,
+            &[("uploadId", upload_id)],
+            S3_REQUEST_TTL,
+        ))
+        .send()
+        .await?;
+
+    if res.status() != reqwest::StatusCode::NOT_FOUND
+        && (res.status().is_client_error() || res.status().is_server_error())
+    {
+        return Err(format!("S3 Error: {}", res.status()).into());
+    }
+
+    Ok(())
+}
+
+// function for removing what a direct upload left in storage, an unfinished multipart upload
+// is aborted so its parts do not linger
+pub async fn discard_direct_object(
+    backend: &str,
+    key: &str,
+    resource_type: &str,
+    multipart_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if backend != S3 {
+        return destroy_cloudinary(key, resource_type).await;
+    }
+
+    if let Some(multipart_id) = multipart_id {
+        s3_abort_multipart(key, multipart_id).await?;
+    }
+    destroy_s3(key).await
+}
+
+// function for deleting a storage object, failures are only logged
+pub async fn destroy_storage_object(public_id: Option<&str>, resource_type: &str, file_id: &str) {
+    // Objects in the S3 bucket are addressed by their url, whatever the current backend is
+    if let Some(key) = s3_config().and_then(|config| s3_key_for_url(&config, file_id)) {
+        if let Err(e) = destroy_s3(&key).await {
+            println!("Failed to delete storage object {}: {}", key, e);
+        }
+        return;
+    }
+
+    match public_id {
+        Some(public_id) => {
+            if let Err(e) = destroy_cloudinary(public_id, resource_type).await {
+                println!("Failed to delete storage object {}: {}", public_id, e);
+            }
+        }
+        None => println!("Storage object without public id kept: {}", file_id),
+    }
+}
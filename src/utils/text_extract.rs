@@ -0,0 +1,157 @@
+use std::io::{Cursor, Read};
+
+// largest file text is extracted from, bigger files are only searchable by name
+pub const TEXT_EXTRACT_MAX_SOURCE_SIZE: u64 = 50 * 1024 * 1024;
+// upper bound of the text stored for one file
+pub const MAX_EXTRACTED_TEXT_LEN: usize = 1024 * 1024;
+// upper bound of the uncompressed document.xml read from a DOCX file
+const DOCX_MAX_XML_SIZE: u64 = 32 * 1024 * 1024;
+
+pub const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+// function for checking text can be extracted from a MIME type
+pub fn is_extractable(mime_type: &str) -> bool {
+    matches!(mime_type, "text/plain" | "application/pdf" | DOCX)
+}
+
+// function for extracting the searchable text of a file, whitespace is collapsed and the
+// result is cut at MAX_EXTRACTED_TEXT_LEN, None when the file has no readable text
+pub fn extract_text(bytes: &[u8], mime_type: &str) -> Option<String> {
+    let text = match mime_type {
+        "text/plain" => String::from_utf8_lossy(bytes).into_owned(),
+        "application/pdf" => pdf_text(bytes)?,
+        DOCX => docx_text(bytes)?,
+        _ => return None,
+    };
+
+    let text = collapse_whitespace(&text, MAX_EXTRACTED_TEXT_LEN);
+    (!text.is_empty()).then_some(text)
+}
+
+// function for extracting the text of every page of a PDF, unreadable pages are skipped
+fn pdf_text(bytes: &[u8]) -> Option<String> {
+    let document = lopdf::Document::load_mem(bytes).ok()?;
+    if document.is_encrypted() {
+        return None;
+    }
+
+    let mut text = String::new();
+    for page_number in document.get_pages().into_keys() {
+        if let Ok(page) = document.extract_text(&[page_number]) {
+            text.push_str(&page);
+            text.push('\n');
+        }
+        if text.len() >= MAX_EXTRACTED_TEXT_LEN {
+            break;
+        }
+    }
+
+    Some(text)
+}
+
+// function for extracting the body text of a DOCX file
+fn docx_text(bytes: &[u8]) -> Option<String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).ok()?;
+    let entry = archive.by_name("word/document.xml").ok()?;
+
+    let mut xml = String::new();
+    entry
+        .take(DOCX_MAX_XML_SIZE)
+        .read_to_string(&mut xml)
+        .ok()?;
+
+    Some(docx_xml_text(&xml))
+}
+
+// function for collecting the text runs of a WordprocessingML document,
+// paragraphs, breaks and tabs become whitespace
+pub fn docx_xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_text = false;
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        if in_text {
+            text.push_str(&decode_xml_entities(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+
+        match name {
+            "w:t" => in_text = !closing && !tag.ends_with('/'),
+            "w:p" if closing => text.push('\n'),
+            "w:tab" => text.push('\t'),
+            "w:br" | "w:cr" => text.push('\n'),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    text
+}
+
+// function for decoding the predefined and numeric XML entities
+fn decode_xml_entities(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let character = entity.and_then(|(entity, _)| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        });
+
+        match (character, entity) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+// function for collapsing runs of whitespace into single spaces, stopping at max_len bytes
+pub fn collapse_whitespace(text: &str, max_len: usize) -> String {
+    let mut collapsed = String::new();
+
+    for word in text.split_whitespace() {
+        let separator = usize::from(!collapsed.is_empty());
+        if collapsed.len() + separator + word.len() > max_len {
+            break;
+        }
+        if separator == 1 {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(word);
+    }
+
+    collapsed
+}
@@ -0,0 +1,190 @@
+use crate::schemas::upload_schema::JanitorReport;
+use crate::utils::storage::discard_direct_object;
+use crate::utils::upload_path::{MERGED_DIR, chunk_dir, upload_root, validate_upload_id};
+use sqlx::MySqlPool;
+use std::env;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+// function for parsing a number of seconds, anything that is not one falls back to the default
+pub fn parse_seconds(value: Option<&str>, default: u64) -> Duration {
+    Duration::from_secs(
+        value
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .unwrap_or(default),
+    )
+}
+
+// function for getting how long an idle upload session is kept (UPLOAD_SESSION_TTL, seconds)
+pub fn upload_session_ttl() -> Duration {
+    parse_seconds(env::var("UPLOAD_SESSION_TTL").ok().as_deref(), 86400)
+}
+
+// function for getting how often the janitor runs (UPLOAD_JANITOR_INTERVAL, seconds)
+pub fn janitor_interval() -> Duration {
+    parse_seconds(env::var("UPLOAD_JANITOR_INTERVAL").ok().as_deref(), 3600)
+}
+
+// function for starting the janitor as a background task
+pub fn spawn(db: MySqlPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(janitor_interval());
+
+        loop {
+            interval.tick().await;
+
+            match run_once(&db).await {
+                Ok(report) => {
+                    if report.reclaimed_bytes > 0
+                        || report.expired_sessions > 0
+                        || report.orphaned_chunk_dirs > 0
+                        || report.orphaned_merged_files > 0
+                        || report.expired_direct_uploads > 0
+                    {
+                        println!(
+                            "Upload janitor: expired {} sessions and {} direct uploads, removed {} orphaned chunk dirs and {} merged files, reclaimed {} bytes",
+                            report.expired_sessions,
+                            report.expired_direct_uploads,
+                            report.orphaned_chunk_dirs,
+                            report.orphaned_merged_files,
+                            report.reclaimed_bytes
+                        );
+                    }
+                }
+                Err(e) => println!("Upload janitor failed: {}", e),
+            }
+        }
+    });
+}
+
+// function for expiring idle upload sessions and removing orphaned files once
+pub async fn run_once(db: &MySqlPool) -> Result<JanitorReport, sqlx::Error> {
+    sweep(db, &upload_root(), upload_session_ttl()).await
+}
+
+// function for expiring the sessions idle for longer than ttl and the files under root they leave behind
+pub async fn sweep(
+    db: &MySqlPool,
+    root: &Path,
+    ttl: Duration,
+) -> Result<JanitorReport, sqlx::Error> {
+    let mut report = JanitorReport::default();
+
+    // Expire sessions that have not received a chunk within the TTL
+    let expired = sqlx::query!(
+        "SELECT id FROM upload_sessions WHERE updated_at < NOW() - INTERVAL ? SECOND",
+        ttl.as_secs()
+    )
+    .fetch_all(db)
+    .await?;
+
+    for session in expired {
+        if let Ok(dir) = chunk_dir(root, &session.id) {
+            report.reclaimed_bytes += remove_dir(&dir).await;
+        }
+
+        sqlx::query!("DELETE FROM upload_sessions WHERE id = ?", session.id)
+            .execute(db)
+            .await?;
+        report.expired_sessions += 1;
+    }
+
+    // Expire direct uploads that were never confirmed, whatever reached storage is removed
+    let expired = sqlx::query!(
+        "
+        SELECT id, backend, object_key, resource_type, multipart_id
+        FROM direct_uploads
+        WHERE expires_at < NOW()
+        "
+    )
+    .fetch_all(db)
+    .await?;
+
+    for upload in expired {
+        if let Err(e) = discard_direct_object(
+            &upload.backend,
+            &upload.object_key,
+            &upload.resource_type,
+            upload.multipart_id.as_deref(),
+        )
+        .await
+        {
+            // Keep the row so the next run tries again
+            println!("Failed to discard direct upload {}: {}", upload.id, e);
+            continue;
+        }
+
+        sqlx::query!("DELETE FROM direct_uploads WHERE id = ?", upload.id)
+            .execute(db)
+            .await?;
+        report.expired_direct_uploads += 1;
+    }
+
+    // Remove chunk directories that no session owns anymore
+    if let Ok(mut read_dir) = fs::read_dir(root).await {
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !file_type.is_dir() || validate_upload_id(&name).is_err() {
+                continue;
+            }
+            if !is_older_than(&entry.path(), ttl).await {
+                continue;
+            }
+
+            let session = sqlx::query!("SELECT id FROM upload_sessions WHERE id = ?", name)
+                .fetch_optional(db)
+                .await?;
+            if session.is_none() {
+                report.reclaimed_bytes += remove_dir(&entry.path()).await;
+                report.orphaned_chunk_dirs += 1;
+            }
+        }
+    }
+
+    // Remove merged files left behind by failed or interrupted completions
+    if let Ok(mut read_dir) = fs::read_dir(root.join(MERGED_DIR)).await {
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            if !is_older_than(&entry.path(), ttl).await {
+                continue;
+            }
+
+            let size = entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+            if fs::remove_file(entry.path()).await.is_ok() {
+                report.reclaimed_bytes += size;
+                report.orphaned_merged_files += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// function for checking a path was last modified more than ttl ago
+async fn is_older_than(path: &Path, ttl: Duration) -> bool {
+    fs::metadata(path)
+        .await
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > ttl)
+}
+
+// function for removing a directory, returns the number of bytes it held
+async fn remove_dir(dir: &Path) -> u64 {
+    let mut size = 0;
+
+    if let Ok(mut read_dir) = fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            size += entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+        }
+    }
+
+    match fs::remove_dir_all(dir).await {
+        Ok(_) => size,
+        Err(_) => 0,
+    }
+}
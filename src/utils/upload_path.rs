@@ -0,0 +1,130 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+// maximum length of a client supplied upload id
+const MAX_UPLOAD_ID_LEN: usize = 64;
+// maximum length of a file extension
+const MAX_EXTENSION_LEN: usize = 10;
+// maximum length of the stored file name stem
+const MAX_FILE_STEM_LEN: usize = 100;
+// directory under the upload root holding merged files, upload ids can never contain '.'
+pub const MERGED_DIR: &str = ".merged";
+// directory under the upload root holding infected files, skipped by the janitor like MERGED_DIR
+pub const QUARANTINE_DIR: &str = ".quarantine";
+
+// function for getting the upload root directory (UPLOAD_DIR, default "uploads")
+pub fn upload_root() -> PathBuf {
+    PathBuf::from(env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string()))
+}
+
+// function for validating an upload id, only ASCII letters, digits, '-' and '_' are allowed
+pub fn validate_upload_id(id: &str) -> Result<&str, String> {
+    if id.is_empty() || id.len() > MAX_UPLOAD_ID_LEN {
+        return Err(format!(
+            "Upload ID harus 1 sampai {} karakter",
+            MAX_UPLOAD_ID_LEN
+        ));
+    }
+
+    if !id
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err("Upload ID hanya boleh berisi huruf, angka, '-' dan '_'".to_string());
+    }
+
+    Ok(id)
+}
+
+// function for generating a random upload ID, 32 hex characters that pass validate_upload_id
+pub fn generate_upload_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("operating system random number generator");
+
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// function for parsing a chunk index, only plain ASCII digits are allowed
+pub fn parse_chunk_index(value: &str) -> Result<u32, String> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Chunk index harus berupa angka".to_string());
+    }
+
+    value
+        .parse::<u32>()
+        .map_err(|_| "Chunk index terlalu besar".to_string())
+}
+
+// function for validating a file extension, returns it lowercased without the leading dot
+pub fn validate_extension(extension: &str) -> Result<String, String> {
+    let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+    if extension.is_empty()
+        || extension.len() > MAX_EXTENSION_LEN
+        || !extension.bytes().all(|b| b.is_ascii_alphanumeric())
+    {
+        return Err("Ekstensi file tidak valid".to_string());
+    }
+
+    Ok(extension.to_ascii_lowercase())
+}
+
+// function for turning a display name into a safe file name stem
+pub fn sanitize_file_stem(name: &str) -> String {
+    let stem = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(MAX_FILE_STEM_LEN)
+        .collect::<String>();
+    let stem = stem.trim_matches('_');
+
+    if stem.is_empty() {
+        "file".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+// function for joining a relative path under root, rejecting anything that could escape it
+pub fn resolve_under(root: &Path, relative: &Path) -> Result<PathBuf, String> {
+    if relative.as_os_str().is_empty() {
+        return Err("Path kosong".to_string());
+    }
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) if part.to_str().is_some() => {}
+            _ => return Err(format!("Path tidak valid: {}", relative.display())),
+        }
+    }
+
+    Ok(root.join(relative))
+}
+
+// function for getting the chunk directory of an upload
+pub fn chunk_dir(root: &Path, upload_id: &str) -> Result<PathBuf, String> {
+    resolve_under(root, Path::new(validate_upload_id(upload_id)?))
+}
+
+// function for getting the path of a single chunk
+pub fn chunk_path(root: &Path, upload_id: &str, chunk_index: u32) -> Result<PathBuf, String> {
+    Ok(chunk_dir(root, upload_id)?.join(format!("chunk_{}", chunk_index)))
+}
+
+// function for getting the path of the merged file, the upload id keeps concurrent uploads
+// of the same name apart
+pub fn merged_path(
+    root: &Path,
+    name: &str,
+    upload_id: &str,
+    extension: &str,
+) -> Result<PathBuf, String> {
+    let file_name = format!(
+        "{}-{}.{}",
+        sanitize_file_stem(name),
+        validate_upload_id(upload_id)?,
+        validate_extension(extension)?
+    );
+
+    resolve_under(root, Path::new(&file_name))
+}
@@ -0,0 +1,351 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::collections::HashSet;
+use std::io;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+
+// sizes and CRC follow the data in a descriptor, names are UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+// made by unix so the external attributes carry permissions
+const MADE_BY_UNIX: u16 = 3 << 8;
+// regular file, rw-r--r--
+const EXTERNAL_ATTRIBUTES: u32 = 0o100644 << 16;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+// placeholder written to a 32-bit field whose value is in the ZIP64 extra field
+const ZIP64_MARKER: u32 = u32::MAX;
+
+// longest entry name in characters, the extension is kept
+const MAX_ENTRY_NAME_LEN: usize = 200;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    time: u16,
+    date: u16,
+    zip64: bool,
+}
+
+// encoder for a ZIP archive written front to back without seeking, entries are stored
+// uncompressed and their CRC and size follow the data, so nothing is buffered
+pub struct ZipStream {
+    entries: Vec<Entry>,
+    offset: u64,
+    current: Option<(Entry, crc32fast::Hasher)>,
+}
+
+impl Default for ZipStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZipStream {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            offset: 0,
+            current: None,
+        }
+    }
+
+    // function for starting an entry, returns its local header,
+    // size_hint decides whether the entry needs ZIP64 sizes
+    pub fn start_entry(&mut self, name: &str, modified: NaiveDateTime, size_hint: u64) -> Vec<u8> {
+        let (time, date) = dos_date_time(modified);
+        let zip64 = size_hint >= u64::from(ZIP64_MARKER);
+        let entry = Entry {
+            name: name.to_string(),
+            crc: 0,
+            size: 0,
+            offset: self.offset,
+            time,
+            date,
+            zip64,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(
+            &mut header,
+            if zip64 {
+                VERSION_ZIP64
+            } else {
+                VERSION_DEFAULT
+            },
+        );
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, 0);
+        put_u16(&mut header, time);
+        put_u16(&mut header, date);
+        // CRC and sizes are unknown until the data has been written
+        put_u32(&mut header, 0);
+        if zip64 {
+            put_u32(&mut header, ZIP64_MARKER);
+            put_u32(&mut header, ZIP64_MARKER);
+        } else {
+            put_u32(&mut header, 0);
+            put_u32(&mut header, 0);
+        }
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, if zip64 { 20 } else { 0 });
+        header.extend_from_slice(name.as_bytes());
+        if zip64 {
+            put_u16(&mut header, ZIP64_EXTRA_ID);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, 0);
+            put_u64(&mut header, 0);
+        }
+
+        self.offset += header.len() as u64;
+        self.current = Some((entry, crc32fast::Hasher::new()));
+        header
+    }
+
+    // function for accounting data of the current entry, the caller sends the bytes itself
+    pub fn write(&mut self, data: &[u8]) {
+        if let Some((entry, hasher)) = self.current.as_mut() {
+            hasher.update(data);
+            entry.size += data.len() as u64;
+            self.offset += data.len() as u64;
+        }
+    }
+
+    // function for finishing the current entry, returns its data descriptor
+    pub fn finish_entry(&mut self) -> io::Result<Vec<u8>> {
+        let Some((mut entry, hasher)) = self.current.take() else {
+            return Err(io::Error::other("no entry has been started"));
+        };
+        entry.crc = hasher.finalize();
+        if !entry.zip64 && entry.size >= u64::from(ZIP64_MARKER) {
+            return Err(io::Error::other(format!(
+                "{} is larger than announced",
+                entry.name
+            )));
+        }
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, DATA_DESCRIPTOR_SIGNATURE);
+        put_u32(&mut descriptor, entry.crc);
+        if entry.zip64 {
+            put_u64(&mut descriptor, entry.size);
+            put_u64(&mut descriptor, entry.size);
+        } else {
+            put_u32(&mut descriptor, entry.size as u32);
+            put_u32(&mut descriptor, entry.size as u32);
+        }
+
+        self.offset += descriptor.len() as u64;
+        self.entries.push(entry);
+        Ok(descriptor)
+    }
+
+    // function for ending the archive, returns the central directory and end records
+    pub fn finish(self) -> Vec<u8> {
+        let directory_offset = self.offset;
+        let mut directory = Vec::new();
+
+        for entry in &self.entries {
+            let large_size = entry.zip64 || entry.size >= u64::from(ZIP64_MARKER);
+            let large_offset = entry.offset >= u64::from(ZIP64_MARKER);
+            let mut extra = Vec::new();
+            if large_size {
+                put_u64(&mut extra, entry.size);
+                put_u64(&mut extra, entry.size);
+            }
+            if large_offset {
+                put_u64(&mut extra, entry.offset);
+            }
+            let version = if extra.is_empty() {
+                VERSION_DEFAULT
+            } else {
+                VERSION_ZIP64
+            };
+            let size = if large_size {
+                ZIP64_MARKER
+            } else {
+                entry.size as u32
+            };
+
+            put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut directory, MADE_BY_UNIX | version);
+            put_u16(&mut directory, version);
+            put_u16(&mut directory, FLAGS);
+            put_u16(&mut directory, 0);
+            put_u16(&mut directory, entry.time);
+            put_u16(&mut directory, entry.date);
+            put_u32(&mut directory, entry.crc);
+            put_u32(&mut directory, size);
+            put_u32(&mut directory, size);
+            put_u16(&mut directory, entry.name.len() as u16);
+            put_u16(
+                &mut directory,
+                if extra.is_empty() {
+                    0
+                } else {
+                    extra.len() as u16 + 4
+                },
+            );
+            put_u16(&mut directory, 0);
+            put_u16(&mut directory, 0);
+            put_u16(&mut directory, 0);
+            put_u32(&mut directory, EXTERNAL_ATTRIBUTES);
+            put_u32(
+                &mut directory,
+                if large_offset {
+                    ZIP64_MARKER
+                } else {
+                    entry.offset as u32
+                },
+            );
+            directory.extend_from_slice(entry.name.as_bytes());
+            if !extra.is_empty() {
+                put_u16(&mut directory, ZIP64_EXTRA_ID);
+                put_u16(&mut directory, extra.len() as u16);
+                directory.extend_from_slice(&extra);
+            }
+        }
+
+        let directory_size = directory.len() as u64;
+        let count = self.entries.len() as u64;
+        let zip64 = count >= u64::from(u16::MAX)
+            || directory_size >= u64::from(ZIP64_MARKER)
+            || directory_offset >= u64::from(ZIP64_MARKER);
+
+        if zip64 {
+            let zip64_end_offset = directory_offset + directory_size;
+            put_u32(&mut directory, ZIP64_END_SIGNATURE);
+            put_u64(&mut directory, 44);
+            put_u16(&mut directory, MADE_BY_UNIX | VERSION_ZIP64);
+            put_u16(&mut directory, VERSION_ZIP64);
+            put_u32(&mut directory, 0);
+            put_u32(&mut directory, 0);
+            put_u64(&mut directory, count);
+            put_u64(&mut directory, count);
+            put_u64(&mut directory, directory_size);
+            put_u64(&mut directory, directory_offset);
+
+            put_u32(&mut directory, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut directory, 0);
+            put_u64(&mut directory, zip64_end_offset);
+            put_u32(&mut directory, 1);
+        }
+
+        let count = if zip64 { u16::MAX } else { count as u16 };
+        put_u32(&mut directory, END_SIGNATURE);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, count);
+        put_u16(&mut directory, count);
+        put_u32(
+            &mut directory,
+            if zip64 {
+                ZIP64_MARKER
+            } else {
+                directory_size as u32
+            },
+        );
+        put_u32(
+            &mut directory,
+            if zip64 {
+                ZIP64_MARKER
+            } else {
+                directory_offset as u32
+            },
+        );
+        put_u16(&mut directory, 0);
+
+        directory
+    }
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+// function for converting a timestamp to MS-DOS time and date, which start in 1980
+pub fn dos_date_time(modified: NaiveDateTime) -> (u16, u16) {
+    if modified.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let year = modified.year().min(2107) as u16;
+
+    let time = ((modified.hour() as u16) << 11)
+        | ((modified.minute() as u16) << 5)
+        | (modified.second() as u16 / 2);
+    let date = ((year - 1980) << 9) | ((modified.month() as u16) << 5) | modified.day() as u16;
+
+    (time, date)
+}
+
+// function for turning a document name into a safe archive entry name ending in its extension
+pub fn entry_name(name: &str, extension: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(MAX_ENTRY_NAME_LEN)
+        .collect::<String>();
+    // leading dots would hide the file or walk up with ".."
+    let name = name
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' ']);
+    let name = if name.is_empty() { "file" } else { name };
+
+    let suffix = format!(".{}", extension);
+    if name.to_lowercase().ends_with(&suffix.to_lowercase()) {
+        name.to_string()
+    } else {
+        format!("{}{}", name, suffix)
+    }
+}
+
+// names already used in an archive, compared case-insensitively
+#[derive(Default)]
+pub struct UniqueNames {
+    taken: HashSet<String>,
+}
+
+impl UniqueNames {
+    // function for claiming a name, duplicates get a counter before the extension
+    pub fn claim(&mut self, name: &str) -> String {
+        if self.taken.insert(name.to_lowercase()) {
+            return name.to_string();
+        }
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name, ""),
+        };
+        let mut counter = 1;
+        loop {
+            let candidate = format!("{} ({}){}", stem, counter, extension);
+            if self.taken.insert(candidate.to_lowercase()) {
+                return candidate;
+            }
+            counter += 1;
+        }
+    }
+}